@@ -1,10 +1,15 @@
-#[derive(Debug, Clone)]
+use std::fmt;
+
+#[derive(Clone)]
 pub struct Config {
     pub api_endpoint: Option<String>,
     pub api_client_id: String,
     pub api_client_secret: String,
     pub webhook_secret: String,
     pub master_seed_hex: String,
+    pub node_seeds_hex: Vec<(String, String)>,
+    pub master_seed_file: Option<String>,
+    pub master_seed_passphrase: String,
     pub api_port: u16,
     pub respond_directly: bool,
 }
@@ -16,6 +21,18 @@ impl Config {
         let api_client_secret = std::env::var("API_CLIENT_SECRET").ok();
         let webhook_secret = std::env::var("WEBHOOK_SECRET").ok();
         let master_seed_hex = std::env::var("MASTER_SEED_HEX").ok();
+        // A comma separated list of `<node id>:<master seed hex>` pairs.
+        let node_seeds_hex = std::env::var("NODE_SEEDS_HEX")
+            .map(|seeds| {
+                seeds
+                    .split(',')
+                    .filter_map(|pair| pair.split_once(':'))
+                    .map(|(node_id, seed)| (node_id.trim().to_owned(), seed.trim().to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let master_seed_file = std::env::var("MASTER_SEED_FILE").ok();
+        let master_seed_passphrase = std::env::var("MASTER_SEED_PASSPHRASE").ok();
         let api_port = std::env::var("PORT").ok();
         let respond_directly = std::env::var("RESPOND_DIRECTLY").is_ok();
 
@@ -25,6 +42,9 @@ impl Config {
             api_client_secret: api_client_secret.unwrap_or_default(),
             webhook_secret: webhook_secret.unwrap_or_default(),
             master_seed_hex: master_seed_hex.unwrap_or_default(),
+            node_seeds_hex,
+            master_seed_file,
+            master_seed_passphrase: master_seed_passphrase.unwrap_or_default(),
             api_port: api_port
                 .unwrap_or("8080".to_string())
                 .parse()
@@ -33,3 +53,38 @@ impl Config {
         }
     }
 }
+
+/// Hides a secret, while still showing whether it is set.
+fn redact(secret: &str) -> &'static str {
+    if secret.is_empty() {
+        ""
+    } else {
+        "<redacted>"
+    }
+}
+
+// The config is logged on startup, so the secrets are left out of its debug output.
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let node_seeds_hex: Vec<(&str, &str)> = self
+            .node_seeds_hex
+            .iter()
+            .map(|(node_id, seed)| (node_id.as_str(), redact(seed)))
+            .collect();
+        f.debug_struct("Config")
+            .field("api_endpoint", &self.api_endpoint)
+            .field("api_client_id", &self.api_client_id)
+            .field("api_client_secret", &redact(&self.api_client_secret))
+            .field("webhook_secret", &redact(&self.webhook_secret))
+            .field("master_seed_hex", &redact(&self.master_seed_hex))
+            .field("node_seeds_hex", &node_seeds_hex)
+            .field("master_seed_file", &self.master_seed_file)
+            .field(
+                "master_seed_passphrase",
+                &redact(&self.master_seed_passphrase),
+            )
+            .field("api_port", &self.api_port)
+            .field("respond_directly", &self.respond_directly)
+            .finish()
+    }
+}
@@ -7,15 +7,60 @@ use lightspark::request::auth_provider::AccountAuthProvider;
 use lightspark_remote_signing::lightspark::objects::webhook_event_type::WebhookEventType::RemoteSigning;
 use lightspark_remote_signing::lightspark::webhooks::WebhookEvent;
 use lightspark_remote_signing::{
-    handler::Handler,
-    signer::{LightsparkSigner, Seed},
+    async_handler::AsyncHandler,
+    registry::{KeyProvider, SignerRegistry},
+    signer::{Network, Seed},
     validation::PositiveValidator,
+    Error,
 };
-use tracing::{debug, info, Level};
+use tracing::{debug, error, info, warn, Level};
 use tracing_subscriber::FmtSubscriber;
 
 pub mod config;
 
+/// Provides the seeds listed in `NODE_SEEDS_HEX`, falling back to `MASTER_SEED_HEX`, or to the
+/// encrypted seed file at `MASTER_SEED_FILE`, for the other nodes.
+///
+/// There is no channel to node mapping in the config, so the per-commitment events that do not
+/// carry their node id are only handled when there is a single seed, by routing them to
+/// `MASTER_SEED_NODE_ID`.
+struct ConfigKeyProvider {
+    config: config::Config,
+}
+
+impl KeyProvider for ConfigKeyProvider {
+    fn seed(&self, node_id: &str) -> Result<Option<Seed>, Error> {
+        let seed_hex = self
+            .config
+            .node_seeds_hex
+            .iter()
+            .find(|(id, _)| id == node_id)
+            .map(|(_, seed_hex)| seed_hex)
+            .or(Some(&self.config.master_seed_hex).filter(|seed_hex| !seed_hex.is_empty()));
+        if let Some(seed_hex) = seed_hex {
+            return hex::decode(seed_hex)
+                .map(|seed| Some(Seed::new(seed)))
+                .map_err(|_| Error::HexEncodingError);
+        }
+        self.config
+            .master_seed_file
+            .as_ref()
+            .map(|path| Seed::load_encrypted(path, &self.config.master_seed_passphrase))
+            .transpose()
+            .map_err(Error::SignerError)
+    }
+
+    fn node_id_for_channel(&self, _channel_id: &str) -> Result<Option<String>, Error> {
+        if self.config.node_seeds_hex.is_empty() {
+            Ok(Some(MASTER_SEED_NODE_ID.to_owned()))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+const MASTER_SEED_NODE_ID: &str = "master_seed_node";
+
 #[get("/ping")]
 async fn ping() -> impl Responder {
     info!("ping");
@@ -27,11 +72,16 @@ async fn webhook_handler(
     req: HttpRequest,
     mut body: web::Payload,
     data: web::Data<config::Config>,
+    registry: web::Data<SignerRegistry<AsyncHandler>>,
 ) -> impl Responder {
     let headers = req.headers();
-    let signature = headers
+    let Some(signature) = headers
         .get(lightspark_remote_signing::lightspark::webhooks::SIGNATURE_HEADER)
-        .unwrap();
+        .and_then(|signature| signature.to_str().ok())
+    else {
+        warn!("Rejected webhook without a signature");
+        return HttpResponse::Unauthorized().finish();
+    };
     let mut bytes = web::BytesMut::new();
     while let Some(item) = body.next().await {
         bytes.extend_from_slice(&item.unwrap());
@@ -42,35 +92,42 @@ async fn webhook_handler(
         lightspark::client::LightsparkClient::<Secp256k1SigningKey>::new(auth).unwrap();
     client.requester.set_base_url(data.api_endpoint.clone());
 
-    let seed = Seed::new(hex::decode(data.master_seed_hex.clone()).unwrap());
-    let signer =
-        LightsparkSigner::new(&seed, lightspark_remote_signing::signer::Network::Regtest).unwrap();
-    let validation = PositiveValidator;
-    let handler = Handler::new(signer, Box::new(validation));
+    if !data.respond_directly {
+        return match registry
+            .handle_and_submit_webhook(&client, &bytes, signature, &data.webhook_secret)
+            .await
+        {
+            Ok(outcome) => {
+                debug!("Webhook outcome {:?}", outcome);
+                HttpResponse::NoContent().finish()
+            }
+            Err(e) => failure_response(e),
+        };
+    }
 
-    let event =
-        WebhookEvent::verify_and_parse(&bytes, signature.to_str().unwrap(), &data.webhook_secret)
-            .unwrap();
+    let event = match WebhookEvent::verify_and_parse(&bytes, signature, &data.webhook_secret) {
+        Ok(event) => event,
+        Err(e @ lightspark::error::Error::WebhookSignatureError) => {
+            return failure_response(Error::LightsparkError(e))
+        }
+        Err(e) => {
+            warn!("Rejected malformed webhook: {}", e);
+            return HttpResponse::BadRequest().finish();
+        }
+    };
 
     match event.event_type {
-        RemoteSigning => {
-            let response = handler
-                .handle_remote_signing_webhook_msg(&event)
-                .unwrap()
-                .unwrap();
-
-            debug!("Response {:?}", response);
-
-            if data.respond_directly {
+        RemoteSigning => match registry.handle_remote_signing_webhook_msg(&event).await {
+            Ok(Some(response)) => {
+                debug!("Response {:?}", response);
                 return HttpResponse::Ok().json(response.variables);
             }
-
-            let result = client
-                .execute_graphql_request_variable(&response.query, response.variables)
-                .await;
-
-            debug!("Graphql response {:?}", result);
-        }
+            Ok(None) => debug!("Webhook Event {} needs no response.", event.event_id),
+            Err(e) => {
+                error!("Failed to handle webhook event {}: {}", event.event_id, e);
+                return HttpResponse::InternalServerError().finish();
+            }
+        },
         _ => {
             debug!("Webhook Event {} is not implemented.", event.event_type);
         }
@@ -79,6 +136,21 @@ async fn webhook_handler(
     HttpResponse::NoContent().finish()
 }
 
+/// Logs a webhook that failed and returns a client error if it could not be verified, or a
+/// server error if it could not be handled or its response could not be submitted.
+fn failure_response(e: Error) -> HttpResponse {
+    match e {
+        Error::LightsparkError(lightspark::error::Error::WebhookSignatureError) => {
+            warn!("Rejected webhook with an invalid signature");
+            HttpResponse::Unauthorized().finish()
+        }
+        e => {
+            error!("Failed to handle webhook: {}", e);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     let subscriber = FmtSubscriber::builder()
@@ -90,9 +162,20 @@ async fn main() -> std::io::Result<()> {
     let port = config.api_port;
 
     info!(config = format!("{:?}", config), "Starting Remote Signer.");
+    let key_provider = ConfigKeyProvider {
+        config: config.clone(),
+    };
+    let registry = web::Data::new(SignerRegistry::new(
+        Box::new(key_provider),
+        Network::Regtest,
+        |node_id, signer| {
+            Ok(AsyncHandler::new(signer, Box::new(PositiveValidator)).with_node_id(node_id))
+        },
+    ));
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(config.clone()))
+            .app_data(registry.clone())
             .wrap(middleware::NormalizePath::trim())
             .service(ping)
             .service(webhook_handler)
@@ -536,26 +536,13 @@ impl<T: PublicKeyCache> SendingVASP<T> {
         &self,
         payment: OutgoingPayment,
     ) -> Result<OutgoingPayment, Error> {
-        let mut attemp_limit = 200;
-        let mut payment = payment;
-        while payment.status.to_string() != TransactionStatus::Success.to_string()
-            && payment.status.to_string() != TransactionStatus::Failed.to_string()
-            && payment.status.to_string() != TransactionStatus::Cancelled.to_string()
-        {
-            if attemp_limit == 0 {
-                return Err(Error::PaymentTimeOut);
-            }
-
-            attemp_limit -= 1;
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-            payment = match self.client.get_entity::<OutgoingPayment>(&payment.id).await {
-                Ok(p) => p,
-                Err(e) => return Err(Error::LightsparkError(e)),
-            };
-        }
-
-        Ok(payment)
+        self.client
+            .wait_for_payment(&payment.id, std::time::Duration::from_secs(20))
+            .await
+            .map_err(|e| match e {
+                lightspark::error::Error::Timeout(_) => Error::PaymentTimeOut,
+                e => Error::LightsparkError(e),
+            })
     }
 
     pub fn handle_well_known_lnurlp(
@@ -0,0 +1,198 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use lightspark::webhooks::WebhookEvent;
+
+use crate::{
+    audit::AuditSink,
+    channel_state::ChannelStateStore,
+    handler::Handler,
+    invoice::PaymentPreimageProvider,
+    response::Response,
+    signer::LightsparkSigner,
+    validation::{AsyncSigningRequestValidation, PositiveValidator},
+    vls::VlsBackend,
+    Error,
+};
+
+/// A handler for lightspark remote signing webhook events validating requests asynchronously, to
+/// be used from async web frameworks such as actix or axum.
+///
+/// Only the validation is asynchronous. Once the validator allowed a request, it is handled as by
+/// `handler::Handler`, synchronously on the calling task: signing, as well as the writes of the
+/// channel state store and of the audit sink, which may fsync files or run SQLite transactions,
+/// block the executor thread until they complete. Callers whose stores are slow should use a
+/// multi-threaded runtime and wrap the call, e.g. in `tokio::task::block_in_place`.
+pub struct AsyncHandler {
+    handler: Handler,
+    validator: Box<dyn AsyncSigningRequestValidation>,
+}
+
+impl AsyncHandler {
+    /// Create a new handler.
+    /// # Arguments
+    ///
+    /// * `signer` - A LightsparkSigner instance, which will be used to sign messages.
+    /// * `validator` - An AsyncSigningRequestValidation instance, which will be used to determine whether to sign messages.
+    pub fn new(
+        signer: LightsparkSigner,
+        validator: Box<dyn AsyncSigningRequestValidation>,
+    ) -> Self {
+        Self {
+            handler: Handler::new_with_request_validator(signer, Box::new(PositiveValidator)),
+            validator,
+        }
+    }
+
+    /// Sets the backend answering `VLS_MESSAGE` events. See `Handler::with_vls_backend`.
+    pub fn with_vls_backend(mut self, vls_backend: Box<dyn VlsBackend>) -> Self {
+        self.handler = self.handler.with_vls_backend(vls_backend);
+        self
+    }
+
+    /// Sets the store used to track the commitments of each channel. See
+    /// `Handler::with_channel_state_store`.
+    pub fn with_channel_state_store(
+        mut self,
+        channel_state_store: Box<dyn ChannelStateStore>,
+    ) -> Self {
+        self.handler = self.handler.with_channel_state_store(channel_state_store);
+        self
+    }
+
+    /// Sets the provider of externally managed payment preimages. See
+    /// `Handler::with_preimage_provider`.
+    pub fn with_preimage_provider(
+        mut self,
+        preimage_provider: Box<dyn PaymentPreimageProvider>,
+    ) -> Self {
+        self.handler = self.handler.with_preimage_provider(preimage_provider);
+        self
+    }
+
+    /// Sets the sink recording the decision taken for every signing request. See
+    /// `Handler::with_audit_sink`.
+    pub fn with_audit_sink(mut self, audit_sink: Box<dyn AuditSink>) -> Self {
+        self.handler = self.handler.with_audit_sink(audit_sink);
+        self
+    }
+
+    /// Sets the id of the node the handler signs for. See `Handler::with_node_id`.
+    pub fn with_node_id(mut self, node_id: &str) -> Self {
+        self.handler = self.handler.with_node_id(node_id);
+        self
+    }
+
+    /// Validates the request of the event with the async validator, then handles it
+    /// synchronously. See the type documentation for what blocks the executor.
+    pub async fn handle_remote_signing_webhook_msg(
+        &self,
+        event: &WebhookEvent,
+    ) -> Result<Option<Response>, Error> {
+        let request = self.handler.parse_request(event)?;
+        let validation = self.validator.validate(&request, event).await;
+        self.handler
+            .handle_validated_request(request, event, validation)
+    }
+
+    /// Returns the per-commitment secret the counterparty revealed for the given commitment of a
+    /// channel. See `Handler::counterparty_per_commitment_secret`.
+    pub fn counterparty_per_commitment_secret(
+        &self,
+        channel_id: &str,
+        per_commitment_idx: u64,
+    ) -> Result<Option<[u8; 32]>, Error> {
+        self.handler
+            .counterparty_per_commitment_secret(channel_id, per_commitment_idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::{Network, Seed};
+    use crate::signing_requests::SigningRequest;
+    use crate::validation::{PolicyValidator, ValidationResult};
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn signer() -> LightsparkSigner {
+        let seed = Seed::new(hex::decode("000102030405060708090a0b0c0d0e0f").unwrap());
+        LightsparkSigner::new(&seed, Network::Bitcoin).unwrap()
+    }
+
+    fn derive_key_and_sign_event() -> WebhookEvent {
+        serde_json::from_value(json!({
+            "event_type": "REMOTE_SIGNING",
+            "event_id": "event_id",
+            "timestamp": "2023-09-18T23:50:15.355980+00:00",
+            "entity_id": "node_id",
+            "wallet_id": null,
+            "data": {
+                "sub_event_type": "DERIVE_KEY_AND_SIGN",
+                "bitcoin_network": "REGTEST",
+                "signing_jobs": [{
+                    "id": "job_id",
+                    "derivation_path": "m/3/2104864975",
+                    "message": "4be2d4a38b8f1d3ed4cf9bf8fa8c1d21c2ab0f1b4e3cfb3ca0ce3e1e6bc8e7a1",
+                }],
+            },
+        }))
+        .unwrap()
+    }
+
+    /// Approves the first `approvals` requests, as an approval service would.
+    struct ApprovalService {
+        approvals: usize,
+        requests: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl AsyncSigningRequestValidation for ApprovalService {
+        async fn validate(&self, _: &SigningRequest, _: &WebhookEvent) -> ValidationResult {
+            tokio::task::yield_now().await;
+            if self.requests.fetch_add(1, Ordering::SeqCst) < self.approvals {
+                ValidationResult::Allow
+            } else {
+                ValidationResult::Deny("not approved".to_owned())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_validator() {
+        let handler = AsyncHandler::new(
+            signer(),
+            Box::new(ApprovalService {
+                approvals: 1,
+                requests: AtomicUsize::new(0),
+            }),
+        );
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.query.contains("sign_messages"));
+        let response = handler
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.query.contains("decline_to_sign_messages"));
+    }
+
+    #[tokio::test]
+    async fn test_sync_validator() {
+        let policy = serde_json::from_value(json!({"allowed_networks": ["MAINNET"]})).unwrap();
+        let handler = AsyncHandler::new(signer(), Box::new(PolicyValidator::new(policy)));
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.query.contains("decline_to_sign_messages"));
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+//! Audit log of the decisions taken by the handlers, so that the signing history of a node can be
+//! reconstructed.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use lightspark::{
+    objects::remote_signing_sub_event_type::RemoteSigningSubEventType, webhooks::WebhookEvent,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    signing_requests::{SigningRequest, WebhookEventEntity},
+    Error,
+};
+
+/// The outcome of a signing request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AuditDecision {
+    /// The request was allowed and handled.
+    Signed,
+    /// The validator denied the request.
+    Declined,
+    /// The request was allowed but handling it failed.
+    Failed,
+}
+
+/// A record of the decision taken for one remote signing webhook event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub event_id: String,
+
+    /// The timestamp of the webhook event, in RFC 3339 format.
+    pub timestamp: String,
+
+    /// The sub event type of the event, `None` if it is missing or unknown.
+    pub sub_event_type: Option<RemoteSigningSubEventType>,
+
+    /// The id of the node the event is for. Channel events only have it if the event data or the
+    /// handler carries it, see `handler::Handler::with_node_id`.
+    pub node_id: Option<String>,
+    pub channel_id: Option<String>,
+    pub derivation_paths: Vec<String>,
+    pub message_hashes: Vec<String>,
+    pub decision: AuditDecision,
+
+    /// The reason the validator gave for declining, or the error the request failed with.
+    pub reason: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(
+        request: &SigningRequest,
+        event: &WebhookEvent,
+        decision: AuditDecision,
+        reason: Option<String>,
+    ) -> Self {
+        Self {
+            event_id: event.event_id.clone(),
+            timestamp: event.timestamp.to_rfc3339(),
+            sub_event_type: Some(request.sub_event_type()),
+            node_id: WebhookEventEntity::from_webhook_event(event)
+                .node_id()
+                .map(str::to_owned),
+            channel_id: request.channel_id().map(str::to_owned),
+            derivation_paths: request
+                .derivation_paths()
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            message_hashes: request
+                .message_hashes()
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            decision,
+            reason,
+        }
+    }
+
+    /// Creates the record of an event no signing request could be parsed from, e.g. because its
+    /// sub event type is unknown or its data is missing.
+    pub fn failed(event: &WebhookEvent, error: &Error) -> Self {
+        let entity = WebhookEventEntity::from_webhook_event(event);
+        let channel_id = match entity {
+            WebhookEventEntity::Channel { channel_id, .. } => Some(channel_id.to_owned()),
+            WebhookEventEntity::Node(_) => None,
+        };
+        Self {
+            event_id: event.event_id.clone(),
+            timestamp: event.timestamp.to_rfc3339(),
+            sub_event_type: event
+                .data
+                .as_ref()
+                .and_then(|data| serde_json::from_value(data["sub_event_type"].clone()).ok()),
+            node_id: entity.node_id().map(str::to_owned),
+            channel_id,
+            derivation_paths: vec![],
+            message_hashes: vec![],
+            decision: AuditDecision::Failed,
+            reason: Some(error.to_string()),
+        }
+    }
+}
+
+/// A destination for the audit records of a handler.
+///
+/// The handler fails the request with the sink's error if a record cannot be written, so that
+/// nothing is signed without being recorded.
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: &AuditRecord) -> Result<(), Error>;
+}
+
+/// An `AuditSink` appending the records to a file, one json object per line.
+pub struct JsonLinesAuditSink {
+    file: Mutex<File>,
+}
+
+impl JsonLinesAuditSink {
+    /// Opens the file at the given path for appending, creating it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::AuditSinkError(e.to_string()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for JsonLinesAuditSink {
+    fn record(&self, record: &AuditRecord) -> Result<(), Error> {
+        let mut line =
+            serde_json::to_vec(record).map_err(|e| Error::AuditSinkError(e.to_string()))?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&line)
+            .and_then(|_| file.flush())
+            .map_err(|e| Error::AuditSinkError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::Handler;
+    use crate::signer::{LightsparkSigner, Network, Seed};
+    use crate::validation::{PolicyValidator, PositiveValidator};
+    use serde_json::json;
+    use std::fs;
+
+    fn derive_key_and_sign_event() -> WebhookEvent {
+        serde_json::from_value(json!({
+            "event_type": "REMOTE_SIGNING",
+            "event_id": "event_id",
+            "timestamp": "2023-09-18T23:50:15.355980+00:00",
+            "entity_id": "node_id",
+            "wallet_id": null,
+            "data": {
+                "sub_event_type": "DERIVE_KEY_AND_SIGN",
+                "bitcoin_network": "REGTEST",
+                "signing_jobs": [{
+                    "id": "job_id",
+                    "derivation_path": "m/3/2104864975",
+                    "message": "4be2d4a38b8f1d3ed4cf9bf8fa8c1d21c2ab0f1b4e3cfb3ca0ce3e1e6bc8e7a1",
+                }],
+            },
+        }))
+        .unwrap()
+    }
+
+    fn read_records(path: &Path) -> Vec<AuditRecord> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_json_lines_sink() {
+        let path =
+            std::env::temp_dir().join(format!("lightspark-audit-{}.jsonl", std::process::id()));
+        let seed = Seed::new(hex::decode("000102030405060708090a0b0c0d0e0f").unwrap());
+        let signer = || LightsparkSigner::new(&seed, Network::Bitcoin).unwrap();
+        let policy = serde_json::from_value(json!({"allowed_networks": ["MAINNET"]})).unwrap();
+
+        Handler::new(signer(), Box::new(PositiveValidator))
+            .with_audit_sink(Box::new(JsonLinesAuditSink::open(&path).unwrap()))
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event())
+            .unwrap();
+        Handler::new_with_request_validator(signer(), Box::new(PolicyValidator::new(policy)))
+            .with_audit_sink(Box::new(JsonLinesAuditSink::open(&path).unwrap()))
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event())
+            .unwrap();
+
+        let records = read_records(&path);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].decision, AuditDecision::Signed);
+        assert_eq!(records[0].reason, None);
+        assert_eq!(records[0].node_id.as_deref(), Some("node_id"));
+        assert_eq!(records[0].derivation_paths, vec!["m/3/2104864975"]);
+        assert_eq!(
+            records[0].message_hashes,
+            vec!["4be2d4a38b8f1d3ed4cf9bf8fa8c1d21c2ab0f1b4e3cfb3ca0ce3e1e6bc8e7a1"]
+        );
+        assert!(matches!(
+            records[0].sub_event_type,
+            Some(RemoteSigningSubEventType::DeriveKeyAndSign)
+        ));
+        assert_eq!(records[1].decision, AuditDecision::Declined);
+        assert!(records[1].reason.is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_records_channel_events_and_parse_failures() {
+        let path = std::env::temp_dir().join(format!(
+            "lightspark-audit-failures-{}.jsonl",
+            std::process::id()
+        ));
+        let seed = Seed::new(hex::decode("000102030405060708090a0b0c0d0e0f").unwrap());
+        let handler = Handler::new(
+            LightsparkSigner::new(&seed, Network::Bitcoin).unwrap(),
+            Box::new(PositiveValidator),
+        )
+        .with_node_id("node_id")
+        .with_audit_sink(Box::new(JsonLinesAuditSink::open(&path).unwrap()));
+        let event = |data| {
+            serde_json::from_value::<WebhookEvent>(json!({
+                "event_type": "REMOTE_SIGNING",
+                "event_id": "event_id",
+                "timestamp": "2023-09-18T23:50:15.355980+00:00",
+                "entity_id": "channel_id",
+                "wallet_id": null,
+                "data": data,
+            }))
+            .unwrap()
+        };
+
+        handler
+            .handle_remote_signing_webhook_msg(&event(json!({
+                "sub_event_type": "GET_PER_COMMITMENT_POINT",
+                "bitcoin_network": "REGTEST",
+                "derivation_path": "m/3/2104864975",
+                "per_commitment_point_idx": 281474976710655u64,
+            })))
+            .unwrap();
+        assert!(handler
+            .handle_remote_signing_webhook_msg(&event(json!({
+                "sub_event_type": "GET_PER_COMMITMENT_POINT",
+                "bitcoin_network": "REGTEST",
+            })))
+            .is_err());
+        assert!(handler
+            .handle_remote_signing_webhook_msg(&event(json!({
+                "sub_event_type": "NOT_A_SUB_EVENT_TYPE",
+            })))
+            .is_err());
+
+        let records = read_records(&path);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].decision, AuditDecision::Signed);
+        assert_eq!(records[0].node_id.as_deref(), Some("node_id"));
+        assert_eq!(records[0].channel_id.as_deref(), Some("channel_id"));
+
+        assert_eq!(records[1].decision, AuditDecision::Failed);
+        assert_eq!(records[1].node_id.as_deref(), Some("node_id"));
+        assert_eq!(records[1].channel_id.as_deref(), Some("channel_id"));
+        assert!(matches!(
+            records[1].sub_event_type,
+            Some(RemoteSigningSubEventType::GetPerCommitmentPoint)
+        ));
+
+        assert_eq!(records[2].decision, AuditDecision::Failed);
+        assert!(records[2].sub_event_type.is_none());
+        assert!(records[2].reason.is_some());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,442 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{shachain::CounterpartySecrets, Error};
+
+/// The commitment state of a channel, as seen through the remote signing requests.
+///
+/// Commitment indices count down from 2^48 - 1 as described in bolt 3, so newer commitments have
+/// lower indices.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChannelState {
+    /// The lowest index a per-commitment point was handed out for. As bolt 2 hands out the point
+    /// of the next commitment ahead of time, this is the commitment after the current one, not
+    /// the current one.
+    pub latest_commitment_point_idx: Option<u64>,
+
+    /// The per-commitment secrets revealed by the counterparty.
+    #[serde(default)]
+    pub counterparty_secrets: CounterpartySecrets,
+}
+
+impl ChannelState {
+    /// Whether the commitment with the given index has been revoked, so that its per-commitment
+    /// secret can be released without letting the counterparty punish us for broadcasting it.
+    ///
+    /// The point of commitment `idx - 1` is handed out while commitment `idx` is still current,
+    /// so it proves nothing. The point of commitment `idx - 2` is only handed out alongside the
+    /// revocation of commitment `idx`, once the counterparty signed commitment `idx - 1`.
+    pub fn can_release_secret(&self, per_commitment_idx: u64) -> bool {
+        self.latest_commitment_point_idx
+            .is_some_and(|latest| per_commitment_idx >= latest.saturating_add(2))
+    }
+
+    /// Records that a per-commitment point was handed out.
+    pub fn record_commitment_point(&mut self, per_commitment_idx: u64) {
+        self.latest_commitment_point_idx = Some(
+            self.latest_commitment_point_idx
+                .map_or(per_commitment_idx, |latest| latest.min(per_commitment_idx)),
+        );
+    }
+}
+
+/// A store for the `ChannelState` of each channel, keyed by channel id.
+///
+/// The store must be persistent for the state to survive restarts. A signer whose state was lost
+/// refuses to release per-commitment secrets until it hands out a newer per-commitment point.
+pub trait ChannelStateStore: Send + Sync {
+    /// Returns the state of the given channel, or `None` if the channel is unknown.
+    fn load(&self, channel_id: &str) -> Result<Option<ChannelState>, Error>;
+
+    /// Saves the state of the given channel, replacing any previous state.
+    fn save(&self, channel_id: &str, state: &ChannelState) -> Result<(), Error>;
+
+    /// Applies `update` to the state of the given channel, starting from the default state if
+    /// the channel is unknown, and saves the result if `update` succeeds. Updates of the store
+    /// are serialized, so `update` always sees the state saved by the previous one.
+    fn update(
+        &self,
+        channel_id: &str,
+        update: &mut dyn FnMut(&mut ChannelState) -> Result<(), Error>,
+    ) -> Result<(), Error>;
+}
+
+/// A `ChannelStateStore` keeping the states in memory. The states are lost when the process
+/// exits.
+#[derive(Default)]
+pub struct InMemoryChannelStateStore {
+    states: Mutex<HashMap<String, ChannelState>>,
+}
+
+impl InMemoryChannelStateStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ChannelStateStore for InMemoryChannelStateStore {
+    fn load(&self, channel_id: &str) -> Result<Option<ChannelState>, Error> {
+        Ok(self.states.lock().unwrap().get(channel_id).cloned())
+    }
+
+    fn save(&self, channel_id: &str, state: &ChannelState) -> Result<(), Error> {
+        self.states
+            .lock()
+            .unwrap()
+            .insert(channel_id.to_owned(), state.clone());
+        Ok(())
+    }
+
+    fn update(
+        &self,
+        channel_id: &str,
+        update: &mut dyn FnMut(&mut ChannelState) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut states = self.states.lock().unwrap();
+        let mut state = states.get(channel_id).cloned().unwrap_or_default();
+        update(&mut state)?;
+        states.insert(channel_id.to_owned(), state);
+        Ok(())
+    }
+}
+
+/// A `ChannelStateStore` keeping the states of all the channels in a json file. The file is
+/// rewritten on every save, through a temporary file renamed over it, so a crash never leaves a
+/// partially written file behind.
+pub struct FileChannelStateStore {
+    path: PathBuf,
+    states: Mutex<HashMap<String, ChannelState>>,
+}
+
+impl FileChannelStateStore {
+    /// Opens the store at the given path. The file is created on the first save if it does not
+    /// exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let states = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(Error::ChannelStateStoreError(e.to_string())),
+        };
+        Ok(Self {
+            path,
+            states: Mutex::new(states),
+        })
+    }
+}
+
+impl ChannelStateStore for FileChannelStateStore {
+    fn load(&self, channel_id: &str) -> Result<Option<ChannelState>, Error> {
+        Ok(self.states.lock().unwrap().get(channel_id).cloned())
+    }
+
+    fn save(&self, channel_id: &str, state: &ChannelState) -> Result<(), Error> {
+        let mut states = self.states.lock().unwrap();
+        self.write(&mut states, channel_id, state.clone())
+    }
+
+    fn update(
+        &self,
+        channel_id: &str,
+        update: &mut dyn FnMut(&mut ChannelState) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut states = self.states.lock().unwrap();
+        let mut state = states.get(channel_id).cloned().unwrap_or_default();
+        update(&mut state)?;
+        self.write(&mut states, channel_id, state)
+    }
+}
+
+impl FileChannelStateStore {
+    /// Writes the states with the given channel state replaced to the file, then to the cache.
+    /// The caller must hold the lock on `states` until this returns.
+    fn write(
+        &self,
+        states: &mut HashMap<String, ChannelState>,
+        channel_id: &str,
+        state: ChannelState,
+    ) -> Result<(), Error> {
+        let mut updated = states.clone();
+        updated.insert(channel_id.to_owned(), state);
+
+        let content = serde_json::to_string_pretty(&updated)
+            .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+        write_atomically(&self.path, content.as_bytes())
+            .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+
+        *states = updated;
+        Ok(())
+    }
+}
+
+/// Writes the content to a temporary file next to the path and renames it over the path. The
+/// temporary file is synced before the rename and the directory before and after it, so the new
+/// content is durable once this returns and a crash leaves either the old or the new file.
+fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let mut tmp_path = path.to_path_buf().into_os_string();
+    tmp_path.push(".tmp");
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    drop(file);
+
+    sync_parent_dir(path)?;
+    fs::rename(&tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+// Directories cannot be opened as files on other platforms.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// A `ChannelStateStore` keeping the states in a SQLite database, one row per channel. Each save
+/// is a single statement and each update a single transaction, which SQLite commits durably
+/// before returning.
+#[cfg(feature = "sqlite")]
+pub struct SqliteChannelStateStore {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteChannelStateStore {
+    /// Opens the database at the given path, creating it and the `channel_states` table if they
+    /// do not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let connection = rusqlite::Connection::open(path)
+            .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+        connection
+            .execute_batch(
+                "PRAGMA synchronous = FULL;
+                CREATE TABLE IF NOT EXISTS channel_states (
+                    channel_id TEXT PRIMARY KEY NOT NULL,
+                    state TEXT NOT NULL
+                );",
+            )
+            .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ChannelStateStore for SqliteChannelStateStore {
+    fn load(&self, channel_id: &str) -> Result<Option<ChannelState>, Error> {
+        load_sqlite_state(&self.connection.lock().unwrap(), channel_id)
+    }
+
+    fn save(&self, channel_id: &str, state: &ChannelState) -> Result<(), Error> {
+        save_sqlite_state(&self.connection.lock().unwrap(), channel_id, state)
+    }
+
+    fn update(
+        &self,
+        channel_id: &str,
+        update: &mut dyn FnMut(&mut ChannelState) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        // An immediate transaction takes the write lock upfront, so that other processes sharing
+        // the database cannot update the channel between the read and the write.
+        let transaction = connection
+            .transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)
+            .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+        let mut state = load_sqlite_state(&transaction, channel_id)?.unwrap_or_default();
+        update(&mut state)?;
+        save_sqlite_state(&transaction, channel_id, &state)?;
+        transaction
+            .commit()
+            .map_err(|e| Error::ChannelStateStoreError(e.to_string()))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn load_sqlite_state(
+    connection: &rusqlite::Connection,
+    channel_id: &str,
+) -> Result<Option<ChannelState>, Error> {
+    use rusqlite::OptionalExtension;
+
+    let state: Option<String> = connection
+        .query_row(
+            "SELECT state FROM channel_states WHERE channel_id = ?1",
+            [channel_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+    state
+        .map(|state| serde_json::from_str(&state))
+        .transpose()
+        .map_err(|e| Error::ChannelStateStoreError(e.to_string()))
+}
+
+#[cfg(feature = "sqlite")]
+fn save_sqlite_state(
+    connection: &rusqlite::Connection,
+    channel_id: &str,
+    state: &ChannelState,
+) -> Result<(), Error> {
+    let state =
+        serde_json::to_string(state).map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+    connection
+        .execute(
+            "INSERT INTO channel_states (channel_id, state) VALUES (?1, ?2)
+            ON CONFLICT (channel_id) DO UPDATE SET state = excluded.state",
+            [channel_id, &state],
+        )
+        .map_err(|e| Error::ChannelStateStoreError(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_state() {
+        let mut state = ChannelState::default();
+        assert!(!state.can_release_secret(281474976710655));
+
+        state.record_commitment_point(281474976710655);
+        state.record_commitment_point(281474976710654);
+        state.record_commitment_point(281474976710655);
+        assert_eq!(state.latest_commitment_point_idx, Some(281474976710654));
+        // Commitment 281474976710655 may still be the current one.
+        assert!(!state.can_release_secret(281474976710655));
+        assert!(!state.can_release_secret(281474976710654));
+
+        state.record_commitment_point(281474976710653);
+        assert!(state.can_release_secret(281474976710655));
+        assert!(!state.can_release_secret(281474976710654));
+        assert!(!state.can_release_secret(281474976710653));
+    }
+
+    #[test]
+    fn test_file_store() {
+        let path = std::env::temp_dir().join(format!(
+            "lightspark-channel-state-{}.json",
+            std::process::id()
+        ));
+        let state = ChannelState {
+            latest_commitment_point_idx: Some(10),
+            ..ChannelState::default()
+        };
+
+        let store = FileChannelStateStore::open(&path).unwrap();
+        assert_eq!(store.load("channel").unwrap(), None);
+        store.save("channel", &state).unwrap();
+        assert_eq!(store.load("channel").unwrap(), Some(state.clone()));
+
+        let reopened = FileChannelStateStore::open(&path).unwrap();
+        assert_eq!(reopened.load("channel").unwrap(), Some(state));
+        assert_eq!(reopened.load("other").unwrap(), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_store_update() {
+        let path = std::env::temp_dir().join(format!(
+            "lightspark-channel-state-update-{}.json",
+            std::process::id()
+        ));
+        let store = FileChannelStateStore::open(&path).unwrap();
+
+        // Concurrent updates must not lose each other's points.
+        std::thread::scope(|scope| {
+            for idx in 0..8 {
+                let store = &store;
+                scope.spawn(move || {
+                    store
+                        .update("channel", &mut |state| {
+                            state.record_commitment_point(100 - idx);
+                            Ok(())
+                        })
+                        .unwrap();
+                });
+            }
+        });
+        assert_eq!(
+            store
+                .load("channel")
+                .unwrap()
+                .unwrap()
+                .latest_commitment_point_idx,
+            Some(93)
+        );
+
+        // A failed update is not saved.
+        let result = store.update("channel", &mut |state| {
+            state.record_commitment_point(1);
+            Err(Error::CommitmentNotRevoked(1))
+        });
+        assert!(result.is_err());
+        let reopened = FileChannelStateStore::open(&path).unwrap();
+        assert_eq!(
+            reopened
+                .load("channel")
+                .unwrap()
+                .unwrap()
+                .latest_commitment_point_idx,
+            Some(93)
+        );
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        let path = std::env::temp_dir().join(format!(
+            "lightspark-channel-state-{}.sqlite",
+            std::process::id()
+        ));
+        let mut state = ChannelState {
+            latest_commitment_point_idx: Some(10),
+            ..ChannelState::default()
+        };
+
+        let store = SqliteChannelStateStore::open(&path).unwrap();
+        assert_eq!(store.load("channel").unwrap(), None);
+        store.save("channel", &state).unwrap();
+        store
+            .update("channel", &mut |state| {
+                state.record_commitment_point(9);
+                Ok(())
+            })
+            .unwrap();
+        let result = store.update("channel", &mut |state| {
+            state.record_commitment_point(1);
+            Err(Error::CommitmentNotRevoked(1))
+        });
+        assert!(result.is_err());
+        state.record_commitment_point(9);
+        assert_eq!(store.load("channel").unwrap(), Some(state.clone()));
+        drop(store);
+
+        let reopened = SqliteChannelStateStore::open(&path).unwrap();
+        assert_eq!(reopened.load("channel").unwrap(), Some(state));
+        assert_eq!(reopened.load("other").unwrap(), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
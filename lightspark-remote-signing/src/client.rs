@@ -0,0 +1,440 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+//! Helpers submitting the responses of the handlers through a `LightsparkClient`, so that a
+//! webhook endpoint only needs a single call to verify, handle and answer an event.
+
+use std::fmt;
+
+use lightspark::{
+    client::LightsparkClient,
+    key::OperationSigningKey,
+    objects::{
+        decline_to_sign_messages_output::DeclineToSignMessagesOutput,
+        release_channel_per_commitment_secret_output::ReleaseChannelPerCommitmentSecretOutput,
+        release_payment_preimage_output::ReleasePaymentPreimageOutput,
+        set_invoice_payment_hash_output::SetInvoicePaymentHashOutput,
+        sign_invoice_output::SignInvoiceOutput, sign_messages_output::SignMessagesOutput,
+        update_channel_per_commitment_point_output::UpdateChannelPerCommitmentPointOutput,
+        update_node_shared_secret_output::UpdateNodeSharedSecretOutput,
+        webhook_event_type::WebhookEventType,
+    },
+    request::retry::RetryPolicy,
+    types::graphql_requester::SigningGraphQLRequester,
+    webhooks::WebhookEvent,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{
+    async_handler::AsyncHandler, handler::Handler, registry::SignerRegistry, response::Response,
+    Error,
+};
+
+/// The output of the mutation a signing response was submitted with.
+#[derive(Debug, Clone)]
+pub enum SigningOutput {
+    UpdateNodeSharedSecret(UpdateNodeSharedSecretOutput),
+    UpdateChannelPerCommitmentPoint(UpdateChannelPerCommitmentPointOutput),
+    ReleaseChannelPerCommitmentSecret(ReleaseChannelPerCommitmentSecretOutput),
+    SignInvoice(SignInvoiceOutput),
+    ReleasePaymentPreimage(ReleasePaymentPreimageOutput),
+    SignMessages(SignMessagesOutput),
+    DeclineToSignMessages(DeclineToSignMessagesOutput),
+    SetInvoicePaymentHash(SetInvoicePaymentHashOutput),
+
+    /// The raw result of a mutation not built by this crate, e.g. by a `vls::VlsBackend`.
+    Other(Value),
+}
+
+impl SigningOutput {
+    fn parse(json: Value) -> Result<Self, Error> {
+        let Some((field, output)) = json.as_object().and_then(|fields| fields.iter().next()) else {
+            return Ok(Self::Other(json));
+        };
+        let output = output.clone();
+        Ok(match field.as_str() {
+            "update_node_shared_secret" => Self::UpdateNodeSharedSecret(parse_output(output)?),
+            "update_channel_per_commitment_point" => {
+                Self::UpdateChannelPerCommitmentPoint(parse_output(output)?)
+            }
+            "release_channel_per_commitment_secret" => {
+                Self::ReleaseChannelPerCommitmentSecret(parse_output(output)?)
+            }
+            "sign_invoice" => Self::SignInvoice(parse_output(output)?),
+            "release_payment_preimage" => Self::ReleasePaymentPreimage(parse_output(output)?),
+            "sign_messages" => Self::SignMessages(parse_output(output)?),
+            "decline_to_sign_messages" => Self::DeclineToSignMessages(parse_output(output)?),
+            "set_invoice_payment_hash" => Self::SetInvoicePaymentHash(parse_output(output)?),
+            _ => Self::Other(json),
+        })
+    }
+}
+
+fn parse_output<T: DeserializeOwned>(output: Value) -> Result<T, Error> {
+    serde_json::from_value(output)
+        .map_err(|e| Error::LightsparkError(lightspark::error::Error::JsonError(e)))
+}
+
+/// The outcome of handling a webhook with `Handler::handle_and_submit_webhook`.
+#[derive(Clone)]
+pub enum WebhookOutcome {
+    /// The event is not a remote signing event. It was verified but not handled.
+    Ignored(WebhookEvent),
+
+    /// The event was handled and did not need a response, e.g. a revealed counterparty secret.
+    Handled,
+
+    /// The response was submitted, with the output of its mutation.
+    Submitted(SigningOutput),
+}
+
+impl fmt::Debug for WebhookOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ignored(event) => write!(f, "Ignored({} {})", event.event_type, event.event_id),
+            Self::Handled => write!(f, "Handled"),
+            Self::Submitted(output) => write!(f, "Submitted({:?})", output),
+        }
+    }
+}
+
+/// Submits a signing response, retrying transient failures according to the given policy.
+/// Signing responses only carry data derived from the request, so they can safely be submitted
+/// more than once.
+pub async fn submit_response<K: OperationSigningKey, R: SigningGraphQLRequester>(
+    client: &LightsparkClient<K, R>,
+    response: &Response,
+    retry_policy: &RetryPolicy,
+) -> Result<SigningOutput, Error> {
+    let mut attempt = 1;
+    loop {
+        let result = client
+            .execute_graphql_request_variable(&response.query, response.variables.clone())
+            .await;
+        match result {
+            Ok(json) => return SigningOutput::parse(json),
+            Err(err) if attempt < retry_policy.max_attempts && is_transient(&err) => {
+                warn!("Submitting signing response failed, retrying: {}", err);
+                tokio::time::sleep(retry_policy.backoff(attempt)).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(Error::LightsparkError(err)),
+        }
+    }
+}
+
+fn is_transient(err: &lightspark::error::Error) -> bool {
+    err.is_retryable() || matches!(err, lightspark::error::Error::ReqwestError(_))
+}
+
+/// Verifies and parses a webhook. Events other than remote signing are returned as an `Ignored`
+/// outcome.
+fn verify_remote_signing_webhook(
+    data: &[u8],
+    hex_digest: &str,
+    webhook_secret: &str,
+) -> Result<Result<WebhookEvent, WebhookOutcome>, Error> {
+    let event = WebhookEvent::verify_and_parse(data, hex_digest, webhook_secret)
+        .map_err(Error::LightsparkError)?;
+    if matches!(event.event_type, WebhookEventType::RemoteSigning) {
+        Ok(Ok(event))
+    } else {
+        info!("Ignoring webhook event {}", event.event_type);
+        Ok(Err(WebhookOutcome::Ignored(event)))
+    }
+}
+
+async fn submit_outcome<K: OperationSigningKey, R: SigningGraphQLRequester>(
+    client: &LightsparkClient<K, R>,
+    response: Option<Response>,
+    retry_policy: &RetryPolicy,
+) -> Result<WebhookOutcome, Error> {
+    match response {
+        Some(response) => Ok(WebhookOutcome::Submitted(
+            submit_response(client, &response, retry_policy).await?,
+        )),
+        None => Ok(WebhookOutcome::Handled),
+    }
+}
+
+impl Handler {
+    /// Verifies a webhook with its signature header, handles it and submits the response
+    /// through the client, retrying transient failures with the default `RetryPolicy`.
+    /// # Arguments
+    ///
+    /// * `client` - The client used to submit the response.
+    /// * `data` - The raw body of the webhook request.
+    /// * `hex_digest` - The value of the `lightspark-signature` header of the webhook request.
+    /// * `webhook_secret` - The webhook secret of the account.
+    pub async fn handle_and_submit_webhook<K: OperationSigningKey, R: SigningGraphQLRequester>(
+        &self,
+        client: &LightsparkClient<K, R>,
+        data: &[u8],
+        hex_digest: &str,
+        webhook_secret: &str,
+    ) -> Result<WebhookOutcome, Error> {
+        self.handle_and_submit_webhook_with_retry_policy(
+            client,
+            data,
+            hex_digest,
+            webhook_secret,
+            &RetryPolicy::default(),
+        )
+        .await
+    }
+
+    /// Like `handle_and_submit_webhook`, retrying according to the given policy.
+    pub async fn handle_and_submit_webhook_with_retry_policy<
+        K: OperationSigningKey,
+        R: SigningGraphQLRequester,
+    >(
+        &self,
+        client: &LightsparkClient<K, R>,
+        data: &[u8],
+        hex_digest: &str,
+        webhook_secret: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<WebhookOutcome, Error> {
+        let event = match verify_remote_signing_webhook(data, hex_digest, webhook_secret)? {
+            Ok(event) => event,
+            Err(outcome) => return Ok(outcome),
+        };
+        let response = self.handle_remote_signing_webhook_msg(&event)?;
+        submit_outcome(client, response, retry_policy).await
+    }
+}
+
+impl AsyncHandler {
+    /// Verifies a webhook with its signature header, handles it and submits the response
+    /// through the client. See `Handler::handle_and_submit_webhook`.
+    pub async fn handle_and_submit_webhook<K: OperationSigningKey, R: SigningGraphQLRequester>(
+        &self,
+        client: &LightsparkClient<K, R>,
+        data: &[u8],
+        hex_digest: &str,
+        webhook_secret: &str,
+    ) -> Result<WebhookOutcome, Error> {
+        self.handle_and_submit_webhook_with_retry_policy(
+            client,
+            data,
+            hex_digest,
+            webhook_secret,
+            &RetryPolicy::default(),
+        )
+        .await
+    }
+
+    /// Like `handle_and_submit_webhook`, retrying according to the given policy.
+    pub async fn handle_and_submit_webhook_with_retry_policy<
+        K: OperationSigningKey,
+        R: SigningGraphQLRequester,
+    >(
+        &self,
+        client: &LightsparkClient<K, R>,
+        data: &[u8],
+        hex_digest: &str,
+        webhook_secret: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<WebhookOutcome, Error> {
+        let event = match verify_remote_signing_webhook(data, hex_digest, webhook_secret)? {
+            Ok(event) => event,
+            Err(outcome) => return Ok(outcome),
+        };
+        let response = self.handle_remote_signing_webhook_msg(&event).await?;
+        submit_outcome(client, response, retry_policy).await
+    }
+}
+
+impl SignerRegistry<AsyncHandler> {
+    /// Verifies a webhook with its signature header, handles it with the handler of the node it
+    /// is for and submits the response through the client. See
+    /// `Handler::handle_and_submit_webhook`.
+    pub async fn handle_and_submit_webhook<K: OperationSigningKey, R: SigningGraphQLRequester>(
+        &self,
+        client: &LightsparkClient<K, R>,
+        data: &[u8],
+        hex_digest: &str,
+        webhook_secret: &str,
+    ) -> Result<WebhookOutcome, Error> {
+        self.handle_and_submit_webhook_with_retry_policy(
+            client,
+            data,
+            hex_digest,
+            webhook_secret,
+            &RetryPolicy::default(),
+        )
+        .await
+    }
+
+    /// Like `handle_and_submit_webhook`, retrying according to the given policy.
+    pub async fn handle_and_submit_webhook_with_retry_policy<
+        K: OperationSigningKey,
+        R: SigningGraphQLRequester,
+    >(
+        &self,
+        client: &LightsparkClient<K, R>,
+        data: &[u8],
+        hex_digest: &str,
+        webhook_secret: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<WebhookOutcome, Error> {
+        let event = match verify_remote_signing_webhook(data, hex_digest, webhook_secret)? {
+            Ok(event) => event,
+            Err(outcome) => return Ok(outcome),
+        };
+        let response = self.handle_remote_signing_webhook_msg(&event).await?;
+        submit_outcome(client, response, retry_policy).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::{LightsparkSigner, Network, Seed};
+    use crate::validation::PositiveValidator;
+    use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+    use lightspark::error::ApiError;
+    use lightspark::key::Secp256k1SigningKey;
+    use lightspark::testing::{fixtures, mock_requester::MockRequester};
+    use serde_json::json;
+    use std::time::Duration;
+
+    const WEBHOOK_SECRET: &str = "3gZ5oQQUASYmqQNuEk0KambNMVkOADDItIJjzUlAWjX";
+
+    fn handler() -> Handler {
+        let seed = Seed::new(hex::decode("000102030405060708090a0b0c0d0e0f").unwrap());
+        let signer = LightsparkSigner::new(&seed, Network::Bitcoin).unwrap();
+        Handler::new(signer, Box::new(PositiveValidator))
+    }
+
+    fn client(requester: MockRequester) -> LightsparkClient<Secp256k1SigningKey, MockRequester> {
+        LightsparkClient::with_requester(requester)
+    }
+
+    /// Returns the body and signature of a remote signing webhook with the given data.
+    fn webhook(data: Value) -> (Vec<u8>, String) {
+        let body = serde_json::to_vec(&json!({
+            "event_type": "REMOTE_SIGNING",
+            "event_id": "event_id",
+            "timestamp": "2023-09-18T23:50:15.355980+00:00",
+            "entity_id": "channel_id",
+            "wallet_id": null,
+            "data": data,
+        }))
+        .unwrap();
+        let mut engine = hmac::HmacEngine::<sha256::Hash>::new(WEBHOOK_SECRET.as_bytes());
+        engine.input(&body);
+        let digest = hmac::Hmac::<sha256::Hash>::from_engine(engine);
+        (body, hex::encode(digest.to_byte_array()))
+    }
+
+    fn get_per_commitment_point_webhook() -> (Vec<u8>, String) {
+        webhook(json!({
+            "sub_event_type": "GET_PER_COMMITMENT_POINT",
+            "bitcoin_network": "REGTEST",
+            "derivation_path": "m/3/2104864975",
+            "per_commitment_point_idx": 281474976710654u64,
+        }))
+    }
+
+    fn no_backoff(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            jitter: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_and_submit_webhook() {
+        let client = client(MockRequester::new().respond(
+            "UpdateChannelPerCommitmentPoint",
+            json!({
+                "update_channel_per_commitment_point":
+                    fixtures::object("UpdateChannelPerCommitmentPointOutput"),
+            }),
+        ));
+        let (body, signature) = get_per_commitment_point_webhook();
+
+        let outcome = handler()
+            .handle_and_submit_webhook(&client, &body, &signature, WEBHOOK_SECRET)
+            .await
+            .unwrap();
+        let WebhookOutcome::Submitted(SigningOutput::UpdateChannelPerCommitmentPoint(output)) =
+            outcome
+        else {
+            panic!("unexpected outcome");
+        };
+        assert!(output.channel.id.starts_with("Entity:"));
+
+        let requests = client.requester.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].variables.as_ref().unwrap()["channel_id"],
+            "channel_id"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_and_submit_webhook_retries() {
+        let client = client(MockRequester::new().fail(
+            "UpdateChannelPerCommitmentPoint",
+            ApiError {
+                status: 503,
+                errors: vec![],
+            },
+        ));
+        let (body, signature) = get_per_commitment_point_webhook();
+
+        let error = handler()
+            .handle_and_submit_webhook_with_retry_policy(
+                &client,
+                &body,
+                &signature,
+                WEBHOOK_SECRET,
+                &no_backoff(3),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::LightsparkError(e) if e.is_retryable()));
+        assert_eq!(client.requester.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_handle_and_submit_webhook_without_response() {
+        let client = client(MockRequester::new());
+        let handler = handler();
+
+        let (body, signature) = webhook(json!({
+            "sub_event_type": "REVEAL_COUNTERPARTY_PER_COMMITMENT_SECRET",
+            "bitcoin_network": "REGTEST",
+            "per_commitment_secret_idx": 281474976710655u64,
+            "per_commitment_secret": "7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc",
+        }));
+        let outcome = handler
+            .handle_and_submit_webhook(&client, &body, &signature, WEBHOOK_SECRET)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, WebhookOutcome::Handled));
+
+        let body = "{\"event_type\": \"NODE_STATUS\", \"event_id\": \"1615c8be5aa44e429eba700db2ed8ca5\", \"timestamp\": \"2023-05-17T23:56:47.874449+00:00\", \"entity_id\": \"lightning_node:01882c25-157a-f96b-0000-362d42b64397\"}";
+        let signature = "62a8829aeb48b4142533520b1f7f86cdb1ee7d718bf3ea15bc1c662d4c453b74";
+        let outcome = handler
+            .handle_and_submit_webhook(&client, body.as_bytes(), signature, WEBHOOK_SECRET)
+            .await
+            .unwrap();
+        assert!(matches!(outcome, WebhookOutcome::Ignored(_)));
+
+        let error = handler
+            .handle_and_submit_webhook(&client, body.as_bytes(), "00", WEBHOOK_SECRET)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::LightsparkError(lightspark::error::Error::WebhookSignatureError)
+        ));
+        assert!(client.requester.requests().is_empty());
+    }
+}
@@ -1,26 +1,37 @@
 use lightspark::{objects::id_and_signature::IdAndSignature, webhooks::WebhookEvent};
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::{
+    audit::{AuditDecision, AuditRecord, AuditSink},
+    channel_state::ChannelStateStore,
+    invoice::PaymentPreimageProvider,
     response::Response,
     signer::LightsparkSigner,
     signing_requests::{
-        DeriveKeyAndSignRequest, GetPerCommitmentPointRequest, InvoicePaymentHashRequest,
-        ReleasePaymentPreimageRequest, ReleasePerCommitmentSecretRequest, SigningJob,
-        SigningRequest,
+        DeriveKeyAndSignRequest, EcdhRequest, GetPerCommitmentPointRequest,
+        InvoicePaymentHashRequest, ReleaseCounterpartyPerCommitmentSecretRequest,
+        ReleasePaymentPreimageRequest, ReleasePerCommitmentSecretRequest, SignInvoiceRequest,
+        SigningJob, SigningRequest, VlsMessageRequest,
     },
     signing_responses::{
-        DeriveKeyAndSignResponse, GetPerCommitmentPointResponse, InvoicePaymentHashResponse,
-        ReleasePaymentPreimageResponse, ReleasePerCommitmentSecretResponse, SigningResponse,
+        DeriveKeyAndSignResponse, EcdhResponse, GetPerCommitmentPointResponse,
+        InvoicePaymentHashResponse, ReleasePaymentPreimageResponse,
+        ReleasePerCommitmentSecretResponse, SignInvoiceResponse, SigningResponse,
     },
-    validation::Validation,
+    validation::{SigningRequestValidation, Validation, ValidationResult, WebhookJsonValidation},
+    vls::VlsBackend,
     Error,
 };
 
 /// A handler for lightspark remote signing webhook events.
 pub struct Handler {
     signer: LightsparkSigner,
-    validator: Box<dyn Validation>,
+    validator: Box<dyn SigningRequestValidation>,
+    vls_backend: Option<Box<dyn VlsBackend>>,
+    channel_state_store: Option<Box<dyn ChannelStateStore>>,
+    preimage_provider: Option<Box<dyn PaymentPreimageProvider>>,
+    audit_sink: Option<Box<dyn AuditSink>>,
+    node_id: Option<String>,
 }
 
 impl Handler {
@@ -30,46 +41,189 @@ impl Handler {
     /// * `signer` - A LightsparkSigner instance, which will be used to sign messages.
     /// * `validator` - A Validation instance, which will be used to determine whether to sign messages.
     pub fn new(signer: LightsparkSigner, validator: Box<dyn Validation>) -> Self {
-        Self { signer, validator }
+        Self::new_with_request_validator(signer, Box::new(WebhookJsonValidation(validator)))
+    }
+
+    /// Create a new handler with a validator receiving the parsed signing requests.
+    /// # Arguments
+    ///
+    /// * `signer` - A LightsparkSigner instance, which will be used to sign messages.
+    /// * `validator` - A SigningRequestValidation instance, which will be used to determine whether to sign messages.
+    pub fn new_with_request_validator(
+        signer: LightsparkSigner,
+        validator: Box<dyn SigningRequestValidation>,
+    ) -> Self {
+        Self {
+            signer,
+            validator,
+            vls_backend: None,
+            channel_state_store: None,
+            preimage_provider: None,
+            audit_sink: None,
+            node_id: None,
+        }
+    }
+
+    /// Sets the backend answering `VLS_MESSAGE` events. Without a backend, these events fail with
+    /// `Error::WebhookEventTypeNotSupported`.
+    pub fn with_vls_backend(mut self, vls_backend: Box<dyn VlsBackend>) -> Self {
+        self.vls_backend = Some(vls_backend);
+        self
+    }
+
+    /// Sets the store used to track the commitments of each channel. With a store, the handler
+    /// refuses to release the per-commitment secret of a commitment that has not been superseded
+    /// by a newer one, failing with `Error::CommitmentNotRevoked`.
+    pub fn with_channel_state_store(
+        mut self,
+        channel_state_store: Box<dyn ChannelStateStore>,
+    ) -> Self {
+        self.channel_state_store = Some(channel_state_store);
+        self
+    }
+
+    /// Sets the provider of externally managed payment preimages. With a provider, the payment
+    /// hashes and preimages of invoices are requested from the provider first, and derived from
+    /// the preimage nonce for the invoices it does not manage.
+    pub fn with_preimage_provider(
+        mut self,
+        preimage_provider: Box<dyn PaymentPreimageProvider>,
+    ) -> Self {
+        self.preimage_provider = Some(preimage_provider);
+        self
+    }
+
+    /// Sets the sink recording the decision taken for every signing request. A request whose
+    /// record cannot be written fails with the sink's error.
+    pub fn with_audit_sink(mut self, audit_sink: Box<dyn AuditSink>) -> Self {
+        self.audit_sink = Some(audit_sink);
+        self
+    }
+
+    /// Sets the id of the node the handler signs for. It is recorded as the node of the audit
+    /// records, which otherwise only know it from the event, and the per-commitment events, whose
+    /// `entity_id` is a channel, do not always carry it.
+    pub fn with_node_id(mut self, node_id: &str) -> Self {
+        self.node_id = Some(node_id.to_owned());
+        self
     }
 
     pub fn handle_remote_signing_webhook_msg(
         &self,
         event: &WebhookEvent,
     ) -> Result<Option<Response>, Error> {
-        let request = SigningRequest::parse_from_webhook_event(event)?;
-        let event_json =
-            serde_json::to_string(&event).expect("Serialize event to json should not fail");
-        if !self.validator.should_sign(event_json) {
-            self.handle_decline_to_sign_messages(event).map(Some)
-        } else {
-            let response: Option<SigningResponse> = match request {
-                SigningRequest::GetPerCommitmentPointRequest(r) => {
-                    Some(self.handle_get_per_commitment_point(&r)?)
-                }
-                SigningRequest::ReleasePerCommitmentSecretRequest(r) => {
-                    Some(self.handle_release_per_commitment_secret(&r)?)
-                }
-                SigningRequest::DeriveKeyAndSignRequest(r) => {
-                    Some(self.handle_derive_key_and_sign(&r)?)
-                }
-                SigningRequest::InvoicePaymentHashRequest(r) => {
-                    Some(self.handle_request_invoice_payment_hash(&r)?)
-                }
-                SigningRequest::ReleasePaymentPreimageRequest(r) => {
-                    Some(self.handle_release_payment_preimage(&r)?)
-                }
-                SigningRequest::ReleaseCounterpartyPerCommitmentSecretRequest(_) => None,
-            };
+        let request = self.parse_request(event)?;
+        let validation = self.validator.validate(&request, event);
+        self.handle_validated_request(request, event, validation)
+    }
 
-            Ok(response.map(|r| r.graphql_response()))
+    /// Parses the signing request of the event, recording the events that cannot be parsed as
+    /// failed in the audit sink.
+    pub(crate) fn parse_request(&self, event: &WebhookEvent) -> Result<SigningRequest, Error> {
+        SigningRequest::parse_from_webhook_event(event).or_else(|e| {
+            self.record(AuditRecord::failed(event, &e))?;
+            Err(e)
+        })
+    }
+
+    fn record(&self, mut record: AuditRecord) -> Result<(), Error> {
+        if let Some(audit_sink) = &self.audit_sink {
+            if self.node_id.is_some() {
+                record.node_id = self.node_id.clone();
+            }
+            audit_sink.record(&record)?;
         }
+        Ok(())
+    }
+
+    /// Handles a request parsed from the given event, declining to sign it if the validation
+    /// denied it. Denied requests without signing jobs fail with `Error::Declined`.
+    pub(crate) fn handle_validated_request(
+        &self,
+        request: SigningRequest,
+        event: &WebhookEvent,
+        validation: ValidationResult,
+    ) -> Result<Option<Response>, Error> {
+        let (result, decision, reason) = match validation {
+            ValidationResult::Deny(reason) => {
+                warn!(
+                    "Declining to sign webhook event {}: {}",
+                    event.event_id, reason
+                );
+                // Only signing jobs can be declined through the API, other requests are left
+                // unanswered.
+                let result = match request {
+                    SigningRequest::DeriveKeyAndSignRequest(_) => {
+                        self.handle_decline_to_sign_messages(event).map(Some)
+                    }
+                    _ => Err(Error::Declined(reason.clone())),
+                };
+                (result, AuditDecision::Declined, Some(reason))
+            }
+            ValidationResult::Allow => {
+                let result = self
+                    .handle_request(&request)
+                    .map(|response| response.map(|r| r.graphql_response()));
+                match &result {
+                    Ok(_) => (result, AuditDecision::Signed, None),
+                    Err(e) => {
+                        let reason = e.to_string();
+                        (result, AuditDecision::Failed, Some(reason))
+                    }
+                }
+            }
+        };
+
+        self.record(AuditRecord::new(&request, event, decision, reason))?;
+        result
+    }
+
+    fn handle_request(&self, request: &SigningRequest) -> Result<Option<SigningResponse>, Error> {
+        let response = match request {
+            SigningRequest::GetPerCommitmentPointRequest(r) => {
+                Some(self.handle_get_per_commitment_point(r)?)
+            }
+            SigningRequest::ReleasePerCommitmentSecretRequest(r) => {
+                Some(self.handle_release_per_commitment_secret(r)?)
+            }
+            SigningRequest::DeriveKeyAndSignRequest(r) => Some(self.handle_derive_key_and_sign(r)?),
+            SigningRequest::InvoicePaymentHashRequest(r) => {
+                Some(self.handle_request_invoice_payment_hash(r)?)
+            }
+            SigningRequest::ReleasePaymentPreimageRequest(r) => {
+                Some(self.handle_release_payment_preimage(r)?)
+            }
+            SigningRequest::ReleaseCounterpartyPerCommitmentSecretRequest(r) => {
+                self.handle_release_counterparty_per_commitment_secret(r)?;
+                None
+            }
+            SigningRequest::EcdhRequest(r) => Some(self.handle_ecdh(r)?),
+            SigningRequest::SignInvoiceRequest(r) => Some(self.handle_sign_invoice(r)?),
+            SigningRequest::VlsMessageRequest(r) => Some(self.handle_vls_message(r)?),
+        };
+        Ok(response)
     }
 
     pub fn handle_request_invoice_payment_hash(
         &self,
         request: &InvoicePaymentHashRequest,
     ) -> Result<SigningResponse, Error> {
+        if let Some(payment_hash) = self
+            .preimage_provider
+            .as_ref()
+            .map(|provider| provider.get_payment_hash(&request.invoice_id))
+            .transpose()?
+            .flatten()
+        {
+            return Ok(SigningResponse::InvoicePaymentHashResponse(
+                InvoicePaymentHashResponse {
+                    invoice_id: request.invoice_id.clone(),
+                    payment_hash,
+                    nonce: None,
+                },
+            ));
+        }
+
         let nonce = self.signer.generate_preimage_nonce();
         let nonce_str = hex::encode(&nonce);
 
@@ -102,20 +256,29 @@ impl Handler {
         request: &ReleasePaymentPreimageRequest,
     ) -> Result<SigningResponse, Error> {
         info!("Handling release payment preimage webhook event");
-        let nonce = request
-            .nonce
-            .clone()
-            .expect("Nonce should not be empty if you use SDK to manage preimage");
-        let nonce_bytes = hex::decode(nonce).map_err(|_| Error::HexEncodingError)?;
-        let preimage = self
-            .signer
-            .generate_preimage(nonce_bytes)
-            .map_err(Error::SignerError)?;
+        let provided_preimage = self
+            .preimage_provider
+            .as_ref()
+            .map(|provider| provider.release_preimage(&request.invoice_id))
+            .transpose()?
+            .flatten();
+        let payment_preimage = match (provided_preimage, &request.nonce) {
+            (Some(preimage), _) => preimage,
+            (None, Some(nonce)) => {
+                let nonce_bytes = hex::decode(nonce).map_err(|_| Error::HexEncodingError)?;
+                let preimage = self
+                    .signer
+                    .generate_preimage(nonce_bytes)
+                    .map_err(Error::SignerError)?;
+                hex::encode(preimage)
+            }
+            (None, None) => return Err(Error::PreimageNonceMissing),
+        };
 
         Ok(SigningResponse::ReleasePaymentPreimageResponse(
             ReleasePaymentPreimageResponse {
                 invoice_id: request.invoice_id.clone(),
-                payment_preimage: hex::encode(preimage),
+                payment_preimage,
             },
         ))
     }
@@ -125,13 +288,28 @@ impl Handler {
         request: &GetPerCommitmentPointRequest,
     ) -> Result<SigningResponse, Error> {
         info!("Handling get per commitment point webhook event");
-        let per_commitment_point = self
-            .signer
-            .get_per_commitment_point(
-                request.derivation_path.clone(),
-                request.per_commitmnet_point_idx,
-            )
-            .map_err(Error::SignerError)?;
+        let get_point = || {
+            self.signer
+                .get_per_commitment_point(
+                    request.derivation_path.clone(),
+                    request.per_commitmnet_point_idx,
+                )
+                .map_err(Error::SignerError)
+        };
+        // The point is recorded in the same update it is derived in, so that a concurrent
+        // release of an older secret sees it.
+        let per_commitment_point = match &self.channel_state_store {
+            Some(store) => {
+                let mut per_commitment_point = None;
+                store.update(&request.channel_id, &mut |state| {
+                    per_commitment_point = Some(get_point()?);
+                    state.record_commitment_point(request.per_commitmnet_point_idx);
+                    Ok(())
+                })?;
+                per_commitment_point.expect("update succeeded without deriving the point")
+            }
+            None => get_point()?,
+        };
 
         let commitment_point_str = hex::encode(per_commitment_point);
         Ok(SigningResponse::GetPerCommitmentPointResponse(
@@ -148,13 +326,32 @@ impl Handler {
         request: &ReleasePerCommitmentSecretRequest,
     ) -> Result<SigningResponse, Error> {
         info!("Handling release per commitment secret webhook event");
-        let commitment_secret = self
-            .signer
-            .release_per_commitment_secret(
-                request.derivation_path.clone(),
-                request.per_commitment_point_idx,
-            )
-            .map_err(Error::SignerError)?;
+        let release_secret = || {
+            self.signer
+                .release_per_commitment_secret(
+                    request.derivation_path.clone(),
+                    request.per_commitment_point_idx,
+                )
+                .map_err(Error::SignerError)
+        };
+        // The check and the release happen in the same update, so that they see a consistent
+        // state of the channel.
+        let commitment_secret = match &self.channel_state_store {
+            Some(store) => {
+                let mut commitment_secret = None;
+                store.update(&request.channel_id, &mut |state| {
+                    if !state.can_release_secret(request.per_commitment_point_idx) {
+                        return Err(Error::CommitmentNotRevoked(
+                            request.per_commitment_point_idx,
+                        ));
+                    }
+                    commitment_secret = Some(release_secret()?);
+                    Ok(())
+                })?;
+                commitment_secret.expect("update succeeded without releasing the secret")
+            }
+            None => release_secret()?,
+        };
 
         let commitment_secret_str = hex::encode(commitment_secret);
 
@@ -167,6 +364,45 @@ impl Handler {
         ))
     }
 
+    /// Stores the revealed counterparty per-commitment secret in the channel state store, after
+    /// checking it is consistent with the secrets revealed before. Without a channel state store,
+    /// the secret is discarded.
+    pub fn handle_release_counterparty_per_commitment_secret(
+        &self,
+        request: &ReleaseCounterpartyPerCommitmentSecretRequest,
+    ) -> Result<(), Error> {
+        info!("Handling release counterparty per commitment secret webhook event");
+        let Some(store) = &self.channel_state_store else {
+            return Ok(());
+        };
+        let secret: [u8; 32] = hex::decode(&request.per_commitment_secret)
+            .map_err(|_| Error::HexEncodingError)?
+            .try_into()
+            .map_err(|_| Error::HexEncodingError)?;
+
+        store.update(&request.channel_id, &mut |state| {
+            state
+                .counterparty_secrets
+                .insert_secret(request.per_commitment_secret_idx, secret)
+        })
+    }
+
+    /// Returns the per-commitment secret the counterparty revealed for the given commitment of a
+    /// channel, e.g. to build a justice transaction. Returns `None` if the secret was not
+    /// revealed or if the handler has no channel state store.
+    pub fn counterparty_per_commitment_secret(
+        &self,
+        channel_id: &str,
+        per_commitment_idx: u64,
+    ) -> Result<Option<[u8; 32]>, Error> {
+        let Some(store) = &self.channel_state_store else {
+            return Ok(None);
+        };
+        Ok(store
+            .load(channel_id)?
+            .and_then(|state| state.counterparty_secrets.get_secret(per_commitment_idx)))
+    }
+
     pub fn handle_derive_key_and_sign(
         &self,
         requeset: &DeriveKeyAndSignRequest,
@@ -201,4 +437,525 @@ impl Handler {
             DeriveKeyAndSignResponse { signatures },
         ))
     }
+
+    pub fn handle_ecdh(&self, request: &EcdhRequest) -> Result<SigningResponse, Error> {
+        info!("Handling ECDH webhook event");
+        let peer_public_key =
+            hex::decode(&request.peer_public_key).map_err(|_| Error::HexEncodingError)?;
+        let shared_secret = self
+            .signer
+            .ecdh(peer_public_key)
+            .map_err(Error::SignerError)?;
+
+        Ok(SigningResponse::EcdhResponse(EcdhResponse {
+            node_id: request.node_id.clone(),
+            shared_secret: hex::encode(shared_secret),
+        }))
+    }
+
+    pub fn handle_sign_invoice(
+        &self,
+        request: &SignInvoiceRequest,
+    ) -> Result<SigningResponse, Error> {
+        info!("Handling sign invoice webhook event");
+        let invoice_hash =
+            hex::decode(&request.invoice_hash).map_err(|_| Error::HexEncodingError)?;
+        let signature = self
+            .signer
+            .sign_invoice_hash(invoice_hash)
+            .map_err(Error::SignerError)?;
+
+        Ok(SigningResponse::SignInvoiceResponse(SignInvoiceResponse {
+            invoice_id: request.invoice_id.clone(),
+            signature: hex::encode(signature.get_signature()),
+            recovery_id: signature.get_recovery_id(),
+        }))
+    }
+
+    pub fn handle_vls_message(
+        &self,
+        request: &VlsMessageRequest,
+    ) -> Result<SigningResponse, Error> {
+        info!("Handling VLS message webhook event");
+        let vls_backend = self
+            .vls_backend
+            .as_ref()
+            .ok_or(Error::WebhookEventTypeNotSupported)?;
+        Ok(SigningResponse::VlsMessageResponse(
+            vls_backend.handle_vls_message(request)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel_state::InMemoryChannelStateStore;
+    use crate::signer::{Network, Seed};
+    use crate::validation::PositiveValidator;
+    use bitcoin::hashes::{sha256, Hash};
+    use lightspark::objects::bitcoin_network::BitcoinNetwork;
+    use serde_json::json;
+
+    const SEED: &str = "000102030405060708090a0b0c0d0e0f";
+    const PEER_PUBLIC_KEY: &str =
+        "03e7156ae33b0a208d0744199163177e909e80176e55d97a2f221ede0f934dd9ad";
+
+    fn signer() -> LightsparkSigner {
+        let seed = Seed::new(hex::decode(SEED).unwrap());
+        LightsparkSigner::new(&seed, Network::Bitcoin).unwrap()
+    }
+
+    fn event(data: serde_json::Value) -> WebhookEvent {
+        serde_json::from_value(json!({
+            "event_type": "REMOTE_SIGNING",
+            "event_id": "event_id",
+            "timestamp": "2023-09-18T23:50:15.355980+00:00",
+            "entity_id": "node_id",
+            "wallet_id": null,
+            "data": data,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_handle_ecdh() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator));
+        let event = event(json!({
+            "sub_event_type": "ECDH",
+            "bitcoin_network": "REGTEST",
+            "peer_public_key": PEER_PUBLIC_KEY,
+        }));
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&event)
+            .unwrap()
+            .unwrap();
+        let shared_secret = signer()
+            .ecdh(hex::decode(PEER_PUBLIC_KEY).unwrap())
+            .unwrap();
+        assert!(response.query.contains("update_node_shared_secret"));
+        assert_eq!(
+            response.variables,
+            json!({"node_id": "node_id", "shared_secret": hex::encode(shared_secret)})
+        );
+    }
+
+    #[test]
+    fn test_handle_sign_invoice() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator));
+        let invoice_hash = "4be2d4a38b8f1d3ed4cf9bf8fa8c1d21c2ab0f1b4e3cfb3ca0ce3e1e6bc8e7a1";
+        let event = event(json!({
+            "sub_event_type": "SIGN_INVOICE",
+            "bitcoin_network": "REGTEST",
+            "invoice_id": "invoice_id",
+            "invoice_hash": invoice_hash,
+        }));
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&event)
+            .unwrap()
+            .unwrap();
+        let signature = signer()
+            .sign_invoice_hash(hex::decode(invoice_hash).unwrap())
+            .unwrap();
+        assert!(response.query.contains("sign_invoice"));
+        assert_eq!(
+            response.variables,
+            json!({
+                "invoice_id": "invoice_id",
+                "signature": hex::encode(signature.get_signature()),
+                "recovery_id": signature.get_recovery_id(),
+            })
+        );
+    }
+
+    fn derive_key_and_sign_event(derivation_path: &str) -> WebhookEvent {
+        event(json!({
+            "sub_event_type": "DERIVE_KEY_AND_SIGN",
+            "bitcoin_network": "REGTEST",
+            "signing_jobs": [{
+                "id": "job_id",
+                "derivation_path": derivation_path,
+                "message": "4be2d4a38b8f1d3ed4cf9bf8fa8c1d21c2ab0f1b4e3cfb3ca0ce3e1e6bc8e7a1",
+            }],
+        }))
+    }
+
+    struct RegtestPathValidator;
+
+    impl SigningRequestValidation for RegtestPathValidator {
+        fn validate(&self, request: &SigningRequest, _: &WebhookEvent) -> ValidationResult {
+            if !matches!(request.bitcoin_network(), Some(BitcoinNetwork::Regtest)) {
+                return ValidationResult::Deny("not regtest".to_owned());
+            }
+            match request
+                .derivation_paths()
+                .iter()
+                .find(|path| !path.starts_with("m/3/"))
+            {
+                Some(path) => ValidationResult::Deny(format!("unexpected path {}", path)),
+                None => ValidationResult::Allow,
+            }
+        }
+    }
+
+    struct NegativeValidator;
+
+    impl Validation for NegativeValidator {
+        fn should_sign(&self, webhook: String) -> bool {
+            !webhook.contains("DERIVE_KEY_AND_SIGN")
+        }
+    }
+
+    #[test]
+    fn test_request_validator() {
+        let handler = Handler::new_with_request_validator(signer(), Box::new(RegtestPathValidator));
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event("m/3/2104864975"))
+            .unwrap()
+            .unwrap();
+        assert!(response.query.contains("sign_messages"));
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event("m/0/1"))
+            .unwrap()
+            .unwrap();
+        assert!(response.query.contains("decline_to_sign_messages"));
+        assert_eq!(response.variables, json!({"payload_ids": ["job_id"]}));
+    }
+
+    #[test]
+    fn test_string_validator() {
+        let handler = Handler::new(signer(), Box::new(NegativeValidator));
+        let response = handler
+            .handle_remote_signing_webhook_msg(&derive_key_and_sign_event("m/3/2104864975"))
+            .unwrap()
+            .unwrap();
+        assert!(response.query.contains("decline_to_sign_messages"));
+    }
+
+    fn per_commitment_event(sub_event_type: &str, idx: u64) -> WebhookEvent {
+        let mut event = event(json!({
+            "sub_event_type": sub_event_type,
+            "bitcoin_network": "REGTEST",
+            "derivation_path": "m/3/2104864975",
+            "per_commitment_point_idx": idx,
+        }));
+        event.entity_id = "channel_id".to_owned();
+        event
+    }
+
+    #[test]
+    fn test_channel_state_store() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator))
+            .with_channel_state_store(Box::new(InMemoryChannelStateStore::new()));
+        let handle = |sub_event_type, idx| {
+            handler.handle_remote_signing_webhook_msg(&per_commitment_event(sub_event_type, idx))
+        };
+
+        assert!(matches!(
+            handle("RELEASE_PER_COMMITMENT_SECRET", 281474976710655),
+            Err(Error::CommitmentNotRevoked(281474976710655))
+        ));
+        handle("GET_PER_COMMITMENT_POINT", 281474976710655).unwrap();
+        assert!(matches!(
+            handle("RELEASE_PER_COMMITMENT_SECRET", 281474976710655),
+            Err(Error::CommitmentNotRevoked(281474976710655))
+        ));
+
+        // The point of the next commitment is handed out while the current one is not revoked.
+        handle("GET_PER_COMMITMENT_POINT", 281474976710654).unwrap();
+        assert!(matches!(
+            handle("RELEASE_PER_COMMITMENT_SECRET", 281474976710655),
+            Err(Error::CommitmentNotRevoked(281474976710655))
+        ));
+
+        handle("GET_PER_COMMITMENT_POINT", 281474976710653).unwrap();
+        let response = handle("RELEASE_PER_COMMITMENT_SECRET", 281474976710655)
+            .unwrap()
+            .unwrap();
+        assert!(response
+            .query
+            .contains("release_channel_per_commitment_secret"));
+        assert!(matches!(
+            handle("RELEASE_PER_COMMITMENT_SECRET", 281474976710654),
+            Err(Error::CommitmentNotRevoked(281474976710654))
+        ));
+    }
+
+    #[test]
+    fn test_counterparty_per_commitment_secret() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator))
+            .with_channel_state_store(Box::new(InMemoryChannelStateStore::new()));
+        let reveal = |idx: u64, secret: &str| {
+            let mut event = event(json!({
+                "sub_event_type": "REVEAL_COUNTERPARTY_PER_COMMITMENT_SECRET",
+                "bitcoin_network": "REGTEST",
+                "per_commitment_secret_idx": idx,
+                "per_commitment_secret": secret,
+            }));
+            event.entity_id = "channel_id".to_owned();
+            handler.handle_remote_signing_webhook_msg(&event)
+        };
+
+        // The first two secrets generated from a seed of 0xff bytes, as in bolt 3.
+        let first = "7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc";
+        let second = "c7518c8ae4660ed02894df8976fa1a3659c1a8b4b5bec0c4b872abeba4cb8964";
+        assert!(reveal(281474976710655, first).unwrap().is_none());
+        assert!(reveal(281474976710654, second).unwrap().is_none());
+        assert_eq!(
+            handler
+                .counterparty_per_commitment_secret("channel_id", 281474976710655)
+                .unwrap()
+                .map(hex::encode)
+                .as_deref(),
+            Some(first)
+        );
+        assert_eq!(
+            handler
+                .counterparty_per_commitment_secret("channel_id", 281474976710653)
+                .unwrap(),
+            None
+        );
+
+        assert!(matches!(
+            reveal(281474976710654, first),
+            Err(Error::InvalidCounterpartySecret(_))
+        ));
+        assert!(matches!(
+            reveal(281474976710653, "00"),
+            Err(Error::HexEncodingError)
+        ));
+    }
+
+    struct EchoVlsBackend;
+
+    impl VlsBackend for EchoVlsBackend {
+        fn handle_vls_message(&self, request: &VlsMessageRequest) -> Result<Response, Error> {
+            if request.message.is_empty() {
+                return Err(Error::VlsBackendError("empty message".to_owned()));
+            }
+            Ok(Response {
+                query: "mutation Echo($message: String!) { echo(message: $message) }".to_owned(),
+                variables: json!({"node_id": request.node_id, "message": request.message}),
+            })
+        }
+    }
+
+    #[test]
+    fn test_handle_vls_message() {
+        let vls_event = |message: &str| {
+            event(json!({
+                "sub_event_type": "VLS_MESSAGE",
+                "bitcoin_network": "REGTEST",
+                "message": message,
+            }))
+        };
+
+        let handler = Handler::new(signer(), Box::new(PositiveValidator));
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&vls_event("0102")),
+            Err(Error::WebhookEventTypeNotSupported)
+        ));
+
+        let handler = handler.with_vls_backend(Box::new(EchoVlsBackend));
+        let response = handler
+            .handle_remote_signing_webhook_msg(&vls_event("0102"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            response.variables,
+            json!({"node_id": "node_id", "message": "0102"})
+        );
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&vls_event("")),
+            Err(Error::VlsBackendError(_))
+        ));
+    }
+
+    #[test]
+    fn test_handle_sign_invoice_invalid_hash() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator));
+        let event = event(json!({
+            "sub_event_type": "SIGN_INVOICE",
+            "bitcoin_network": "REGTEST",
+            "invoice_id": "invoice_id",
+            "invoice_hash": "not hex",
+        }));
+
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&event),
+            Err(Error::HexEncodingError)
+        ));
+    }
+
+    /// Serves a fixed preimage for the invoice `hodl_invoice_id`.
+    struct HodlPreimageProvider;
+
+    const HODL_PREIMAGE: &str = "0000000000000000000000000000000000000000000000000000000000000001";
+
+    impl PaymentPreimageProvider for HodlPreimageProvider {
+        fn release_preimage(&self, invoice_id: &str) -> Result<Option<String>, Error> {
+            match invoice_id {
+                "hodl_invoice_id" => Ok(Some(HODL_PREIMAGE.to_owned())),
+                "failing_invoice_id" => Err(Error::WebhookEventDataMissing),
+                _ => Ok(None),
+            }
+        }
+
+        fn get_payment_hash(&self, invoice_id: &str) -> Result<Option<String>, Error> {
+            Ok(self.release_preimage(invoice_id)?.map(|preimage| {
+                let preimage = hex::decode(preimage).unwrap();
+                hex::encode(sha256::Hash::hash(&preimage).to_byte_array())
+            }))
+        }
+    }
+
+    fn release_payment_preimage_event(invoice_id: &str, nonce: Option<&str>) -> WebhookEvent {
+        event(json!({
+            "sub_event_type": "RELEASE_PAYMENT_PREIMAGE",
+            "bitcoin_network": "REGTEST",
+            "invoice_id": invoice_id,
+            "preimage_nonce": nonce,
+        }))
+    }
+
+    #[test]
+    fn test_preimage_provider() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator))
+            .with_preimage_provider(Box::new(HodlPreimageProvider));
+
+        let event = event(json!({
+            "sub_event_type": "REQUEST_INVOICE_PAYMENT_HASH",
+            "bitcoin_network": "REGTEST",
+            "invoice_id": "hodl_invoice_id",
+        }));
+        let response = handler
+            .handle_remote_signing_webhook_msg(&event)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            response.variables,
+            json!({
+                "invoice_id": "hodl_invoice_id",
+                "payment_hash": HodlPreimageProvider.get_payment_hash("hodl_invoice_id").unwrap().unwrap(),
+                "preimage_nonce": null,
+            })
+        );
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&release_payment_preimage_event(
+                "hodl_invoice_id",
+                None,
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.variables["payment_preimage"], HODL_PREIMAGE);
+
+        // The preimage of the provider wins over the nonce.
+        let response = handler
+            .handle_remote_signing_webhook_msg(&release_payment_preimage_event(
+                "hodl_invoice_id",
+                Some(&"00".repeat(32)),
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.variables["payment_preimage"], HODL_PREIMAGE);
+
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&release_payment_preimage_event(
+                "failing_invoice_id",
+                None,
+            )),
+            Err(Error::WebhookEventDataMissing)
+        ));
+    }
+
+    #[test]
+    fn test_preimage_provider_falls_back_to_nonce() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator))
+            .with_preimage_provider(Box::new(HodlPreimageProvider));
+
+        // The provider does not manage the invoice, so the payment hash comes from a new nonce.
+        let event = event(json!({
+            "sub_event_type": "REQUEST_INVOICE_PAYMENT_HASH",
+            "bitcoin_network": "REGTEST",
+            "invoice_id": "invoice_id",
+        }));
+        let response = handler
+            .handle_remote_signing_webhook_msg(&event)
+            .unwrap()
+            .unwrap();
+        let nonce = response.variables["preimage_nonce"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+        let payment_hash = signer()
+            .generate_preimage_hash(hex::decode(&nonce).unwrap())
+            .unwrap();
+        assert_eq!(
+            response.variables["payment_hash"],
+            hex::encode(payment_hash)
+        );
+
+        let response = handler
+            .handle_remote_signing_webhook_msg(&release_payment_preimage_event(
+                "invoice_id",
+                Some(&nonce),
+            ))
+            .unwrap()
+            .unwrap();
+        let preimage = signer()
+            .generate_preimage(hex::decode(&nonce).unwrap())
+            .unwrap();
+        assert_eq!(
+            response.variables["payment_preimage"],
+            hex::encode(preimage)
+        );
+    }
+
+    #[test]
+    fn test_release_payment_preimage_without_nonce() {
+        let handler = Handler::new(signer(), Box::new(PositiveValidator));
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&release_payment_preimage_event(
+                "invoice_id",
+                None
+            )),
+            Err(Error::PreimageNonceMissing)
+        ));
+
+        let event = event(json!({
+            "sub_event_type": "RELEASE_PAYMENT_PREIMAGE",
+            "bitcoin_network": "REGTEST",
+            "invoice_id": "invoice_id",
+        }));
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&event),
+            Err(Error::PreimageNonceMissing)
+        ));
+
+        let handler = handler.with_preimage_provider(Box::new(HodlPreimageProvider));
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&event),
+            Err(Error::PreimageNonceMissing)
+        ));
+    }
+
+    #[test]
+    fn test_declines_request_without_signing_jobs() {
+        let handler = Handler::new_with_request_validator(signer(), Box::new(RegtestPathValidator));
+        let event = event(json!({
+            "sub_event_type": "ECDH",
+            "bitcoin_network": "MAINNET",
+            "peer_public_key": PEER_PUBLIC_KEY,
+        }));
+
+        assert!(matches!(
+            handler.handle_remote_signing_webhook_msg(&event),
+            Err(Error::Declined(reason)) if reason == "not regtest"
+        ));
+    }
 }
@@ -1,6 +1,19 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
 use crate::Error;
 
+/// A provider of the payment preimages of invoices managed outside of the signer, e.g. HODL
+/// invoices whose preimage is only known once the payment can be settled.
+///
+/// When a `handler::Handler` has a provider, the payment hashes and preimages of invoices are
+/// requested from it first. The invoices it returns `None` for are served from the nonce-based
+/// derivation of the signer, so a provider only needs to know about the invoices it manages.
 pub trait PaymentPreimageProvider: Send + Sync {
-    fn release_preimage(&self, invoice_id: &str) -> Result<String, Error>;
-    fn get_payment_hash(&self, invoice_id: &str) -> Result<String, Error>;
+    /// Returns the hex encoded payment preimage of the given invoice, or `None` if the invoice is
+    /// not managed by the provider.
+    fn release_preimage(&self, invoice_id: &str) -> Result<Option<String>, Error>;
+
+    /// Returns the hex encoded payment hash of the given invoice, or `None` if the invoice is not
+    /// managed by the provider.
+    fn get_payment_hash(&self, invoice_id: &str) -> Result<Option<String>, Error>;
 }
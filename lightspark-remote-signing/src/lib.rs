@@ -10,13 +10,21 @@
 use std::fmt;
 
 pub extern crate lightspark;
+pub mod async_handler;
+pub mod audit;
+pub mod channel_state;
+#[cfg(feature = "client")]
+pub mod client;
 pub mod handler;
 pub mod invoice;
+pub mod registry;
 pub mod response;
+pub mod shachain;
 pub mod signer;
 pub mod signing_requests;
 pub mod signing_responses;
 pub mod validation;
+pub mod vls;
 
 #[derive(Debug)]
 pub enum Error {
@@ -27,6 +35,17 @@ pub enum Error {
     HexEncodingError,
     SignerError(signer::Error),
     WebhookEventTypeNotSupported,
+    VlsBackendError(String),
+    PolicyError(String),
+    ChannelStateStoreError(String),
+    CommitmentNotRevoked(u64),
+    InvalidCounterpartySecret(String),
+    LightsparkError(lightspark::error::Error),
+    UnknownNode(String),
+    UnknownChannel(String),
+    PreimageNonceMissing,
+    AuditSinkError(String),
+    Declined(String),
 }
 
 impl fmt::Display for Error {
@@ -43,6 +62,27 @@ impl fmt::Display for Error {
             Error::WebhookEventTypeNotSupported => {
                 "Remote signing event type not supported".to_string()
             }
+            Error::VlsBackendError(e) => format!("VLS backend error: {}", e),
+            Error::PolicyError(e) => format!("Invalid signing policy: {}", e),
+            Error::ChannelStateStoreError(e) => format!("Channel state store error: {}", e),
+            Error::CommitmentNotRevoked(idx) => format!(
+                "Refusing to release the per-commitment secret of unrevoked commitment {}",
+                idx
+            ),
+            Error::InvalidCounterpartySecret(e) => {
+                format!("Invalid counterparty per-commitment secret: {}", e)
+            }
+            Error::LightsparkError(e) => format!("Lightspark error: {}", e),
+            Error::UnknownNode(node_id) => format!("No signer is registered for node {}", node_id),
+            Error::UnknownChannel(channel_id) => {
+                format!("The node of channel {} is unknown", channel_id)
+            }
+            Error::PreimageNonceMissing => {
+                "Payment preimage nonce is missing and no preimage provider has the preimage"
+                    .to_string()
+            }
+            Error::AuditSinkError(e) => format!("Audit sink error: {}", e),
+            Error::Declined(reason) => format!("Declined to handle the request: {}", reason),
         };
         write!(f, "{}", msg)
     }
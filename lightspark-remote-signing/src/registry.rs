@@ -0,0 +1,311 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use lightspark::webhooks::WebhookEvent;
+
+use crate::{
+    async_handler::AsyncHandler,
+    handler::Handler,
+    response::Response,
+    signer::{LightsparkSigner, Network, Seed},
+    signing_requests::WebhookEventEntity,
+    Error,
+};
+
+/// A source of the master seeds of the remote signing nodes, keyed by node id.
+pub trait KeyProvider: Send + Sync {
+    /// Returns the master seed of the given node, or `None` if the node is unknown.
+    fn seed(&self, node_id: &str) -> Result<Option<Seed>, Error>;
+
+    /// Returns the id of the node the given channel belongs to, or `None` if the channel is
+    /// unknown. Used to route the per-commitment events, whose entity is a channel, when they do
+    /// not carry the node id.
+    fn node_id_for_channel(&self, _channel_id: &str) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+}
+
+/// A `KeyProvider` keeping the seeds, and the nodes of the channels, in memory.
+#[derive(Default)]
+pub struct InMemoryKeyProvider {
+    seeds: Mutex<HashMap<String, Seed>>,
+    channel_nodes: Mutex<HashMap<String, String>>,
+}
+
+impl InMemoryKeyProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the seed of a node, replacing any previous seed of that node.
+    pub fn insert(&self, node_id: &str, seed: Seed) {
+        self.seeds.lock().unwrap().insert(node_id.to_owned(), seed);
+    }
+
+    /// Records the node a channel belongs to.
+    pub fn insert_channel(&self, channel_id: &str, node_id: &str) {
+        self.channel_nodes
+            .lock()
+            .unwrap()
+            .insert(channel_id.to_owned(), node_id.to_owned());
+    }
+}
+
+impl KeyProvider for InMemoryKeyProvider {
+    fn seed(&self, node_id: &str) -> Result<Option<Seed>, Error> {
+        Ok(self.seeds.lock().unwrap().get(node_id).cloned())
+    }
+
+    fn node_id_for_channel(&self, channel_id: &str) -> Result<Option<String>, Error> {
+        Ok(self.channel_nodes.lock().unwrap().get(channel_id).cloned())
+    }
+}
+
+type HandlerBuilder<H> = Box<dyn Fn(&str, LightsparkSigner) -> Result<H, Error> + Send + Sync>;
+
+/// Routes the remote signing webhook events of several nodes to a handler built around the
+/// signer of the node the event is for.
+///
+/// The node of an event is its `entity_id`, except for the per-commitment events, whose
+/// `entity_id` is a channel. Those are routed to the `node_id` of the event data if there is one,
+/// and to the node the key provider returns for the channel otherwise; they are rejected with
+/// `Error::UnknownChannel` if neither is known.
+///
+/// The handler of a node is built the first time an event for that node is received, from the
+/// seed returned by the key provider, and reused for the following events. Events for nodes the
+/// key provider does not know are rejected with `Error::UnknownNode`.
+pub struct SignerRegistry<H = Handler> {
+    key_provider: Box<dyn KeyProvider>,
+    network: Network,
+    handler_builder: HandlerBuilder<H>,
+    handlers: Mutex<HashMap<String, Arc<H>>>,
+}
+
+impl<H> SignerRegistry<H> {
+    /// Create a new registry.
+    /// # Arguments
+    ///
+    /// * `key_provider` - A KeyProvider instance, which will be used to load the seed of each node.
+    /// * `network` - The bitcoin network the nodes run on.
+    /// * `handler_builder` - A function building the handler of a node from its id and signer,
+    ///   which allows each node to have its own validator or channel state store. The node id
+    ///   should be passed to `with_node_id` if the handler has an audit sink.
+    pub fn new<F>(key_provider: Box<dyn KeyProvider>, network: Network, handler_builder: F) -> Self
+    where
+        F: Fn(&str, LightsparkSigner) -> Result<H, Error> + Send + Sync + 'static,
+    {
+        Self {
+            key_provider,
+            network,
+            handler_builder: Box::new(handler_builder),
+            handlers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the handler of the given node, building it if needed.
+    ///
+    /// The handler is built without holding the registry lock, so that loading a seed does not
+    /// block the requests of other nodes. Concurrent first requests of a node may therefore each
+    /// build a handler, in which case only the first one inserted is kept and returned.
+    pub fn handler(&self, node_id: &str) -> Result<Arc<H>, Error> {
+        if let Some(handler) = self.handlers.lock().unwrap().get(node_id) {
+            return Ok(handler.clone());
+        }
+
+        let seed = self
+            .key_provider
+            .seed(node_id)?
+            .ok_or_else(|| Error::UnknownNode(node_id.to_owned()))?;
+        let signer = LightsparkSigner::new(&seed, self.network).map_err(Error::SignerError)?;
+        let handler = Arc::new((self.handler_builder)(node_id, signer)?);
+        Ok(self
+            .handlers
+            .lock()
+            .unwrap()
+            .entry(node_id.to_owned())
+            .or_insert(handler)
+            .clone())
+    }
+
+    /// Returns the id of the node the event is for.
+    pub fn node_id(&self, event: &WebhookEvent) -> Result<String, Error> {
+        match WebhookEventEntity::from_webhook_event(event) {
+            WebhookEventEntity::Node(node_id) => Ok(node_id.to_owned()),
+            WebhookEventEntity::Channel {
+                node_id: Some(node_id),
+                ..
+            } => Ok(node_id.to_owned()),
+            WebhookEventEntity::Channel {
+                channel_id,
+                node_id: None,
+            } => self
+                .key_provider
+                .node_id_for_channel(channel_id)?
+                .ok_or_else(|| Error::UnknownChannel(channel_id.to_owned())),
+        }
+    }
+
+    /// Forgets the handler of the given node, so that it is rebuilt from a freshly loaded seed
+    /// on the next event for that node.
+    pub fn remove(&self, node_id: &str) {
+        self.handlers.lock().unwrap().remove(node_id);
+    }
+}
+
+impl SignerRegistry<Handler> {
+    /// Handles the event with the handler of the node it is for. See
+    /// `Handler::handle_remote_signing_webhook_msg`.
+    pub fn handle_remote_signing_webhook_msg(
+        &self,
+        event: &WebhookEvent,
+    ) -> Result<Option<Response>, Error> {
+        self.handler(&self.node_id(event)?)?
+            .handle_remote_signing_webhook_msg(event)
+    }
+}
+
+impl SignerRegistry<AsyncHandler> {
+    /// Handles the event with the handler of the node it is for. See
+    /// `AsyncHandler::handle_remote_signing_webhook_msg`.
+    pub async fn handle_remote_signing_webhook_msg(
+        &self,
+        event: &WebhookEvent,
+    ) -> Result<Option<Response>, Error> {
+        self.handler(&self.node_id(event)?)?
+            .handle_remote_signing_webhook_msg(event)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::PositiveValidator;
+    use serde_json::json;
+
+    fn ecdh_event(node_id: &str) -> WebhookEvent {
+        serde_json::from_value(json!({
+            "event_type": "REMOTE_SIGNING",
+            "event_id": "event_id",
+            "timestamp": "2023-09-18T23:50:15.355980+00:00",
+            "entity_id": node_id,
+            "wallet_id": null,
+            "data": {
+                "sub_event_type": "ECDH",
+                "bitcoin_network": "REGTEST",
+                "peer_public_key": "02a1633cafcc01ebfb6d78e39f687a1f0995c62fc95f51ead10a02ee0be551b5dc",
+            },
+        }))
+        .unwrap()
+    }
+
+    fn per_commitment_event(sub_event_type: &str, node_id: Option<&str>) -> WebhookEvent {
+        let mut data = json!({
+            "sub_event_type": sub_event_type,
+            "bitcoin_network": "REGTEST",
+            "derivation_path": "m/3/2104864975",
+            "per_commitment_point_idx": 281474976710655u64,
+        });
+        if let Some(node_id) = node_id {
+            data["node_id"] = json!(node_id);
+        }
+        serde_json::from_value(json!({
+            "event_type": "REMOTE_SIGNING",
+            "event_id": "event_id",
+            "timestamp": "2023-09-18T23:50:15.355980+00:00",
+            "entity_id": "channel_1",
+            "wallet_id": null,
+            "data": data,
+        }))
+        .unwrap()
+    }
+
+    fn registry() -> SignerRegistry {
+        let key_provider = InMemoryKeyProvider::new();
+        key_provider.insert("node_1", Seed::new(vec![1; 32]));
+        key_provider.insert("node_2", Seed::new(vec![2; 32]));
+        key_provider.insert_channel("channel_1", "node_2");
+        SignerRegistry::new(
+            Box::new(key_provider),
+            Network::Regtest,
+            |node_id, signer| {
+                Ok(Handler::new(signer, Box::new(PositiveValidator)).with_node_id(node_id))
+            },
+        )
+    }
+
+    #[test]
+    fn test_routes_events_by_node_id() {
+        let registry = registry();
+        let response_1 = registry
+            .handle_remote_signing_webhook_msg(&ecdh_event("node_1"))
+            .unwrap()
+            .unwrap();
+        let response_2 = registry
+            .handle_remote_signing_webhook_msg(&ecdh_event("node_2"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(response_1.variables["node_id"], "node_1");
+        assert_eq!(response_2.variables["node_id"], "node_2");
+        assert_ne!(
+            response_1.variables["shared_secret"],
+            response_2.variables["shared_secret"]
+        );
+
+        assert!(Arc::ptr_eq(
+            &registry.handler("node_1").unwrap(),
+            &registry.handler("node_1").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_rejects_unknown_node() {
+        let registry = registry();
+        assert!(matches!(
+            registry.handle_remote_signing_webhook_msg(&ecdh_event("node_3")),
+            Err(Error::UnknownNode(node_id)) if node_id == "node_3"
+        ));
+    }
+
+    #[test]
+    fn test_routes_channel_events_to_their_node() {
+        let registry = registry();
+        let point = |node_id| {
+            registry
+                .handle_remote_signing_webhook_msg(&per_commitment_event(
+                    "GET_PER_COMMITMENT_POINT",
+                    node_id,
+                ))
+                .unwrap()
+                .unwrap()
+                .variables["per_commitment_point"]
+                .clone()
+        };
+        assert_eq!(point(None), point(Some("node_2")));
+        assert_ne!(point(None), point(Some("node_1")));
+
+        let response = registry
+            .handle_remote_signing_webhook_msg(&per_commitment_event(
+                "RELEASE_PER_COMMITMENT_SECRET",
+                None,
+            ))
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.variables["channel_id"], "channel_1");
+        assert_eq!(registry.handlers.lock().unwrap().len(), 2);
+        assert!(registry.handlers.lock().unwrap().get("channel_1").is_none());
+    }
+
+    #[test]
+    fn test_rejects_unknown_channel() {
+        let registry = registry();
+        let mut event = per_commitment_event("RELEASE_PER_COMMITMENT_SECRET", None);
+        event.entity_id = "channel_2".to_owned();
+        assert!(matches!(
+            registry.handle_remote_signing_webhook_msg(&event),
+            Err(Error::UnknownChannel(channel_id)) if channel_id == "channel_2"
+        ));
+    }
+}
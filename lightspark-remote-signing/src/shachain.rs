@@ -0,0 +1,266 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+//! Compact storage of the per-commitment secrets revealed by a channel counterparty.
+//!
+//! Per-commitment secrets are generated from a seed so that any secret can be derived from the
+//! secrets of lower indices. Storing at most 49 secrets is therefore enough to derive every
+//! secret revealed so far, as described in [Bolt 3].
+//!
+//! [Bolt 3]: https://github.com/lightning/bolts/blob/master/03-transactions.md#efficient-per-commitment-secret-storage
+
+use bitcoin::hashes::{sha256, Hash};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// The number of bits of a per-commitment index.
+const INDEX_BITS: usize = 48;
+
+/// The per-commitment secrets revealed by the counterparty of a channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CounterpartySecrets {
+    known: Vec<Option<KnownSecret>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct KnownSecret {
+    index: u64,
+
+    #[serde(with = "hex::serde")]
+    secret: [u8; 32],
+}
+
+impl Default for CounterpartySecrets {
+    fn default() -> Self {
+        Self {
+            known: vec![None; INDEX_BITS + 1],
+        }
+    }
+}
+
+impl CounterpartySecrets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores the secret revealed for the commitment with the given index. Secrets must be
+    /// inserted in decreasing index order, as they are revealed.
+    ///
+    /// Fails with `Error::InvalidCounterpartySecret` if the index is out of range or if the
+    /// secret is not consistent with the secrets inserted before, i.e. it was not generated from
+    /// the same seed. Inserting an already known secret again is a no-op.
+    pub fn insert_secret(&mut self, index: u64, secret: [u8; 32]) -> Result<(), Error> {
+        if index >> INDEX_BITS != 0 {
+            return Err(Error::InvalidCounterpartySecret(format!(
+                "index {} is out of range",
+                index
+            )));
+        }
+        match self.get_secret(index) {
+            Some(known) if known == secret => return Ok(()),
+            Some(_) => {
+                return Err(Error::InvalidCounterpartySecret(format!(
+                    "secret {} differs from the one revealed before",
+                    index
+                )))
+            }
+            None => {}
+        }
+
+        let position = index.trailing_zeros().min(INDEX_BITS as u32) as usize;
+        for known in self.known[..position].iter().flatten() {
+            if derive_secret(secret, position, known.index) != known.secret {
+                return Err(Error::InvalidCounterpartySecret(format!(
+                    "secret {} is inconsistent with secret {}",
+                    index, known.index
+                )));
+            }
+        }
+        self.known[position] = Some(KnownSecret { index, secret });
+        Ok(())
+    }
+
+    /// Returns the secret revealed for the commitment with the given index, if it was revealed.
+    pub fn get_secret(&self, index: u64) -> Option<[u8; 32]> {
+        self.known.iter().enumerate().find_map(|(position, known)| {
+            let known = known.as_ref()?;
+            let mask = !((1u64 << position) - 1);
+            (index & mask == known.index).then(|| derive_secret(known.secret, position, index))
+        })
+    }
+
+    /// The lowest index a secret was revealed for, i.e. the latest revoked commitment.
+    pub fn min_index(&self) -> Option<u64> {
+        self.known.iter().flatten().map(|known| known.index).min()
+    }
+}
+
+/// Derives the secret of `index` from `base`, the secret of an index sharing all but the lowest
+/// `bits` bits of `index`.
+fn derive_secret(base: [u8; 32], bits: usize, index: u64) -> [u8; 32] {
+    let mut secret = base;
+    for bit in (0..bits).rev() {
+        if index & (1 << bit) != 0 {
+            secret[bit / 8] ^= 1 << (bit % 8);
+            secret = sha256::Hash::hash(&secret).to_byte_array();
+        }
+    }
+    secret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_INDEX: u64 = (1 << INDEX_BITS) - 1;
+
+    fn generate_from_seed(seed: [u8; 32], index: u64) -> [u8; 32] {
+        derive_secret(seed, INDEX_BITS, index)
+    }
+
+    fn secret(hex_str: &str) -> [u8; 32] {
+        hex::decode(hex_str).unwrap().try_into().unwrap()
+    }
+
+    #[test]
+    fn test_generate_from_seed_vectors() {
+        assert_eq!(
+            generate_from_seed([0; 32], MAX_INDEX),
+            secret("02a40c85b6f28da08dfdbe0926c53fab2de6d28c10301f8f7c4073d5e42e3148")
+        );
+        assert_eq!(
+            generate_from_seed([0xff; 32], MAX_INDEX),
+            secret("7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc")
+        );
+    }
+
+    /// The secrets of the bolt 3 storage tests, for indices `MAX_INDEX` down to `MAX_INDEX - 7`.
+    const BOLT3_SECRETS: [&str; 8] = [
+        "7cc854b54e3e0dcdb010d7a3fee464a9687be6e8db3be6854c475621e007a5dc",
+        "c7518c8ae4660ed02894df8976fa1a3659c1a8b4b5bec0c4b872abeba4cb8964",
+        "2273e227a5b7449b6e70f1fb4652864038b1cbf9cd7c043a7d6456b7fc275ad8",
+        "27cddaa5624534cb6cb9d7da077cf2b22ab21e9b506fd4998a51d54502e99116",
+        "c65716add7aa98ba7acb236352d665cab17345fe45b55fb879ff80e6bd0c41dd",
+        "969660042a28f32d9be17344e09374b379962d03db1574df5a8a5a47e19ce3f2",
+        "a5a64476122ca0925fb344bdc1854c1c0a59fc614298e50a33e331980a220f32",
+        "05cde6323d949933f7f7b78776bcc1ea6d9b31447732e3802e1f7ac44b650e17",
+    ];
+
+    /// The incorrect secrets of the bolt 3 storage tests, for the same indices.
+    const BOLT3_INCORRECT_SECRETS: [&str; 8] = [
+        "02a40c85b6f28da08dfdbe0926c53fab2de6d28c10301f8f7c4073d5e42e3148",
+        "dddc3a8d14fddf2b68fa8c7fbad2748274937479dd0f8930d5ebb4ab6bd866a3",
+        "c51a18b13e8527e579ec56365482c62f180b7d5760b46e9477dae59e87ed423a",
+        "ba65d7b0ef55a3ba300d4e87af29868f394f8f138d78a7011669c79b37b936f4",
+        "631373ad5f9ef654bb3dade742d09504c567edd24320d2fcd68e3cc47e2ff6a6",
+        "b7e76a83668bde38b373970155c868a653304308f9896692f904a23731224bb1",
+        "e7971de736e01da8ed58b94c2fc216cb1dca9e326f3a96e7194fe8ea8af6c0a3",
+        "a7efbc61aac46d34f77778bac22c8a20c6a46ca460addc49009bda875ec88fa4",
+    ];
+
+    #[test]
+    fn test_insert_secret_correct_sequence() {
+        let mut secrets = CounterpartySecrets::new();
+        for (i, hex_str) in BOLT3_SECRETS.iter().enumerate() {
+            secrets
+                .insert_secret(MAX_INDEX - i as u64, secret(hex_str))
+                .unwrap();
+        }
+        for (i, hex_str) in BOLT3_SECRETS.iter().enumerate() {
+            assert_eq!(
+                secrets.get_secret(MAX_INDEX - i as u64),
+                Some(secret(hex_str))
+            );
+        }
+    }
+
+    #[test]
+    fn test_insert_secret_incorrect() {
+        // Each case lists the secrets replaced by incorrect ones, and the first secret whose
+        // insertion must fail, as offsets from `MAX_INDEX`.
+        let cases: [(&[usize], usize); 8] = [
+            (&[0], 1),
+            (&[0, 1], 3),
+            (&[2], 3),
+            (&[0, 1, 2, 3], 7),
+            (&[4], 5),
+            (&[4, 5], 7),
+            (&[6], 7),
+            (&[7], 7),
+        ];
+        for (incorrect, failing) in cases {
+            let mut secrets = CounterpartySecrets::new();
+            for i in 0..=failing {
+                let hex_str = if incorrect.contains(&i) {
+                    BOLT3_INCORRECT_SECRETS[i]
+                } else {
+                    BOLT3_SECRETS[i]
+                };
+                let result = secrets.insert_secret(MAX_INDEX - i as u64, secret(hex_str));
+                if i == failing {
+                    assert!(
+                        matches!(result, Err(Error::InvalidCounterpartySecret(_))),
+                        "case {:?} did not fail at {}",
+                        incorrect,
+                        i
+                    );
+                } else {
+                    result.unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_and_derive() {
+        let seed = [0xff; 32];
+        let mut secrets = CounterpartySecrets::new();
+        for index in (MAX_INDEX - 1000..=MAX_INDEX).rev() {
+            secrets
+                .insert_secret(index, generate_from_seed(seed, index))
+                .unwrap();
+        }
+        assert_eq!(secrets.min_index(), Some(MAX_INDEX - 1000));
+        assert!(secrets.known.iter().flatten().count() <= INDEX_BITS + 1);
+        for index in (MAX_INDEX - 1000..=MAX_INDEX).step_by(7) {
+            assert_eq!(
+                secrets.get_secret(index),
+                Some(generate_from_seed(seed, index))
+            );
+        }
+        assert_eq!(secrets.get_secret(MAX_INDEX - 1001), None);
+
+        // Revealing the same secret again is accepted, a different one is not.
+        secrets
+            .insert_secret(MAX_INDEX - 3, generate_from_seed(seed, MAX_INDEX - 3))
+            .unwrap();
+        assert!(secrets.insert_secret(MAX_INDEX - 3, [0; 32]).is_err());
+
+        let json = serde_json::to_string(&secrets).unwrap();
+        assert_eq!(
+            serde_json::from_str::<CounterpartySecrets>(&json).unwrap(),
+            secrets
+        );
+    }
+
+    #[test]
+    fn test_insert_inconsistent_secret() {
+        let seed = [0xff; 32];
+        let mut secrets = CounterpartySecrets::new();
+        for index in [MAX_INDEX, MAX_INDEX - 1] {
+            secrets
+                .insert_secret(index, generate_from_seed(seed, index))
+                .unwrap();
+        }
+        // An odd index is stored in the first position without any check.
+        secrets
+            .insert_secret(MAX_INDEX - 2, generate_from_seed([0; 32], MAX_INDEX - 2))
+            .unwrap();
+        // The next index reveals that the previous secret was not derived from the seed.
+        assert!(matches!(
+            secrets.insert_secret(MAX_INDEX - 3, generate_from_seed(seed, MAX_INDEX - 3)),
+            Err(Error::InvalidCounterpartySecret(_))
+        ));
+        assert!(secrets.insert_secret(1 << INDEX_BITS, [0; 32]).is_err());
+    }
+}
@@ -1,25 +1,45 @@
 // Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
 
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 use std::str::FromStr;
 
-use bitcoin::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey};
+use base64::Engine;
+use bitcoin::bip32::{ChainCode, DerivationPath, ExtendedPrivKey, ExtendedPubKey};
 use bitcoin::hashes::{sha512, Hash, HashEngine, Hmac, HmacEngine};
 use bitcoin::secp256k1::ecdh::SharedSecret;
 use bitcoin::secp256k1::hashes::sha256;
 use bitcoin::secp256k1::{Message, PublicKey, Scalar, Secp256k1, SecretKey};
+use lightspark::crypto::{decrypt_private_key, encrypt};
 use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
 use tracing::debug;
+use zeroize::{Zeroize, Zeroizing};
+
+pub use bip39::Language;
 
 const NODE_KEY_PATH: &str = "m/0";
 
 #[derive(Copy, Clone, Debug)]
 pub enum Error {
+    #[deprecated(
+        note = "bip39 errors are mapped to the mnemonic errors, e.g. `InvalidMnemonicChecksum`"
+    )]
     Bip39Error(bip39::Error),
     Secp256k1Error(bitcoin::secp256k1::Error),
     KeyDerivationError,
     EntropyLengthError,
     KeyTweakError,
+    SeedFileError(io::ErrorKind),
+    SeedFileFormatError,
+    SeedEncryptionError,
+    SeedDecryptionError,
+    InvalidMnemonicWordCount(usize),
+    UnknownMnemonicWord(usize),
+    InvalidMnemonicChecksum,
+    AmbiguousMnemonicLanguage,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -31,68 +51,246 @@ pub enum Network {
 }
 
 impl fmt::Display for Error {
+    #[allow(deprecated)]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Bip39Error(err) => write!(f, "Bip39 error {}", err),
             Self::Secp256k1Error(err) => write!(f, "Secp256k1 error {}", err),
             Self::KeyDerivationError => write!(f, "Key derivation error"),
-            Self::EntropyLengthError => write!(f, "Entropy must be 32 bytes"),
+            Self::EntropyLengthError => write!(f, "Entropy must be 16, 20, 24, 28 or 32 bytes"),
             Self::KeyTweakError => write!(f, "Key tweak error"),
+            Self::SeedFileError(kind) => write!(f, "Seed file error {}", kind),
+            Self::SeedFileFormatError => write!(f, "Invalid seed file"),
+            Self::SeedEncryptionError => write!(f, "Seed encryption error"),
+            Self::SeedDecryptionError => {
+                write!(f, "Seed decryption error, the passphrase may be wrong")
+            }
+            Self::InvalidMnemonicWordCount(count) => write!(
+                f,
+                "Invalid mnemonic word count {}, must be 12, 15, 18, 21 or 24",
+                count
+            ),
+            Self::UnknownMnemonicWord(idx) => write!(f, "Unknown mnemonic word at index {}", idx),
+            Self::InvalidMnemonicChecksum => write!(f, "Invalid mnemonic checksum"),
+            Self::AmbiguousMnemonicLanguage => {
+                write!(f, "Mnemonic words match more than one language")
+            }
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+impl From<bip39::Error> for Error {
+    fn from(err: bip39::Error) -> Self {
+        match err {
+            bip39::Error::BadWordCount(count) => Self::InvalidMnemonicWordCount(count),
+            bip39::Error::UnknownWord(idx) => Self::UnknownMnemonicWord(idx),
+            bip39::Error::BadEntropyBitCount(_) => Self::EntropyLengthError,
+            bip39::Error::InvalidChecksum => Self::InvalidMnemonicChecksum,
+            bip39::Error::AmbiguousLanguages(_) => Self::AmbiguousMnemonicLanguage,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Mnemonic {
     internal: bip39::Mnemonic,
 }
 
 impl Mnemonic {
+    /// Generates a random 24 word english mnemonic.
     pub fn random() -> Result<Mnemonic, Error> {
-        let internal = bip39::Mnemonic::generate(24).map_err(Error::Bip39Error)?;
+        Self::random_in(Language::English, 24)
+    }
+
+    /// Generates a random mnemonic with the given number of words, which must be 12, 15, 18, 21
+    /// or 24, from the given wordlist.
+    pub fn random_in(language: Language, word_count: usize) -> Result<Mnemonic, Error> {
+        let internal = bip39::Mnemonic::generate_in(language, word_count)?;
         Ok(Self { internal })
     }
 
+    /// Creates the english mnemonic of the given entropy, which must be 16, 20, 24, 28 or 32
+    /// bytes long for a 12, 15, 18, 21 or 24 word mnemonic.
     pub fn from_entropy(entropy: Vec<u8>) -> Result<Mnemonic, Error> {
-        let slice = entropy.as_slice();
-        let array: [u8; 32] = slice.try_into().map_err(|_| Error::EntropyLengthError)?;
-        let internal = bip39::Mnemonic::from_entropy(&array).map_err(Error::Bip39Error)?;
+        let entropy = Zeroizing::new(entropy);
+        let internal = bip39::Mnemonic::from_entropy(&entropy)?;
         Ok(Self { internal })
     }
 
+    /// Parses an english mnemonic, validating its checksum.
     pub fn from_phrase(phrase: String) -> Result<Mnemonic, Error> {
-        let internal =
-            bip39::Mnemonic::parse_normalized(phrase.as_str()).map_err(Error::Bip39Error)?;
+        let internal = bip39::Mnemonic::parse_in_normalized(Language::English, phrase.as_str())?;
+        Ok(Self { internal })
+    }
+
+    /// Parses a mnemonic from the given wordlist, validating its checksum. The phrase is
+    /// normalized first, so it may come from any input method.
+    pub fn from_phrase_in(language: Language, phrase: String) -> Result<Mnemonic, Error> {
+        let internal = bip39::Mnemonic::parse_in(language, phrase)?;
         Ok(Self { internal })
     }
 
     pub fn as_string(&self) -> String {
         self.internal.to_string()
     }
+
+    pub fn language(&self) -> Language {
+        self.internal.language()
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.internal.word_count()
+    }
 }
 
+/// A master seed. The seed is zeroized when dropped.
 #[derive(Clone)]
 pub struct Seed {
     seed: Vec<u8>,
 }
 
+/// The content of an encrypted seed file. The seed is encrypted with `lightspark::crypto::encrypt`,
+/// `cipher` being the versioned header describing the key derivation and the encryption.
+#[derive(Serialize, Deserialize)]
+struct EncryptedSeedFile {
+    cipher: String,
+    encrypted_seed: String,
+}
+
+/// The length of the salt prepended to the encrypted seed.
+const SEED_SALT_LEN: usize = 16;
+
+/// The length of the authentication tag appended to the encrypted seed.
+const SEED_TAG_LEN: usize = 16;
+
+/// The only header version `save_encrypted` writes: AES-256-GCM with a PBKDF2-SHA256 key.
+const SEED_CIPHER_VERSION: u32 = 4;
+
+/// The bounds on the PBKDF2 iteration count of a seed file. `save_encrypted` uses 500000; the
+/// bounds reject files weakened to a few iterations, or made to hang the signer on load.
+const SEED_MIN_ITERATIONS: u32 = 100_000;
+const SEED_MAX_ITERATIONS: u32 = 10_000_000;
+
+/// The header of an encrypted seed file, e.g. `{"v":4,"i":500000}`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedCipherHeader {
+    v: u32,
+    i: u32,
+}
+
 impl Seed {
     pub fn from_mnemonic(mnemonic: &Mnemonic) -> Self {
-        let seed = mnemonic.internal.to_seed("").to_vec();
-        Self { seed }
+        Self::from_mnemonic_with_passphrase(mnemonic, "")
+    }
+
+    /// Derives the seed of a mnemonic protected with a bip39 passphrase. A different passphrase
+    /// derives a different, equally valid, seed.
+    pub fn from_mnemonic_with_passphrase(mnemonic: &Mnemonic, passphrase: &str) -> Self {
+        let mut seed = mnemonic.internal.to_seed(passphrase);
+        let result = Self {
+            seed: seed.to_vec(),
+        };
+        seed.zeroize();
+        result
     }
 
     pub fn new(seed: Vec<u8>) -> Self {
         Self { seed }
     }
 
-    pub fn as_bytes(&self) -> Vec<u8> {
-        self.seed.clone()
+    /// Returns a copy of the seed, zeroized when dropped.
+    pub fn as_bytes(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new(self.seed.clone())
+    }
+
+    /// Loads a seed from a file written by `save_encrypted`. Files with another cipher version,
+    /// or an iteration count outside of sane bounds, are rejected with `Error::SeedFileFormatError`
+    /// before anything is derived from the passphrase.
+    /// # Arguments
+    ///
+    /// * `path` - The path of the seed file.
+    /// * `passphrase` - The passphrase the seed was encrypted with.
+    pub fn load_encrypted<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self, Error> {
+        let content = fs::read(path).map_err(|e| Error::SeedFileError(e.kind()))?;
+        let file: EncryptedSeedFile =
+            serde_json::from_slice(&content).map_err(|_| Error::SeedFileFormatError)?;
+        let header: SeedCipherHeader =
+            serde_json::from_str(&file.cipher).map_err(|_| Error::SeedFileFormatError)?;
+        if header.v != SEED_CIPHER_VERSION
+            || !(SEED_MIN_ITERATIONS..=SEED_MAX_ITERATIONS).contains(&header.i)
+        {
+            return Err(Error::SeedFileFormatError);
+        }
+        let encrypted_seed = base64::engine::general_purpose::STANDARD
+            .decode(&file.encrypted_seed)
+            .map_err(|_| Error::SeedFileFormatError)?;
+        if encrypted_seed.len() < SEED_SALT_LEN + SEED_TAG_LEN {
+            return Err(Error::SeedFileFormatError);
+        }
+
+        let seed = decrypt_private_key(&file.cipher, &file.encrypted_seed, passphrase)
+            .map_err(|_| Error::SeedDecryptionError)?;
+        Ok(Self { seed })
+    }
+
+    /// Encrypts the seed with a key derived from the passphrase and writes it to a file, replacing
+    /// any existing file. The file is written through a temporary file renamed over it, and is
+    /// only readable by its owner on unix.
+    /// # Arguments
+    ///
+    /// * `path` - The path of the seed file.
+    /// * `passphrase` - The passphrase to encrypt the seed with.
+    pub fn save_encrypted<P: AsRef<Path>>(&self, path: P, passphrase: &str) -> Result<(), Error> {
+        let (cipher, encrypted_seed) =
+            encrypt(&self.seed, passphrase).map_err(|_| Error::SeedEncryptionError)?;
+        let content = serde_json::to_vec_pretty(&EncryptedSeedFile {
+            cipher,
+            encrypted_seed,
+        })
+        .map_err(|_| Error::SeedEncryptionError)?;
+
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        write_private_file(Path::new(&tmp_path), &content)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| {
+                // The temporary file may have been written before the failure.
+                let _ = fs::remove_file(&tmp_path);
+                Error::SeedFileError(e.kind())
+            })
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.seed.zeroize();
     }
 }
 
+#[cfg(unix)]
+fn write_private_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(content)?;
+    file.sync_all()
+}
+
+#[cfg(not(unix))]
+fn write_private_file(path: &Path, content: &[u8]) -> io::Result<()> {
+    fs::write(path, content)
+}
+
 #[derive(Clone)]
 pub struct InvoiceSignature {
     signature: Vec<u8>,
@@ -109,11 +307,41 @@ impl InvoiceSignature {
     }
 }
 
+/// Overwrites the private key and chain code of an extended key.
+///
+/// This is best effort: `ExtendedPrivKey` and `SecretKey` are `Copy`, so copies made by the
+/// bitcoin and secp256k1 libraries, or left on the stack, are not erased, and the write of
+/// `non_secure_erase` may in theory be optimized out.
+fn erase_key(key: &mut ExtendedPrivKey) {
+    key.private_key.non_secure_erase();
+    key.chain_code = ChainCode::from([0; 32]);
+}
+
+/// A key derived from the master key for a single request, erased with `erase_key` when dropped.
+struct DerivedKey(ExtendedPrivKey);
+
+impl Drop for DerivedKey {
+    fn drop(&mut self) {
+        erase_key(&mut self.0);
+    }
+}
+
+/// A signer deriving its keys from a master seed.
+///
+/// The master and node private keys, and the keys and secrets derived from them for each request,
+/// are overwritten once they are no longer needed. See `erase_key` for the limits of the erasure.
 pub struct LightsparkSigner {
     master_private_key: ExtendedPrivKey,
     node_private_key: ExtendedPrivKey,
 }
 
+impl Drop for LightsparkSigner {
+    fn drop(&mut self) {
+        erase_key(&mut self.master_private_key);
+        erase_key(&mut self.node_private_key);
+    }
+}
+
 impl LightsparkSigner {
     pub fn new(seed: &Seed, network: Network) -> Result<LightsparkSigner, Error> {
         let network: bitcoin::Network = match network {
@@ -122,7 +350,7 @@ impl LightsparkSigner {
             Network::Regtest => bitcoin::Network::Regtest,
             Network::Signet => bitcoin::Network::Signet,
         };
-        let master_private_key = ExtendedPrivKey::new_master(network, seed.as_bytes().as_slice())
+        let master_private_key = ExtendedPrivKey::new_master(network, &seed.seed)
             .map_err(|_| Error::KeyDerivationError)?;
         let secp = Secp256k1::new();
         let node_key_path =
@@ -148,14 +376,8 @@ impl LightsparkSigner {
     }
 
     pub fn derive_public_key(&self, derivation_path: String) -> Result<String, Error> {
-        let secp = Secp256k1::new();
-        let path =
-            DerivationPath::from_str(&derivation_path).map_err(|_| Error::KeyDerivationError)?;
-        let private_key = self
-            .master_private_key
-            .derive_priv(&secp, &path)
-            .map_err(|_| Error::KeyDerivationError)?;
-        let pubkey = ExtendedPubKey::from_priv(&secp, &private_key);
+        let private_key = self.derive_key(derivation_path)?;
+        let pubkey = ExtendedPubKey::from_priv(&Secp256k1::new(), &private_key.0);
         Ok(pubkey.to_string())
     }
 
@@ -167,20 +389,21 @@ impl LightsparkSigner {
         mul_tweak: Option<Vec<u8>>,
     ) -> Result<Vec<u8>, Error> {
         let secp = Secp256k1::new();
-        let signing_key =
+        let mut signing_key =
             self.derive_and_tweak_key(derivation_path.clone(), add_tweak, mul_tweak)?;
-        let msg = Message::from_slice(message.as_slice()).map_err(Error::Secp256k1Error)?;
-        let signature = secp.sign_ecdsa(&msg, &signing_key);
+        let result = Message::from_slice(message.as_slice())
+            .map(|msg| secp.sign_ecdsa(&msg, &signing_key))
+            .map_err(Error::Secp256k1Error);
 
         debug!("Derivation: {}", derivation_path);
-        debug!("Signing Key: {}", hex::encode(signing_key.as_ref()));
         debug!(
             "Verification Key: {}",
             signing_key.public_key(&secp).to_string()
         );
         debug!("Message: {}", hex::encode(message.as_slice()));
+        signing_key.non_secure_erase();
 
-        Ok(signature.serialize_compact().to_vec())
+        Ok(result?.serialize_compact().to_vec())
     }
 
     pub fn ecdh(&self, public_key: Vec<u8>) -> Result<Vec<u8>, Error> {
@@ -195,11 +418,13 @@ impl LightsparkSigner {
         derivation_path: String,
         per_commitment_point_idx: u64,
     ) -> Result<Vec<u8>, Error> {
-        let per_commitment_secret =
-            self.release_per_commitment_secret(derivation_path, per_commitment_point_idx)?;
-        let secret_key = SecretKey::from_slice(per_commitment_secret.as_slice())
+        let per_commitment_secret = Zeroizing::new(
+            self.release_per_commitment_secret(derivation_path, per_commitment_point_idx)?,
+        );
+        let mut secret_key = SecretKey::from_slice(per_commitment_secret.as_slice())
             .map_err(Error::Secp256k1Error)?;
         let public_key = secret_key.public_key(&Secp256k1::new());
+        secret_key.non_secure_erase();
         Ok(public_key.serialize().to_vec())
     }
 
@@ -208,14 +433,11 @@ impl LightsparkSigner {
         derivation_path: String,
         per_commitment_point_idx: u64,
     ) -> Result<Vec<u8>, Error> {
-        let key = self
-            .derive_key(derivation_path)
-            .map_err(|_| Error::KeyDerivationError)?;
-        let channel_seed = sha256::Hash::hash(&key.private_key[..])
-            .as_byte_array()
-            .to_vec();
-        let commitment_seed = self.build_commitment_seed(channel_seed);
-        Ok(self.build_commitment_secret(commitment_seed, per_commitment_point_idx))
+        let key = self.derive_key(derivation_path)?;
+        let channel_seed =
+            Zeroizing::new(sha256::Hash::hash(&key.0.private_key[..]).to_byte_array());
+        let commitment_seed = Zeroizing::new(self.build_commitment_seed(&*channel_seed));
+        Ok(self.build_commitment_secret(&commitment_seed, per_commitment_point_idx))
     }
 
     pub fn generate_preimage_nonce(&self) -> Vec<u8> {
@@ -228,7 +450,7 @@ impl LightsparkSigner {
     pub fn generate_preimage(&self, nonce: Vec<u8>) -> Result<Vec<u8>, Error> {
         let key = self.derive_key("m/4h".to_owned())?;
         let mut hmac_engine: HmacEngine<sha512::Hash> =
-            HmacEngine::new(&key.private_key.secret_bytes());
+            HmacEngine::new(&*Zeroizing::new(key.0.private_key.secret_bytes()));
         hmac_engine.input(b"invoice preimage");
         hmac_engine.input(nonce.as_slice());
         let hmac_result: Hmac<sha512::Hash> = Hmac::from_engine(hmac_engine);
@@ -248,9 +470,7 @@ impl LightsparkSigner {
         add_tweak: Option<Vec<u8>>,
         mul_tweak: Option<Vec<u8>>,
     ) -> Result<SecretKey, Error> {
-        let derived_key = self
-            .derive_key(derivation_path)
-            .map_err(|_| Error::KeyDerivationError)?;
+        let derived_key = self.derive_key(derivation_path)?;
         let add_tweak: Option<[u8; 32]> = add_tweak
             .filter(|tweak| !tweak.is_empty())
             .map(|tweak| tweak.try_into().map_err(|_| Error::KeyTweakError))
@@ -259,10 +479,10 @@ impl LightsparkSigner {
             .filter(|tweak| !tweak.is_empty())
             .map(|tweak| tweak.try_into().map_err(|_| Error::KeyTweakError))
             .transpose()?;
-        self.tweak_key(derived_key.private_key, add_tweak, mul_tweak)
+        self.tweak_key(derived_key.0.private_key, add_tweak, mul_tweak)
     }
 
-    fn derive_key(&self, derivation_path: String) -> Result<ExtendedPrivKey, Error> {
+    fn derive_key(&self, derivation_path: String) -> Result<DerivedKey, Error> {
         let secp = Secp256k1::new();
         let path =
             DerivationPath::from_str(&derivation_path).map_err(|_| Error::KeyDerivationError)?;
@@ -270,26 +490,26 @@ impl LightsparkSigner {
             .master_private_key
             .derive_priv(&secp, &path)
             .map_err(|_| Error::KeyDerivationError)?;
-        Ok(private_key)
+        Ok(DerivedKey(private_key))
     }
 
-    fn build_commitment_seed(&self, seed: Vec<u8>) -> Vec<u8> {
+    fn build_commitment_seed(&self, seed: &[u8]) -> [u8; 32] {
         let mut hasher = sha256::Hash::engine();
-        hasher.input(seed.as_slice());
+        hasher.input(seed);
         hasher.input(&b"commitment seed"[..]);
-        sha256::Hash::from_engine(hasher).to_byte_array().to_vec()
+        sha256::Hash::from_engine(hasher).to_byte_array()
     }
 
-    fn build_commitment_secret(&self, seed: Vec<u8>, idx: u64) -> Vec<u8> {
-        let mut res = seed;
+    fn build_commitment_secret(&self, seed: &[u8; 32], idx: u64) -> Vec<u8> {
+        let mut res = Zeroizing::new(*seed);
         for i in 0..48 {
             let bitpos = 47 - i;
             if idx & (1 << bitpos) == (1 << bitpos) {
                 res[bitpos / 8] ^= 1 << (bitpos & 7);
-                res = sha256::Hash::hash(&res).to_byte_array().to_vec();
+                *res = sha256::Hash::hash(&*res).to_byte_array();
             }
         }
-        res
+        res.to_vec()
     }
 
     fn tweak_key(
@@ -355,13 +575,13 @@ mod tests {
 
         let signer = LightsparkSigner::new(&seed, Network::Bitcoin).unwrap();
         let xprv = signer.derive_key("m".to_owned()).unwrap();
-        let xprv_string = xprv.to_string();
+        let xprv_string = xprv.0.to_string();
         let expected_string = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
         assert_eq!(xprv_string.as_str(), expected_string);
 
         let signer = LightsparkSigner::new(&seed, Network::Bitcoin).unwrap();
         let xprv = signer.derive_key("m/0'".to_owned()).unwrap();
-        let xprv_string = xprv.to_string();
+        let xprv_string = xprv.0.to_string();
         let expected_string = "xprv9uHRZZhk6KAJC1avXpDAp4MDc3sQKNxDiPvvkX8Br5ngLNv1TxvUxt4cV1rGL5hj6KCesnDYUhd7oWgT11eZG7XnxHrnYeSvkzY7d2bhkJ7";
         assert_eq!(xprv_string.as_str(), expected_string);
     }
@@ -543,4 +763,148 @@ mod tests {
             .verify_ecdsa(&msg, &signature, &verification_key)
             .is_ok());
     }
+
+    #[test]
+    fn test_erase_key() {
+        let signer = LightsparkSigner::from_bytes(vec![1; 32], Network::Bitcoin).unwrap();
+        let key = signer.derive_key("m/3/2104864975".to_owned()).unwrap();
+        let mut erased = key.0;
+        erase_key(&mut erased);
+        assert_ne!(erased.private_key, key.0.private_key);
+        assert_eq!(erased.chain_code, ChainCode::from([0; 32]));
+    }
+
+    #[test]
+    fn test_encrypted_seed_file() {
+        let path =
+            std::env::temp_dir().join(format!("lightspark-seed-{}.json", std::process::id()));
+        let seed = Seed::new(hex::decode("000102030405060708090a0b0c0d0e0f").unwrap());
+        seed.save_encrypted(&path, "passphrase").unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("000102030405060708090a0b0c0d0e0f"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = Seed::load_encrypted(&path, "passphrase").unwrap();
+        assert_eq!(loaded.as_bytes(), seed.as_bytes());
+        assert!(matches!(
+            Seed::load_encrypted(&path, "wrong passphrase"),
+            Err(Error::SeedDecryptionError)
+        ));
+
+        for cipher in [
+            r#""{\"v\":4,\"i\":500000}""#,
+            r#""{\"v\":3,\"i\":500000}""#,
+            r#""{\"v\":4,\"i\":1}""#,
+            r#""{\"v\":4,\"i\":4294967295}""#,
+            r#""{\"v\":4}""#,
+            r#""AES_256_CBC_PBKDF2_5000_SHA256""#,
+        ] {
+            fs::write(
+                &path,
+                format!(r#"{{"cipher": {}, "encrypted_seed": "AAAA"}}"#, cipher),
+            )
+            .unwrap();
+            assert!(matches!(
+                Seed::load_encrypted(&path, "passphrase"),
+                Err(Error::SeedFileFormatError)
+            ));
+        }
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            Seed::load_encrypted(&path, "passphrase"),
+            Err(Error::SeedFileError(io::ErrorKind::NotFound))
+        ));
+
+        // A failed rename does not leave the temporary file behind.
+        fs::create_dir(&path).unwrap();
+        assert!(seed.save_encrypted(&path, "passphrase").is_err());
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        assert!(!Path::new(&tmp_path).exists());
+        fs::remove_dir(&path).unwrap();
+    }
+
+    #[test]
+    fn test_mnemonic_word_counts() {
+        for word_count in [12, 15, 18, 21, 24] {
+            let mnemonic = Mnemonic::random_in(Language::English, word_count).unwrap();
+            assert_eq!(mnemonic.word_count(), word_count);
+            let parsed = Mnemonic::from_phrase(mnemonic.as_string()).unwrap();
+            assert_eq!(parsed.as_string(), mnemonic.as_string());
+        }
+        assert_eq!(Mnemonic::random().unwrap().word_count(), 24);
+        assert!(matches!(
+            Mnemonic::random_in(Language::English, 13),
+            Err(Error::InvalidMnemonicWordCount(13))
+        ));
+    }
+
+    #[test]
+    fn test_mnemonic_languages() {
+        let mnemonic = Mnemonic::random_in(Language::Japanese, 12).unwrap();
+        assert_eq!(mnemonic.language(), Language::Japanese);
+        let parsed = Mnemonic::from_phrase_in(Language::Japanese, mnemonic.as_string()).unwrap();
+        assert_eq!(parsed.as_string(), mnemonic.as_string());
+        assert!(matches!(
+            Mnemonic::from_phrase(mnemonic.as_string()),
+            Err(Error::UnknownMnemonicWord(0))
+        ));
+    }
+
+    #[test]
+    fn test_mnemonic_from_entropy() {
+        for (len, word_count) in [(16, 12), (20, 15), (24, 18), (28, 21), (32, 24)] {
+            let mnemonic = Mnemonic::from_entropy(vec![0; len]).unwrap();
+            assert_eq!(mnemonic.word_count(), word_count);
+        }
+        assert_eq!(
+            Mnemonic::from_entropy(vec![0; 16]).unwrap().as_string(),
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        for len in [0, 15, 17, 33] {
+            assert!(matches!(
+                Mnemonic::from_entropy(vec![0; len]),
+                Err(Error::EntropyLengthError)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_mnemonic_errors() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon";
+        assert!(matches!(
+            Mnemonic::from_phrase(format!("{} abandon", phrase)),
+            Err(Error::InvalidMnemonicChecksum)
+        ));
+        assert!(matches!(
+            Mnemonic::from_phrase(format!("{} lightspark", phrase)),
+            Err(Error::UnknownMnemonicWord(11))
+        ));
+        assert!(matches!(
+            Mnemonic::from_phrase(phrase.to_owned()),
+            Err(Error::InvalidMnemonicWordCount(11))
+        ));
+    }
+
+    #[test]
+    fn test_seed_from_mnemonic_with_passphrase() {
+        // Test vector from https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+        let mnemonic = Mnemonic::from_phrase(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+                .to_owned(),
+        )
+        .unwrap();
+        let seed = Seed::from_mnemonic_with_passphrase(&mnemonic, "TREZOR");
+        assert_eq!(
+            hex::encode(seed.as_bytes()),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        assert_ne!(Seed::from_mnemonic(&mnemonic).as_bytes(), seed.as_bytes());
+    }
 }
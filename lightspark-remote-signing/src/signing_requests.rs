@@ -18,6 +18,9 @@ pub enum SigningRequest {
     InvoicePaymentHashRequest(InvoicePaymentHashRequest),
     ReleasePaymentPreimageRequest(ReleasePaymentPreimageRequest),
     ReleaseCounterpartyPerCommitmentSecretRequest(ReleaseCounterpartyPerCommitmentSecretRequest),
+    EcdhRequest(EcdhRequest),
+    SignInvoiceRequest(SignInvoiceRequest),
+    VlsMessageRequest(VlsMessageRequest),
 }
 
 impl SigningRequest {
@@ -63,7 +66,134 @@ impl SigningRequest {
                     )?,
                 ))
             }
-            _ => Err(Error::WebhookEventTypeNotSupported),
+            RemoteSigningSubEventType::Ecdh => Ok(Self::EcdhRequest(
+                EcdhRequest::parse_from_webhook_event(webhook_event)?,
+            )),
+            RemoteSigningSubEventType::SignInvoice => Ok(Self::SignInvoiceRequest(
+                SignInvoiceRequest::parse_from_webhook_event(webhook_event)?,
+            )),
+            RemoteSigningSubEventType::VlsMessage => Ok(Self::VlsMessageRequest(
+                VlsMessageRequest::parse_from_webhook_event(webhook_event)?,
+            )),
+        }
+    }
+
+    /// The network of the request, if it has one.
+    pub fn bitcoin_network(&self) -> Option<&BitcoinNetwork> {
+        match self {
+            Self::GetPerCommitmentPointRequest(r) => Some(&r.bitcoin_network),
+            Self::ReleasePerCommitmentSecretRequest(r) => Some(&r.bitcoin_network),
+            Self::DeriveKeyAndSignRequest(r) => Some(&r.bitcoin_network),
+            Self::InvoicePaymentHashRequest(r) => Some(&r.bitcoin_network),
+            Self::ReleasePaymentPreimageRequest(r) => Some(&r.bitcoin_network),
+            Self::ReleaseCounterpartyPerCommitmentSecretRequest(_) => None,
+            Self::EcdhRequest(r) => Some(&r.bitcoin_network),
+            Self::SignInvoiceRequest(r) => Some(&r.bitcoin_network),
+            Self::VlsMessageRequest(r) => Some(&r.bitcoin_network),
+        }
+    }
+
+    /// The channel the request is about, if any.
+    pub fn channel_id(&self) -> Option<&str> {
+        match self {
+            Self::GetPerCommitmentPointRequest(r) => Some(&r.channel_id),
+            Self::ReleasePerCommitmentSecretRequest(r) => Some(&r.channel_id),
+            Self::ReleaseCounterpartyPerCommitmentSecretRequest(r) => Some(&r.channel_id),
+            _ => None,
+        }
+    }
+
+    /// The derivation paths of the keys the request uses.
+    pub fn derivation_paths(&self) -> Vec<&str> {
+        match self {
+            Self::GetPerCommitmentPointRequest(r) => vec![&r.derivation_path],
+            Self::ReleasePerCommitmentSecretRequest(r) => vec![&r.derivation_path],
+            Self::DeriveKeyAndSignRequest(r) => r
+                .signing_jobs
+                .iter()
+                .map(|job| job.derivation_path.as_str())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// The hashes of the messages the request asks to sign.
+    pub fn message_hashes(&self) -> Vec<&str> {
+        match self {
+            Self::DeriveKeyAndSignRequest(r) => r
+                .signing_jobs
+                .iter()
+                .map(|job| job.message.as_str())
+                .collect(),
+            Self::SignInvoiceRequest(r) => vec![&r.invoice_hash],
+            _ => vec![],
+        }
+    }
+
+    /// The sub event type of the webhook event the request was parsed from.
+    pub fn sub_event_type(&self) -> RemoteSigningSubEventType {
+        match self {
+            Self::GetPerCommitmentPointRequest(_) => {
+                RemoteSigningSubEventType::GetPerCommitmentPoint
+            }
+            Self::ReleasePerCommitmentSecretRequest(_) => {
+                RemoteSigningSubEventType::ReleasePerCommitmentSecret
+            }
+            Self::DeriveKeyAndSignRequest(_) => RemoteSigningSubEventType::DeriveKeyAndSign,
+            Self::InvoicePaymentHashRequest(_) => {
+                RemoteSigningSubEventType::RequestInvoicePaymentHash
+            }
+            Self::ReleasePaymentPreimageRequest(_) => {
+                RemoteSigningSubEventType::ReleasePaymentPreimage
+            }
+            Self::ReleaseCounterpartyPerCommitmentSecretRequest(_) => {
+                RemoteSigningSubEventType::RevealCounterpartyPerCommitmentSecret
+            }
+            Self::EcdhRequest(_) => RemoteSigningSubEventType::Ecdh,
+            Self::SignInvoiceRequest(_) => RemoteSigningSubEventType::SignInvoice,
+            Self::VlsMessageRequest(_) => RemoteSigningSubEventType::VlsMessage,
+        }
+    }
+}
+
+/// The entity a remote signing webhook event is for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WebhookEventEntity<'a> {
+    /// The event's `entity_id` is the id of the node.
+    Node(&'a str),
+
+    /// The event's `entity_id` is the id of a channel, which is the case of the per-commitment
+    /// sub events. `node_id` is the id of the channel's node, if the event data carries it.
+    Channel {
+        channel_id: &'a str,
+        node_id: Option<&'a str>,
+    },
+}
+
+impl<'a> WebhookEventEntity<'a> {
+    /// Returns the entity of the event. Events whose sub event type cannot be read are assumed to
+    /// be for a node, so that they reach the node's handler and fail there.
+    pub fn from_webhook_event(webhook_event: &'a WebhookEvent) -> Self {
+        let data = webhook_event.data.as_ref();
+        let sub_type = data.and_then(|data| from_value(data["sub_event_type"].clone()).ok());
+        match sub_type {
+            Some(
+                RemoteSigningSubEventType::GetPerCommitmentPoint
+                | RemoteSigningSubEventType::ReleasePerCommitmentSecret
+                | RemoteSigningSubEventType::RevealCounterpartyPerCommitmentSecret,
+            ) => Self::Channel {
+                channel_id: &webhook_event.entity_id,
+                node_id: data.and_then(|data| data["node_id"].as_str()),
+            },
+            _ => Self::Node(&webhook_event.entity_id),
+        }
+    }
+
+    /// The id of the node, if known without looking the channel up.
+    pub fn node_id(&self) -> Option<&'a str> {
+        match self {
+            Self::Node(node_id) => Some(node_id),
+            Self::Channel { node_id, .. } => *node_id,
         }
     }
 }
@@ -249,8 +379,7 @@ impl ReleasePaymentPreimageRequest {
             .to_string();
         let nonce = data
             .get("preimage_nonce")
-            .ok_or(Error::WebhookEventDataMissing)?
-            .as_str()
+            .and_then(|nonce| nonce.as_str())
             .map(|s| s.to_string());
         let bitcoin_network = bitcoin_network_from_webhook_event(webhook_event)?;
         Ok(Self {
@@ -299,6 +428,118 @@ impl ReleaseCounterpartyPerCommitmentSecretRequest {
     }
 }
 
+/// A signing request asking for the shared secret between the node and a peer, computed with
+/// elliptic curve Diffie-Hellman from the node key and the peer public key.
+#[derive(Clone, Deserialize)]
+pub struct EcdhRequest {
+    pub node_id: String,
+    pub peer_public_key: String,
+    pub bitcoin_network: BitcoinNetwork,
+}
+
+impl EcdhRequest {
+    pub fn parse_from_webhook_event(webhook_event: &WebhookEvent) -> Result<Self, Error> {
+        let data = webhook_event
+            .data
+            .as_ref()
+            .ok_or(Error::WebhookEventDataMissing)?;
+        let data = data
+            .as_object()
+            .ok_or(Error::WebhookEventDataMissing)?
+            .clone();
+        let node_id = webhook_event.entity_id.clone();
+        let peer_public_key = data
+            .get("peer_public_key")
+            .ok_or(Error::WebhookEventDataMissing)?
+            .as_str()
+            .ok_or(Error::WebhookEventDataMissing)?
+            .to_string();
+        let bitcoin_network = bitcoin_network_from_webhook_event(webhook_event)?;
+        Ok(Self {
+            node_id,
+            peer_public_key,
+            bitcoin_network,
+        })
+    }
+}
+
+/// A signing request asking for an invoice to be signed with the node key.
+/// The invoice hash is the sha256 hash of the unsigned invoice described in bolt 11.
+///
+/// [Bolt 11]: https://github.com/lightning/bolts/blob/master/11-payment-encoding.md#data-part
+#[derive(Clone, Deserialize)]
+pub struct SignInvoiceRequest {
+    pub invoice_id: String,
+    pub invoice_hash: String,
+    pub bitcoin_network: BitcoinNetwork,
+}
+
+impl SignInvoiceRequest {
+    pub fn parse_from_webhook_event(webhook_event: &WebhookEvent) -> Result<Self, Error> {
+        let data = webhook_event
+            .data
+            .as_ref()
+            .ok_or(Error::WebhookEventDataMissing)?;
+        let data = data
+            .as_object()
+            .ok_or(Error::WebhookEventDataMissing)?
+            .clone();
+        let invoice_id = data
+            .get("invoice_id")
+            .ok_or(Error::WebhookEventDataMissing)?
+            .as_str()
+            .ok_or(Error::WebhookEventDataMissing)?
+            .to_string();
+        let invoice_hash = data
+            .get("invoice_hash")
+            .ok_or(Error::WebhookEventDataMissing)?
+            .as_str()
+            .ok_or(Error::WebhookEventDataMissing)?
+            .to_string();
+        let bitcoin_network = bitcoin_network_from_webhook_event(webhook_event)?;
+        Ok(Self {
+            invoice_id,
+            invoice_hash,
+            bitcoin_network,
+        })
+    }
+}
+
+/// A signing request carrying an opaque, hex encoded message for a Validating Lightning Signer.
+/// The message is not interpreted by this crate and is forwarded as is to a `vls::VlsBackend`.
+#[derive(Clone, Deserialize)]
+pub struct VlsMessageRequest {
+    pub node_id: String,
+    pub message: String,
+    pub bitcoin_network: BitcoinNetwork,
+}
+
+impl VlsMessageRequest {
+    pub fn parse_from_webhook_event(webhook_event: &WebhookEvent) -> Result<Self, Error> {
+        let data = webhook_event
+            .data
+            .as_ref()
+            .ok_or(Error::WebhookEventDataMissing)?;
+        let data = data
+            .as_object()
+            .ok_or(Error::WebhookEventDataMissing)?
+            .clone();
+        let node_id = webhook_event.entity_id.clone();
+        let message = data
+            .get("message")
+            .ok_or(Error::WebhookEventDataMissing)?
+            .as_str()
+            .ok_or(Error::WebhookEventDataMissing)?
+            .to_string();
+        let bitcoin_network = bitcoin_network_from_webhook_event(webhook_event)?;
+        Ok(Self {
+            node_id,
+            message,
+            bitcoin_network,
+        })
+    }
+}
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct SigningJob {
     pub id: String,
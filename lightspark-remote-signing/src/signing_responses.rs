@@ -9,6 +9,9 @@ pub enum SigningResponse {
     DeriveKeyAndSignResponse(DeriveKeyAndSignResponse),
     InvoicePaymentHashResponse(InvoicePaymentHashResponse),
     ReleasePaymentPreimageResponse(ReleasePaymentPreimageResponse),
+    EcdhResponse(EcdhResponse),
+    SignInvoiceResponse(SignInvoiceResponse),
+    VlsMessageResponse(Response),
 }
 
 impl SigningResponse {
@@ -39,6 +42,11 @@ impl SigningResponse {
             Self::ReleasePaymentPreimageResponse(r) => {
                 Response::release_payment_preimage_response(&r.invoice_id, &r.payment_preimage)
             }
+            Self::EcdhResponse(r) => Response::ecdh_response(&r.node_id, &r.shared_secret),
+            Self::SignInvoiceResponse(r) => {
+                Response::sign_invoice_response(&r.invoice_id, &r.signature, r.recovery_id)
+            }
+            Self::VlsMessageResponse(r) => r.clone(),
         }
     }
 }
@@ -74,3 +82,16 @@ pub struct ReleasePaymentPreimageResponse {
     pub invoice_id: String,
     pub payment_preimage: String,
 }
+
+#[derive(Clone)]
+pub struct EcdhResponse {
+    pub node_id: String,
+    pub shared_secret: String,
+}
+
+#[derive(Clone)]
+pub struct SignInvoiceResponse {
+    pub invoice_id: String,
+    pub signature: String,
+    pub recovery_id: i32,
+}
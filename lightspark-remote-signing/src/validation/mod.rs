@@ -0,0 +1,94 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+mod policy;
+
+use async_trait::async_trait;
+use lightspark::webhooks::WebhookEvent;
+
+use crate::signing_requests::SigningRequest;
+
+pub use policy::{Policy, PolicyValidator, RateLimit};
+
+pub trait Validation: Send + Sync {
+    /// This function should return true if the webhook should be signed.
+    ///
+    /// Arguments:
+    /// * `webhook` - The webhook event json serialized string to be validated.
+    fn should_sign(&self, webhook: String) -> bool;
+}
+
+/// The decision of a `SigningRequestValidation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationResult {
+    Allow,
+
+    /// The request should not be signed, for the given reason.
+    Deny(String),
+}
+
+impl ValidationResult {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Self::Allow)
+    }
+}
+
+/// A validator deciding whether to sign a remote signing request from its parsed content, e.g.
+/// its derivation paths, messages, tweaks, network or channel id.
+pub trait SigningRequestValidation: Send + Sync {
+    /// This function should return `ValidationResult::Allow` if the request should be signed.
+    ///
+    /// Arguments:
+    /// * `request` - The signing request parsed from the webhook event.
+    /// * `event` - The webhook event the request was parsed from.
+    fn validate(&self, request: &SigningRequest, event: &WebhookEvent) -> ValidationResult;
+}
+
+/// An asynchronous `SigningRequestValidation`, for validators that need to consult a database,
+/// an approval service or the Lightspark API before deciding. Every `SigningRequestValidation`
+/// is also an `AsyncSigningRequestValidation`.
+#[async_trait]
+pub trait AsyncSigningRequestValidation: Send + Sync {
+    /// This function should return `ValidationResult::Allow` if the request should be signed.
+    ///
+    /// Arguments:
+    /// * `request` - The signing request parsed from the webhook event.
+    /// * `event` - The webhook event the request was parsed from.
+    async fn validate(&self, request: &SigningRequest, event: &WebhookEvent) -> ValidationResult;
+}
+
+#[async_trait]
+impl<T: SigningRequestValidation + ?Sized> AsyncSigningRequestValidation for T {
+    async fn validate(&self, request: &SigningRequest, event: &WebhookEvent) -> ValidationResult {
+        SigningRequestValidation::validate(self, request, event)
+    }
+}
+
+/// Adapts a string based `Validation` to a `SigningRequestValidation`, passing it the json
+/// serialized webhook event.
+pub struct WebhookJsonValidation(pub Box<dyn Validation>);
+
+impl SigningRequestValidation for WebhookJsonValidation {
+    fn validate(&self, _: &SigningRequest, event: &WebhookEvent) -> ValidationResult {
+        let event_json =
+            serde_json::to_string(event).expect("Serialize event to json should not fail");
+        if self.0.should_sign(event_json) {
+            ValidationResult::Allow
+        } else {
+            ValidationResult::Deny("declined by validator".to_owned())
+        }
+    }
+}
+
+pub struct PositiveValidator;
+
+impl Validation for PositiveValidator {
+    fn should_sign(&self, _: String) -> bool {
+        true
+    }
+}
+
+impl SigningRequestValidation for PositiveValidator {
+    fn validate(&self, _: &SigningRequest, _: &WebhookEvent) -> ValidationResult {
+        ValidationResult::Allow
+    }
+}
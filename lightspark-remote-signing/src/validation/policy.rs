@@ -0,0 +1,434 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::mem::discriminant;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lightspark::{objects::bitcoin_network::BitcoinNetwork, webhooks::WebhookEvent};
+use serde::Deserialize;
+
+use crate::{signing_requests::SigningRequest, Error};
+
+use super::{SigningRequestValidation, ValidationResult};
+
+/// The rules enforced by a `PolicyValidator`. Every rule is disabled when left out, so an empty
+/// policy allows every request.
+///
+/// A policy is usually loaded from a json or toml file, e.g.
+///
+/// ```json
+/// {
+///     "allowed_derivation_path_prefixes": ["m/3", "m/4h"],
+///     "allowed_networks": ["MAINNET"],
+///     "max_signing_jobs_per_event": 10,
+///     "enforce_commitment_index_order": true,
+///     "rate_limit": {"max_events": 100, "period_seconds": 60}
+/// }
+/// ```
+///
+/// or
+///
+/// ```toml
+/// allowed_derivation_path_prefixes = ["m/3", "m/4h"]
+/// allowed_networks = ["MAINNET"]
+/// max_signing_jobs_per_event = 10
+/// enforce_commitment_index_order = true
+/// rate_limit = { max_events = 100, period_seconds = 60 }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// The derivation paths keys may be derived from. A path is allowed if it is one of the
+    /// prefixes or a descendant of one of them, so `m/3` allows `m/3/1` but not `m/30`. Empty
+    /// allows any path.
+    pub allowed_derivation_path_prefixes: Vec<String>,
+
+    /// The networks requests may be for. Empty allows any network.
+    pub allowed_networks: Vec<BitcoinNetwork>,
+
+    /// The maximum number of signing jobs in a single derive key and sign request.
+    pub max_signing_jobs_per_event: Option<usize>,
+
+    /// Whether to reject per-commitment point and secret requests going back to a commitment
+    /// index higher than a previous allowed request for the same channel, whether or not the
+    /// handler then managed to answer it. Commitment indices count down from 2^48 - 1 as
+    /// described in bolt 3.
+    pub enforce_commitment_index_order: bool,
+
+    /// The maximum rate of allowed events. Every allowed event counts, including those the
+    /// handler then fails to answer.
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Allows at most `max_events` events in any window of `period_seconds` seconds.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RateLimit {
+    pub max_events: usize,
+    pub period_seconds: u64,
+}
+
+impl Policy {
+    /// Parses a policy from a json string.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|e| Error::PolicyError(e.to_string()))
+    }
+
+    /// Loads a policy from a json file.
+    pub fn from_json_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let json = fs::read_to_string(path.as_ref())
+            .map_err(|e| Error::PolicyError(format!("{}: {}", path.as_ref().display(), e)))?;
+        Self::from_json(&json)
+    }
+
+    /// Parses a policy from a toml string.
+    pub fn from_toml(toml: &str) -> Result<Self, Error> {
+        toml::from_str(toml).map_err(|e| Error::PolicyError(e.to_string()))
+    }
+
+    /// Loads a policy from a toml file.
+    pub fn from_toml_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let toml = fs::read_to_string(path.as_ref())
+            .map_err(|e| Error::PolicyError(format!("{}: {}", path.as_ref().display(), e)))?;
+        Self::from_toml(&toml)
+    }
+
+    /// Loads a policy from a file, as toml if its extension is `.toml` and as json otherwise.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml_file(path),
+            _ => Self::from_json_file(path),
+        }
+    }
+}
+
+/// A `SigningRequestValidation` enforcing a `Policy`. The validator keeps the state needed by the
+/// commitment index and rate limit rules in memory, so a single instance should handle all the
+/// events of a node.
+///
+/// The state is updated when a request is allowed, before it is handled, so it counts attempts
+/// rather than signatures: a request failing in the handler afterwards, e.g. on a channel state
+/// or signer error, still takes a slot of the rate limit and still moves the lowest commitment
+/// index of its channel. Both rules are meant as limits on what the signer can be asked for, for
+/// which counting attempts is the conservative choice, and retrying a request at the same
+/// commitment index stays allowed.
+pub struct PolicyValidator {
+    policy: Policy,
+    state: Mutex<PolicyState>,
+}
+
+#[derive(Default)]
+struct PolicyState {
+    /// The lowest per-commitment point index allowed, by channel id.
+    commitment_point_indices: HashMap<String, u64>,
+
+    /// The lowest per-commitment secret index allowed, by channel id.
+    commitment_secret_indices: HashMap<String, u64>,
+
+    /// The times of the allowed events within the rate limit period, oldest first.
+    recent_events: VecDeque<Instant>,
+}
+
+impl PolicyValidator {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(PolicyState::default()),
+        }
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    fn check_request(&self, request: &SigningRequest) -> Result<(), String> {
+        if let Some(network) = request.bitcoin_network() {
+            if !self.policy.allowed_networks.is_empty()
+                && !self
+                    .policy
+                    .allowed_networks
+                    .iter()
+                    .any(|allowed| discriminant(allowed) == discriminant(network))
+            {
+                return Err(format!("network {} is not allowed", network));
+            }
+        }
+
+        if !self.policy.allowed_derivation_path_prefixes.is_empty() {
+            if let Some(path) = request.derivation_paths().into_iter().find(|path| {
+                !self
+                    .policy
+                    .allowed_derivation_path_prefixes
+                    .iter()
+                    .any(|prefix| is_path_prefix(prefix, path))
+            }) {
+                return Err(format!("derivation path {} is not allowed", path));
+            }
+        }
+
+        if let (Some(max), SigningRequest::DeriveKeyAndSignRequest(r)) =
+            (self.policy.max_signing_jobs_per_event, request)
+        {
+            if r.signing_jobs.len() > max {
+                return Err(format!(
+                    "{} signing jobs exceed the maximum of {}",
+                    r.signing_jobs.len(),
+                    max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SigningRequestValidation for PolicyValidator {
+    fn validate(&self, request: &SigningRequest, _: &WebhookEvent) -> ValidationResult {
+        if let Err(reason) = self.check_request(request) {
+            return ValidationResult::Deny(reason);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if let Some(rate_limit) = &self.policy.rate_limit {
+            let period = Duration::from_secs(rate_limit.period_seconds);
+            while state
+                .recent_events
+                .front()
+                .is_some_and(|time| now.duration_since(*time) >= period)
+            {
+                state.recent_events.pop_front();
+            }
+            if state.recent_events.len() >= rate_limit.max_events {
+                return ValidationResult::Deny(format!(
+                    "more than {} events in {} seconds",
+                    rate_limit.max_events, rate_limit.period_seconds
+                ));
+            }
+        }
+
+        let commitment = match request {
+            SigningRequest::GetPerCommitmentPointRequest(r) => Some((
+                &mut state.commitment_point_indices,
+                &r.channel_id,
+                r.per_commitmnet_point_idx,
+            )),
+            SigningRequest::ReleasePerCommitmentSecretRequest(r) => Some((
+                &mut state.commitment_secret_indices,
+                &r.channel_id,
+                r.per_commitment_point_idx,
+            )),
+            _ => None,
+        };
+        if let Some((indices, channel_id, index)) =
+            commitment.filter(|_| self.policy.enforce_commitment_index_order)
+        {
+            match indices.get(channel_id) {
+                Some(lowest) if index > *lowest => {
+                    return ValidationResult::Deny(format!(
+                        "commitment index {} is above {} for channel {}",
+                        index, lowest, channel_id
+                    ));
+                }
+                _ => {
+                    indices.insert(channel_id.clone(), index);
+                }
+            }
+        }
+
+        if self.policy.rate_limit.is_some() {
+            state.recent_events.push_back(now);
+        }
+        ValidationResult::Allow
+    }
+}
+
+/// Whether `path` is `prefix` or one of its descendants.
+fn is_path_prefix(prefix: &str, path: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing_requests::{
+        DeriveKeyAndSignRequest, GetPerCommitmentPointRequest, ReleasePerCommitmentSecretRequest,
+        SigningJob,
+    };
+    use serde_json::json;
+
+    fn event() -> WebhookEvent {
+        serde_json::from_value(json!({
+            "event_type": "REMOTE_SIGNING",
+            "event_id": "event_id",
+            "timestamp": "2023-09-18T23:50:15.355980+00:00",
+            "entity_id": "node_id",
+            "wallet_id": null,
+            "data": {},
+        }))
+        .unwrap()
+    }
+
+    fn derive_key_and_sign(paths: &[&str], network: BitcoinNetwork) -> SigningRequest {
+        SigningRequest::DeriveKeyAndSignRequest(DeriveKeyAndSignRequest {
+            signing_jobs: paths
+                .iter()
+                .map(|path| SigningJob {
+                    id: "job_id".to_owned(),
+                    derivation_path: path.to_string(),
+                    message: "00".to_owned(),
+                    add_tweak: None,
+                    mul_tweak: None,
+                })
+                .collect(),
+            bitcoin_network: network,
+        })
+    }
+
+    fn commitment_point(channel_id: &str, index: u64) -> SigningRequest {
+        SigningRequest::GetPerCommitmentPointRequest(GetPerCommitmentPointRequest {
+            channel_id: channel_id.to_owned(),
+            derivation_path: "m/3/1".to_owned(),
+            per_commitmnet_point_idx: index,
+            bitcoin_network: BitcoinNetwork::Regtest,
+        })
+    }
+
+    fn commitment_secret(channel_id: &str, index: u64) -> SigningRequest {
+        SigningRequest::ReleasePerCommitmentSecretRequest(ReleasePerCommitmentSecretRequest {
+            channel_id: channel_id.to_owned(),
+            derivation_path: "m/3/1".to_owned(),
+            per_commitment_point_idx: index,
+            bitcoin_network: BitcoinNetwork::Regtest,
+        })
+    }
+
+    #[test]
+    fn test_from_json() {
+        let policy = Policy::from_json(
+            r#"{
+                "allowed_derivation_path_prefixes": ["m/3"],
+                "allowed_networks": ["MAINNET", "REGTEST"],
+                "max_signing_jobs_per_event": 2,
+                "rate_limit": {"max_events": 5, "period_seconds": 60}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(policy.allowed_derivation_path_prefixes, vec!["m/3"]);
+        assert_eq!(policy.allowed_networks.len(), 2);
+        assert_eq!(policy.max_signing_jobs_per_event, Some(2));
+        assert!(!policy.enforce_commitment_index_order);
+        assert_eq!(policy.rate_limit.unwrap().max_events, 5);
+
+        assert!(Policy::from_json("{}").is_ok());
+        assert!(matches!(
+            Policy::from_json(r#"{"allowed_networks": ["MOONNET"]}"#),
+            Err(Error::PolicyError(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let policy = Policy::from_toml(
+            r#"
+            allowed_derivation_path_prefixes = ["m/3"]
+            allowed_networks = ["MAINNET", "REGTEST"]
+            enforce_commitment_index_order = true
+            rate_limit = { max_events = 5, period_seconds = 60 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(policy.allowed_derivation_path_prefixes, vec!["m/3"]);
+        assert_eq!(policy.allowed_networks.len(), 2);
+        assert_eq!(policy.max_signing_jobs_per_event, None);
+        assert!(policy.enforce_commitment_index_order);
+        assert_eq!(policy.rate_limit.unwrap().period_seconds, 60);
+
+        let path =
+            std::env::temp_dir().join(format!("lightspark-policy-{}.toml", std::process::id()));
+        fs::write(&path, "max_signing_jobs_per_event = 3").unwrap();
+        assert_eq!(
+            Policy::from_file(&path).unwrap().max_signing_jobs_per_event,
+            Some(3)
+        );
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            Policy::from_toml("allowed_networks = \"MAINNET\""),
+            Err(Error::PolicyError(_))
+        ));
+    }
+
+    #[test]
+    fn test_static_rules() {
+        let validator = PolicyValidator::new(Policy {
+            allowed_derivation_path_prefixes: vec!["m/3/".to_owned(), "m/4h".to_owned()],
+            allowed_networks: vec![BitcoinNetwork::Regtest],
+            max_signing_jobs_per_event: Some(2),
+            ..Policy::default()
+        });
+        let validate = |request| validator.validate(&request, &event());
+
+        assert!(validate(derive_key_and_sign(
+            &["m/3/1", "m/4h"],
+            BitcoinNetwork::Regtest
+        ))
+        .is_allowed());
+        assert!(!validate(derive_key_and_sign(&["m/30/1"], BitcoinNetwork::Regtest)).is_allowed());
+        assert!(!validate(derive_key_and_sign(&["m/3/1"], BitcoinNetwork::Mainnet)).is_allowed());
+        assert_eq!(
+            validate(derive_key_and_sign(&["m/3/1"; 3], BitcoinNetwork::Regtest)),
+            ValidationResult::Deny("3 signing jobs exceed the maximum of 2".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_commitment_index_order() {
+        let validator = PolicyValidator::new(Policy {
+            enforce_commitment_index_order: true,
+            ..Policy::default()
+        });
+        let validate = |request| validator.validate(&request, &event());
+
+        assert!(validate(commitment_point("a", 10)).is_allowed());
+        assert!(validate(commitment_point("a", 10)).is_allowed());
+        assert!(validate(commitment_point("a", 9)).is_allowed());
+        assert!(!validate(commitment_point("a", 10)).is_allowed());
+        assert!(validate(commitment_point("b", 10)).is_allowed());
+
+        assert!(validate(commitment_secret("a", 10)).is_allowed());
+        assert!(validate(commitment_secret("a", 8)).is_allowed());
+        assert!(!validate(commitment_secret("a", 9)).is_allowed());
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let validator = PolicyValidator::new(Policy {
+            allowed_networks: vec![BitcoinNetwork::Regtest],
+            rate_limit: Some(RateLimit {
+                max_events: 2,
+                period_seconds: 3600,
+            }),
+            ..Policy::default()
+        });
+        let validate = |request| validator.validate(&request, &event());
+
+        assert!(validate(derive_key_and_sign(&["m/3/1"], BitcoinNetwork::Regtest)).is_allowed());
+        // Denied events do not count toward the limit.
+        assert!(!validate(derive_key_and_sign(&["m/3/1"], BitcoinNetwork::Mainnet)).is_allowed());
+        assert!(validate(derive_key_and_sign(&["m/3/1"], BitcoinNetwork::Regtest)).is_allowed());
+        assert!(!validate(derive_key_and_sign(&["m/3/1"], BitcoinNetwork::Regtest)).is_allowed());
+    }
+
+    #[test]
+    fn test_is_path_prefix() {
+        assert!(is_path_prefix("m/3", "m/3"));
+        assert!(is_path_prefix("m/3", "m/3/2104864975"));
+        assert!(is_path_prefix("m/3/", "m/3/2104864975"));
+        assert!(!is_path_prefix("m/3", "m/30"));
+        assert!(!is_path_prefix("m/4h", "m/4"));
+    }
+}
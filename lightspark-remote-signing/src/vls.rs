@@ -0,0 +1,18 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use crate::{response::Response, signing_requests::VlsMessageRequest, Error};
+
+/// A Validating Lightning Signer backend, answering the `VLS_MESSAGE` remote signing events.
+///
+/// The messages are opaque to this crate: the backend is responsible for decoding the message,
+/// enforcing its policies with its own signer, and building the graphql response to send back to
+/// lightspark. Register a backend with `handler::Handler::with_vls_backend`.
+pub trait VlsBackend: Send + Sync {
+    /// This function should handle the VLS message and return the response to send to lightspark.
+    ///
+    /// Arguments:
+    /// * `request` - The VLS message request, holding the hex encoded message.
+    ///
+    /// Errors should be reported with `Error::VlsBackendError`.
+    fn handle_vls_message(&self, request: &VlsMessageRequest) -> Result<Response, Error>;
+}
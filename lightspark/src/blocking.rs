@@ -0,0 +1,707 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+//! A synchronous facade over `LightsparkClient`, for programs that do not run an async runtime.
+//!
+//! ```no_run
+//! use lightspark::blocking::BlockingLightsparkClient;
+//! use lightspark::key::RSASigningKey;
+//! use lightspark::request::auth_provider::AccountAuthProvider;
+//!
+//! let auth_provider = AccountAuthProvider::new("<api token id>".into(), "<api token secret>".into());
+//! let client = BlockingLightsparkClient::<RSASigningKey>::new(auth_provider).unwrap();
+//! let account = client.get_current_account().unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::client::{LightsparkClient, LightsparkClientBuilder};
+use crate::error::Error;
+use crate::key::OperationSigningKey;
+use crate::objects::account::Account;
+use crate::objects::api_token::ApiToken;
+use crate::objects::bitcoin_network;
+use crate::objects::compliance_provider::ComplianceProvider;
+use crate::objects::currency_amount::CurrencyAmount;
+use crate::objects::fee_estimate::FeeEstimate;
+use crate::objects::incoming_payment::IncomingPayment;
+use crate::objects::invoice::Invoice;
+use crate::objects::invoice_data::InvoiceData;
+use crate::objects::invoice_type::InvoiceType;
+use crate::objects::outgoing_payment::OutgoingPayment;
+use crate::objects::region_code::RegionCode;
+use crate::objects::risk_rating::RiskRating;
+use crate::objects::transaction_status::TransactionStatus;
+use crate::objects::uma_invitation::UmaInvitation;
+use crate::objects::withdrawal_mode::WithdrawalMode;
+use crate::objects::withdrawal_request::WithdrawalRequest;
+use crate::request::auth_provider::AuthProvider;
+use crate::request::requester::Requester;
+use crate::request::retry::RetryPolicy;
+use crate::types::get_entity::GetEntity;
+use crate::types::graphql_requester::SigningGraphQLRequester;
+
+/// A `LightsparkClient` whose methods block until the operation completes.
+///
+/// Each client drives its requests on its own single threaded tokio runtime, so it must not be
+/// used from within an async context: calling its methods from a tokio task panics.
+pub struct BlockingLightsparkClient<K: OperationSigningKey, R: SigningGraphQLRequester = Requester>
+{
+    client: LightsparkClient<K, R>,
+    runtime: Runtime,
+}
+
+impl<K: OperationSigningKey> BlockingLightsparkClient<K> {
+    pub fn new<T: AuthProvider>(auth_provider: T) -> Result<Self, Error> {
+        Self::from_client(LightsparkClient::new(auth_provider)?)
+    }
+
+    /// Creates a client from a configured builder, e.g. to set timeouts or a proxy.
+    pub fn from_builder(builder: LightsparkClientBuilder) -> Result<Self, Error> {
+        Self::from_client(builder.build()?)
+    }
+
+    /// Sets the policy used to retry failed requests.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.client.set_retry_policy(retry_policy);
+    }
+}
+
+impl<K: OperationSigningKey, R: SigningGraphQLRequester> BlockingLightsparkClient<K, R> {
+    /// Wraps an async client.
+    pub fn from_client(client: LightsparkClient<K, R>) -> Result<Self, Error> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| Error::ClientCreationError(err.to_string()))?;
+        Ok(Self { client, runtime })
+    }
+
+    /// The underlying async client. Its `requester` can be passed to the methods of the objects,
+    /// e.g. `Account::get_transactions`, whose futures can then be run with `block_on`.
+    pub fn client(&self) -> &LightsparkClient<K, R> {
+        &self.client
+    }
+
+    /// Runs the given future to completion on this client's runtime.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    pub fn provide_master_seed(
+        &mut self,
+        node_id: &str,
+        master_seed: Vec<u8>,
+        network: bitcoin_network::BitcoinNetwork,
+    ) -> Result<(), Error> {
+        self.client
+            .provide_master_seed(node_id, master_seed, network)
+    }
+
+    pub fn get_bitcoin_fee_estimates(
+        &self,
+        bitcoin_network: bitcoin_network::BitcoinNetwork,
+    ) -> Result<FeeEstimate, Error> {
+        self.runtime
+            .block_on(self.client.get_bitcoin_fee_estimates(bitcoin_network))
+    }
+
+    pub fn get_lightning_fee_estimate_for_node(
+        &self,
+        node_id: &str,
+        destination_node_public_key: &str,
+        amount_msats: i64,
+    ) -> Result<CurrencyAmount, Error> {
+        self.runtime
+            .block_on(self.client.get_lightning_fee_estimate_for_node(
+                node_id,
+                destination_node_public_key,
+                amount_msats,
+            ))
+    }
+
+    pub fn get_lightning_fee_estimate_for_invoice(
+        &self,
+        node_id: &str,
+        encoded_payment_request: &str,
+        amount_msats: i64,
+    ) -> Result<CurrencyAmount, Error> {
+        self.runtime
+            .block_on(self.client.get_lightning_fee_estimate_for_invoice(
+                node_id,
+                encoded_payment_request,
+                amount_msats,
+            ))
+    }
+
+    pub fn get_current_account(&self) -> Result<Account, Error> {
+        self.runtime.block_on(self.client.get_current_account())
+    }
+
+    pub fn create_api_token(
+        &self,
+        name: &str,
+        transact: bool,
+        test_mode: bool,
+    ) -> Result<(ApiToken, String), Error> {
+        self.runtime
+            .block_on(self.client.create_api_token(name, transact, test_mode))
+    }
+
+    pub fn delete_api_token(&self, api_token_id: &str) -> Result<(), Error> {
+        self.runtime
+            .block_on(self.client.delete_api_token(api_token_id))
+    }
+
+    pub fn get_entity<T>(&self, id: &str) -> Result<T, Error>
+    where
+        T: GetEntity,
+    {
+        self.runtime.block_on(self.client.get_entity::<T>(id))
+    }
+
+    pub fn create_invoice(
+        &self,
+        node_id: &str,
+        amount_msats: i64,
+        memo: Option<&str>,
+        invoice_type: Option<InvoiceType>,
+    ) -> Result<Invoice, Error> {
+        self.runtime.block_on(
+            self.client
+                .create_invoice(node_id, amount_msats, memo, invoice_type),
+        )
+    }
+
+    pub fn create_lnurl_invoice(
+        &self,
+        node_id: &str,
+        amount_msats: i64,
+        metadata: &str,
+    ) -> Result<Invoice, Error> {
+        self.runtime.block_on(
+            self.client
+                .create_lnurl_invoice(node_id, amount_msats, metadata),
+        )
+    }
+
+    /// Cancels an existing unpaid invoice and returns that invoice. Cancelled invoices cannot be paid.
+    pub fn cancel_invoice(&self, invoice_id: &str) -> Result<Invoice, Error> {
+        self.runtime
+            .block_on(self.client.cancel_invoice(invoice_id))
+    }
+
+    pub fn fund_node(&self, node_id: &str, amount_sats: i64) -> Result<CurrencyAmount, Error> {
+        self.runtime
+            .block_on(self.client.fund_node(node_id, amount_sats))
+    }
+
+    pub fn get_decoded_payment_request(
+        &self,
+        encoded_payment_request: &str,
+    ) -> Result<InvoiceData, Error> {
+        self.runtime.block_on(
+            self.client
+                .get_decoded_payment_request(encoded_payment_request),
+        )
+    }
+
+    pub fn recover_node_signing_key(
+        &mut self,
+        node_id: &str,
+        node_password: &str,
+    ) -> Result<Vec<u8>, Error> {
+        self.runtime
+            .block_on(self.client.recover_node_signing_key(node_id, node_password))
+    }
+
+    pub fn pay_invoice(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        timeout_secs: i32,
+        amount_msats: Option<i64>,
+        maximum_fees_msats: i64,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime.block_on(self.client.pay_invoice(
+            node_id,
+            encoded_invoice,
+            timeout_secs,
+            amount_msats,
+            maximum_fees_msats,
+        ))
+    }
+
+    /// Pays an invoice. Requests sharing an `idempotency_key` return the same payment, and if the
+    /// request fails without a definitive answer, the payment already created for the key (if
+    /// any) is returned instead of the error.
+    pub fn pay_invoice_with_idempotency_key(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        timeout_secs: i32,
+        amount_msats: Option<i64>,
+        maximum_fees_msats: i64,
+        idempotency_key: Option<&str>,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime
+            .block_on(self.client.pay_invoice_with_idempotency_key(
+                node_id,
+                encoded_invoice,
+                timeout_secs,
+                amount_msats,
+                maximum_fees_msats,
+                idempotency_key,
+            ))
+    }
+
+    pub fn send_payment(
+        &self,
+        node_id: &str,
+        destination_public_key: &str,
+        timeout_secs: i32,
+        amount_msats: i64,
+        maximum_fees_msats: i64,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime.block_on(self.client.send_payment(
+            node_id,
+            destination_public_key,
+            timeout_secs,
+            amount_msats,
+            maximum_fees_msats,
+        ))
+    }
+
+    /// Sends a keysend payment. Requests sharing an `idempotency_key` return the same payment, and
+    /// if the request fails without a definitive answer, the payment already created for the key
+    /// (if any) is returned instead of the error.
+    pub fn send_payment_with_idempotency_key(
+        &self,
+        node_id: &str,
+        destination_public_key: &str,
+        timeout_secs: i32,
+        amount_msats: i64,
+        maximum_fees_msats: i64,
+        idempotency_key: Option<&str>,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime
+            .block_on(self.client.send_payment_with_idempotency_key(
+                node_id,
+                destination_public_key,
+                timeout_secs,
+                amount_msats,
+                maximum_fees_msats,
+                idempotency_key,
+            ))
+    }
+
+    pub fn execute_graphql_request(
+        &self,
+        operation: &str,
+        variables: HashMap<&str, Value>,
+    ) -> Result<Value, Error> {
+        self.runtime
+            .block_on(self.client.execute_graphql_request(operation, variables))
+    }
+
+    pub fn execute_graphql_request_variable(
+        &self,
+        operation: &str,
+        variables: Value,
+    ) -> Result<Value, Error> {
+        self.runtime.block_on(
+            self.client
+                .execute_graphql_request_variable(operation, variables),
+        )
+    }
+
+    pub fn create_node_wallet_address(&self, node_id: &str) -> Result<String, Error> {
+        self.runtime
+            .block_on(self.client.create_node_wallet_address(node_id))
+    }
+
+    pub fn request_withdrawal(
+        &self,
+        node_id: &str,
+        bitcoin_address: &str,
+        amount_sats: i64,
+        withdrawal_mode: WithdrawalMode,
+    ) -> Result<WithdrawalRequest, Error> {
+        self.runtime.block_on(self.client.request_withdrawal(
+            node_id,
+            bitcoin_address,
+            amount_sats,
+            withdrawal_mode,
+        ))
+    }
+
+    /// Requests a withdrawal. Requests sharing an `idempotency_key` return the same withdrawal
+    /// request, and if the request fails without a definitive answer, the withdrawal request
+    /// already created for the key (if any) is returned instead of the error.
+    pub fn request_withdrawal_with_idempotency_key(
+        &self,
+        node_id: &str,
+        bitcoin_address: &str,
+        amount_sats: i64,
+        withdrawal_mode: WithdrawalMode,
+        idempotency_key: Option<&str>,
+    ) -> Result<WithdrawalRequest, Error> {
+        self.runtime
+            .block_on(self.client.request_withdrawal_with_idempotency_key(
+                node_id,
+                bitcoin_address,
+                amount_sats,
+                withdrawal_mode,
+                idempotency_key,
+            ))
+    }
+
+    pub fn create_test_mode_invoice(
+        &self,
+        node_id: &str,
+        amount_msats: i64,
+        memo: Option<&str>,
+        invoice_type: Option<InvoiceType>,
+    ) -> Result<String, Error> {
+        self.runtime.block_on(self.client.create_test_mode_invoice(
+            node_id,
+            amount_msats,
+            memo,
+            invoice_type,
+        ))
+    }
+
+    pub fn create_test_mode_payment(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        amount_msats: Option<i64>,
+    ) -> Result<IncomingPayment, Error> {
+        self.runtime.block_on(self.client.create_test_mode_payment(
+            node_id,
+            encoded_invoice,
+            amount_msats,
+        ))
+    }
+
+    pub fn create_uma_invoice(
+        &self,
+        node_id: &str,
+        amount_msats: i64,
+        metadata: &str,
+        expiry_secs: Option<i32>,
+    ) -> Result<Invoice, Error> {
+        self.runtime.block_on(self.client.create_uma_invoice(
+            node_id,
+            amount_msats,
+            metadata,
+            expiry_secs,
+        ))
+    }
+
+    pub fn create_uma_invoice_with_receiver_identifier(
+        &self,
+        node_id: &str,
+        amount_msats: i64,
+        metadata: &str,
+        expiry_secs: Option<i32>,
+        signing_private_key: Option<&[u8]>,
+        receiver_identifier: Option<&str>,
+    ) -> Result<Invoice, Error> {
+        self.runtime
+            .block_on(self.client.create_uma_invoice_with_receiver_identifier(
+                node_id,
+                amount_msats,
+                metadata,
+                expiry_secs,
+                signing_private_key,
+                receiver_identifier,
+            ))
+    }
+
+    pub fn pay_uma_invoice(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        timeout_secs: i32,
+        maximum_fees_msats: i64,
+        amount_msats: Option<i64>,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime.block_on(self.client.pay_uma_invoice(
+            node_id,
+            encoded_invoice,
+            timeout_secs,
+            maximum_fees_msats,
+            amount_msats,
+        ))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn pay_uma_invoice_with_sender_identifier(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        timeout_secs: i32,
+        maximum_fees_msats: i64,
+        amount_msats: Option<i64>,
+        signing_private_key: Option<&[u8]>,
+        sender_identifier: Option<&str>,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime
+            .block_on(self.client.pay_uma_invoice_with_sender_identifier(
+                node_id,
+                encoded_invoice,
+                timeout_secs,
+                maximum_fees_msats,
+                amount_msats,
+                signing_private_key,
+                sender_identifier,
+            ))
+    }
+
+    /// Pays an UMA invoice. Requests sharing an `idempotency_key` return the same payment, and if
+    /// the request fails without a definitive answer, the payment already created for the key
+    /// (if any) is returned instead of the error.
+    #[allow(clippy::too_many_arguments)]
+    pub fn pay_uma_invoice_with_idempotency_key(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        timeout_secs: i32,
+        maximum_fees_msats: i64,
+        amount_msats: Option<i64>,
+        signing_private_key: Option<&[u8]>,
+        sender_identifier: Option<&str>,
+        idempotency_key: Option<&str>,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime
+            .block_on(self.client.pay_uma_invoice_with_idempotency_key(
+                node_id,
+                encoded_invoice,
+                timeout_secs,
+                maximum_fees_msats,
+                amount_msats,
+                signing_private_key,
+                sender_identifier,
+                idempotency_key,
+            ))
+    }
+
+    pub fn screen_node(
+        &self,
+        provider: ComplianceProvider,
+        destination_node_public_key: &str,
+    ) -> Result<RiskRating, Error> {
+        self.runtime.block_on(
+            self.client
+                .screen_node(provider, destination_node_public_key),
+        )
+    }
+
+    /// Creates an UMA invitation. If you are part of the incentive program, you should use
+    /// `create_uma_invitation_with_incentives`.
+    pub fn create_uma_invitation(&self, inviter_uma: &str) -> Result<UmaInvitation, Error> {
+        self.runtime
+            .block_on(self.client.create_uma_invitation(inviter_uma))
+    }
+
+    /// Creates an UMA invitation as part of the incentive program. If you are not part of the
+    /// incentive program, you should use `create_uma_invitation`.
+    pub fn create_uma_invitation_with_incentives(
+        &self,
+        inviter_uma: &str,
+        inviter_phone_number_e164: &str,
+        inviter_region: RegionCode,
+    ) -> Result<UmaInvitation, Error> {
+        self.runtime
+            .block_on(self.client.create_uma_invitation_with_incentives(
+                inviter_uma,
+                inviter_phone_number_e164,
+                inviter_region,
+            ))
+    }
+
+    /// Claims an UMA invitation. If you are part of the incentive program, you should use
+    /// `claim_uma_invitation_with_incentives`.
+    pub fn claim_uma_invitation(
+        &self,
+        invitation_code: &str,
+        invitee_uma: &str,
+    ) -> Result<UmaInvitation, Error> {
+        self.runtime.block_on(
+            self.client
+                .claim_uma_invitation(invitation_code, invitee_uma),
+        )
+    }
+
+    /// Claims an UMA invitation as part of the incentive program. If you are not part of the
+    /// incentive program, you should use `claim_uma_invitation`.
+    pub fn claim_uma_invitation_with_incentives(
+        &self,
+        invitation_code: &str,
+        invitee_uma: &str,
+        invitee_phone_number_e164: &str,
+        invitee_region: RegionCode,
+    ) -> Result<UmaInvitation, Error> {
+        self.runtime
+            .block_on(self.client.claim_uma_invitation_with_incentives(
+                invitation_code,
+                invitee_uma,
+                invitee_phone_number_e164,
+                invitee_region,
+            ))
+    }
+
+    /// Fetches a UMA invitation by its code.
+    pub fn fetch_uma_invitation(&self, invitation_code: &str) -> Result<UmaInvitation, Error> {
+        self.runtime
+            .block_on(self.client.fetch_uma_invitation(invitation_code))
+    }
+
+    /// Fetches the outgoing payment created with the given idempotency key, if any.
+    pub fn outgoing_payment_for_idempotency_key(
+        &self,
+        idempotency_key: &str,
+    ) -> Result<Option<OutgoingPayment>, Error> {
+        self.runtime.block_on(
+            self.client
+                .outgoing_payment_for_idempotency_key(idempotency_key),
+        )
+    }
+
+    /// Fetches the outgoing payments made to the given encoded invoice, optionally filtered by
+    /// status.
+    pub fn outgoing_payments_for_invoice(
+        &self,
+        encoded_invoice: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<OutgoingPayment>, Error> {
+        self.runtime.block_on(
+            self.client
+                .outgoing_payments_for_invoice(encoded_invoice, statuses),
+        )
+    }
+
+    /// Fetches the outgoing payments for the given payment hash, optionally filtered by status.
+    pub fn outgoing_payments_for_payment_hash(
+        &self,
+        payment_hash: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<OutgoingPayment>, Error> {
+        self.runtime.block_on(
+            self.client
+                .outgoing_payments_for_payment_hash(payment_hash, statuses),
+        )
+    }
+
+    /// Fetches the incoming payments received for the given invoice, optionally filtered by
+    /// status.
+    pub fn incoming_payments_for_invoice(
+        &self,
+        invoice_id: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<IncomingPayment>, Error> {
+        self.runtime.block_on(
+            self.client
+                .incoming_payments_for_invoice(invoice_id, statuses),
+        )
+    }
+
+    /// Fetches the incoming payments for the given payment hash, optionally filtered by status.
+    pub fn incoming_payments_for_payment_hash(
+        &self,
+        payment_hash: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<IncomingPayment>, Error> {
+        self.runtime.block_on(
+            self.client
+                .incoming_payments_for_payment_hash(payment_hash, statuses),
+        )
+    }
+
+    /// Fetches the invoice for the given payment hash, if any.
+    pub fn invoice_for_payment_hash(&self, payment_hash: &str) -> Result<Option<Invoice>, Error> {
+        self.runtime
+            .block_on(self.client.invoice_for_payment_hash(payment_hash))
+    }
+
+    /// Fetches the withdrawal request created with the given idempotency key, if any.
+    pub fn withdrawal_request_for_idempotency_key(
+        &self,
+        idempotency_key: &str,
+    ) -> Result<Option<WithdrawalRequest>, Error> {
+        self.runtime.block_on(
+            self.client
+                .withdrawal_request_for_idempotency_key(idempotency_key),
+        )
+    }
+
+    pub fn wait_for_payment(
+        &self,
+        payment_id: &str,
+        timeout: Duration,
+    ) -> Result<OutgoingPayment, Error> {
+        self.runtime
+            .block_on(self.client.wait_for_payment(payment_id, timeout))
+    }
+
+    pub fn wait_for_incoming_payment(
+        &self,
+        payment_id: &str,
+        timeout: Duration,
+    ) -> Result<IncomingPayment, Error> {
+        self.runtime
+            .block_on(self.client.wait_for_incoming_payment(payment_id, timeout))
+    }
+
+    pub fn wait_for_withdrawal_request(
+        &self,
+        withdrawal_request_id: &str,
+        timeout: Duration,
+    ) -> Result<WithdrawalRequest, Error> {
+        self.runtime.block_on(
+            self.client
+                .wait_for_withdrawal_request(withdrawal_request_id, timeout),
+        )
+    }
+
+    pub fn hash_uma_identifier(
+        identifier: &str,
+        signing_private_key: &[u8],
+        now: DateTime<Utc>,
+    ) -> String {
+        LightsparkClient::<K, R>::hash_uma_identifier(identifier, signing_private_key, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::RSASigningKey;
+    use crate::testing::{fixtures, mock_requester::MockRequester};
+    use serde_json::json;
+
+    #[test]
+    fn test_blocking_client() {
+        let requester = MockRequester::new().respond(
+            "GetCurrentAccount",
+            json!({"current_account": fixtures::object("Account")}),
+        );
+        let client = BlockingLightsparkClient::<RSASigningKey, _>::from_client(
+            LightsparkClient::with_requester(requester),
+        )
+        .unwrap();
+
+        let account = client.get_current_account().unwrap();
+        let wallets = client
+            .block_on(account.get_wallets(&client.client().requester, None, None, None))
+            .unwrap_err();
+        assert!(matches!(wallets, Error::GraphqlError(_)));
+        assert_eq!(client.client().requester.requests().len(), 2);
+    }
+}
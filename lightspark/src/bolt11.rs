@@ -0,0 +1,690 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+//! Offline decoding and validation of BOLT #11 invoices.
+//!
+//! `LightsparkClient::get_decoded_payment_request` asks the API to decode an invoice. `decode`
+//! does the same locally, which is enough to reject malformed, tampered, expired or wrong network
+//! invoices before calling `pay_invoice`.
+
+use std::fmt;
+
+use bech32::u5;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::error::Error;
+use crate::objects::bitcoin_network::BitcoinNetwork;
+use crate::objects::currency_amount::CurrencyAmount;
+use crate::objects::currency_unit::CurrencyUnit;
+use crate::objects::graph_node::GraphNode;
+use crate::objects::invoice_data::InvoiceData;
+use crate::objects::node::NodeEnum;
+
+/// The expiry of an invoice without an `x` field, in seconds.
+const DEFAULT_EXPIRY_SECS: i64 = 3600;
+
+/// The `min_final_cltv_expiry_delta` of an invoice without a `c` field.
+const DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA: u64 = 18;
+
+/// The length of the signature at the end of the data part, in 5 bit words.
+const SIGNATURE_WORDS: usize = 104;
+
+/// The length of the timestamp at the start of the data part, in 5 bit words.
+const TIMESTAMP_WORDS: usize = 7;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvoiceError {
+    Bech32Error(bech32::Error),
+    InvalidPrefix,
+    InvalidAmount,
+    InvalidLength,
+    InvalidField(&'static str),
+    MissingPaymentHash,
+    InvalidSignature,
+    NetworkMismatch { expected: String, actual: String },
+    Expired(DateTime<Utc>),
+}
+
+impl fmt::Display for InvoiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bech32Error(err) => write!(f, "Bech32 error {}", err),
+            Self::InvalidPrefix => write!(f, "Invalid invoice prefix"),
+            Self::InvalidAmount => write!(f, "Invalid invoice amount"),
+            Self::InvalidLength => write!(f, "Invoice is too short"),
+            Self::InvalidField(field) => write!(f, "Invalid {} field", field),
+            Self::MissingPaymentHash => write!(f, "Invoice has no payment hash"),
+            Self::InvalidSignature => write!(f, "Invoice signature is invalid"),
+            Self::NetworkMismatch { expected, actual } => write!(
+                f,
+                "Invoice is for network {} but {} was expected",
+                actual, expected
+            ),
+            Self::Expired(expires_at) => write!(f, "Invoice expired at {}", expires_at),
+        }
+    }
+}
+
+impl std::error::Error for InvoiceError {}
+
+/// A hop of a private route to the payee.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteHintHop {
+    /// The hex encoded public key of the node at the start of the channel.
+    pub public_key: String,
+
+    pub short_channel_id: u64,
+    pub fee_base_msat: u32,
+    pub fee_proportional_millionths: u32,
+    pub cltv_expiry_delta: u16,
+}
+
+/// A decoded BOLT #11 invoice whose signature was verified.
+#[derive(Debug, Clone)]
+pub struct Bolt11Invoice {
+    pub encoded_payment_request: String,
+    pub bitcoin_network: BitcoinNetwork,
+
+    /// The requested amount, or `None` if the sender should choose the amount to send.
+    pub amount_msats: Option<i64>,
+
+    pub created_at: DateTime<Utc>,
+
+    /// The duration after `created_at` during which the invoice can be paid.
+    pub expiry: Duration,
+
+    /// The hex encoded payment hash.
+    pub payment_hash: String,
+
+    /// The hex encoded payment secret, if any.
+    pub payment_secret: Option<String>,
+
+    pub memo: Option<String>,
+
+    /// The hex encoded hash of the description, for invoices whose description is too long to be
+    /// included.
+    pub description_hash: Option<String>,
+
+    /// The hex encoded public key of the node that will be paid.
+    pub payee_public_key: String,
+
+    pub min_final_cltv_expiry_delta: u64,
+
+    /// The private routes to the payee, each as a list of hops.
+    pub route_hints: Vec<Vec<RouteHintHop>>,
+
+    /// The indexes of the feature bits set in the invoice.
+    pub features: Vec<usize>,
+}
+
+impl Bolt11Invoice {
+    /// The time after which the invoice can no longer be paid. An expiry too large to be
+    /// represented saturates to the latest representable time.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.created_at
+            .checked_add_signed(self.expiry)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at()
+    }
+
+    /// Checks that the invoice is for the given network and has not expired at `now`.
+    pub fn validate(&self, network: &BitcoinNetwork, now: DateTime<Utc>) -> Result<(), Error> {
+        if self.bitcoin_network.to_string() != network.to_string() {
+            return Err(Error::InvoiceError(InvoiceError::NetworkMismatch {
+                expected: network.to_string(),
+                actual: self.bitcoin_network.to_string(),
+            }));
+        }
+        if self.is_expired_at(now) {
+            return Err(Error::InvoiceError(InvoiceError::Expired(
+                self.expires_at(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Converts the invoice to the shape returned by the API. The destination is a `GraphNode`
+    /// identified by its public key, since its Lightspark id is not known offline.
+    pub fn to_invoice_data(&self) -> InvoiceData {
+        let amount_msats = self.amount_msats.unwrap_or(0);
+        InvoiceData {
+            encoded_payment_request: self.encoded_payment_request.clone(),
+            bitcoin_network: self.bitcoin_network.clone(),
+            payment_hash: self.payment_hash.clone(),
+            amount: CurrencyAmount {
+                original_value: amount_msats,
+                original_unit: CurrencyUnit::Millisatoshi,
+                preferred_currency_unit: CurrencyUnit::Millisatoshi,
+                preferred_currency_value_rounded: amount_msats,
+                preferred_currency_value_approx: amount_msats as f64,
+            },
+            created_at: self.created_at,
+            expires_at: self.expires_at(),
+            memo: self.memo.clone(),
+            destination: NodeEnum::GraphNode(GraphNode {
+                id: self.payee_public_key.clone(),
+                created_at: self.created_at,
+                updated_at: self.created_at,
+                alias: None,
+                bitcoin_network: self.bitcoin_network.clone(),
+                color: None,
+                conductivity: None,
+                display_name: self.payee_public_key.clone(),
+                public_key: Some(self.payee_public_key.clone()),
+                typename: "GraphNode".to_owned(),
+            }),
+            typename: "InvoiceData".to_owned(),
+        }
+    }
+}
+
+/// Decodes a BOLT #11 invoice and verifies its signature.
+pub fn decode(encoded_payment_request: &str) -> Result<Bolt11Invoice, Error> {
+    decode_invoice(encoded_payment_request).map_err(Error::InvoiceError)
+}
+
+fn decode_invoice(encoded_payment_request: &str) -> Result<Bolt11Invoice, InvoiceError> {
+    let encoded_payment_request = encoded_payment_request.trim();
+    let without_scheme = match encoded_payment_request.split_once(':') {
+        Some((scheme, invoice)) if scheme.eq_ignore_ascii_case("lightning") => invoice,
+        _ => encoded_payment_request,
+    };
+    let (hrp, data, variant) = bech32::decode(without_scheme).map_err(InvoiceError::Bech32Error)?;
+    if variant != bech32::Variant::Bech32 {
+        return Err(InvoiceError::Bech32Error(bech32::Error::InvalidChecksum));
+    }
+    if data.len() < TIMESTAMP_WORDS + SIGNATURE_WORDS {
+        return Err(InvoiceError::InvalidLength);
+    }
+
+    let (bitcoin_network, amount_msats) = parse_hrp(&hrp)?;
+    let (signed_data, signature) = data.split_at(data.len() - SIGNATURE_WORDS);
+    let (timestamp, fields) = signed_data.split_at(TIMESTAMP_WORDS);
+    let created_at = Utc
+        .timestamp_opt(words_to_u64(timestamp) as i64, 0)
+        .single()
+        .ok_or(InvoiceError::InvalidField("timestamp"))?;
+
+    let mut invoice = Bolt11Invoice {
+        encoded_payment_request: without_scheme.to_owned(),
+        bitcoin_network,
+        amount_msats,
+        created_at,
+        expiry: Duration::seconds(DEFAULT_EXPIRY_SECS),
+        payment_hash: String::new(),
+        payment_secret: None,
+        memo: None,
+        description_hash: None,
+        payee_public_key: String::new(),
+        min_final_cltv_expiry_delta: DEFAULT_MIN_FINAL_CLTV_EXPIRY_DELTA,
+        route_hints: vec![],
+        features: vec![],
+    };
+    let mut payee_public_key = None;
+    parse_fields(fields, &mut invoice, &mut payee_public_key)?;
+    if invoice
+        .created_at
+        .checked_add_signed(invoice.expiry)
+        .is_none()
+    {
+        return Err(InvoiceError::InvalidField("expiry"));
+    }
+    if invoice.payment_hash.is_empty() {
+        return Err(InvoiceError::MissingPaymentHash);
+    }
+
+    // The signature covers the human readable part and the data part before the signature,
+    // padded to whole bytes.
+    let mut preimage = hrp.as_bytes().to_vec();
+    preimage.extend(words_to_bytes_with_padding(signed_data, true));
+    let hash = sha256::Hash::hash(&preimage);
+    let message = Message::from_slice(hash.as_byte_array()).expect("hashes are 32 bytes");
+
+    let signature = words_to_bytes(signature);
+    let recovery_id =
+        RecoveryId::from_i32(signature[64] as i32).map_err(|_| InvoiceError::InvalidSignature)?;
+    let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)
+        .map_err(|_| InvoiceError::InvalidSignature)?;
+    let secp = Secp256k1::verification_only();
+    let public_key = match payee_public_key {
+        Some(public_key) => {
+            secp.verify_ecdsa(&message, &signature.to_standard(), &public_key)
+                .map_err(|_| InvoiceError::InvalidSignature)?;
+            public_key
+        }
+        None => secp
+            .recover_ecdsa(&message, &signature)
+            .map_err(|_| InvoiceError::InvalidSignature)?,
+    };
+    invoice.payee_public_key = hex::encode(public_key.serialize());
+    Ok(invoice)
+}
+
+/// Parses `ln` + currency prefix + optional amount.
+fn parse_hrp(hrp: &str) -> Result<(BitcoinNetwork, Option<i64>), InvoiceError> {
+    let rest = hrp.strip_prefix("ln").ok_or(InvoiceError::InvalidPrefix)?;
+    // The longer prefixes come first, since `bc` is a prefix of `bcrt` and `tb` of `tbs`.
+    let (network, amount) = [
+        ("bcrt", BitcoinNetwork::Regtest),
+        ("bc", BitcoinNetwork::Mainnet),
+        ("tbs", BitcoinNetwork::Signet),
+        ("tb", BitcoinNetwork::Testnet),
+    ]
+    .into_iter()
+    .find_map(|(prefix, network)| rest.strip_prefix(prefix).map(|amount| (network, amount)))
+    .ok_or(InvoiceError::InvalidPrefix)?;
+    if amount.is_empty() {
+        return Ok((network, None));
+    }
+
+    let (digits, msats_per_unit) = match amount.chars().last() {
+        Some('m') => (&amount[..amount.len() - 1], Some(100_000_000)),
+        Some('u') => (&amount[..amount.len() - 1], Some(100_000)),
+        Some('n') => (&amount[..amount.len() - 1], Some(100)),
+        Some('p') => (&amount[..amount.len() - 1], None),
+        _ => (amount, Some(100_000_000_000)),
+    };
+    if digits.is_empty() || digits.starts_with('0') || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(InvoiceError::InvalidAmount);
+    }
+    let value: i64 = digits.parse().map_err(|_| InvoiceError::InvalidAmount)?;
+    let amount_msats = match msats_per_unit {
+        Some(msats_per_unit) => value.checked_mul(msats_per_unit),
+        // A pico-bitcoin is a tenth of a millisatoshi.
+        None if value % 10 == 0 => Some(value / 10),
+        None => None,
+    }
+    .ok_or(InvoiceError::InvalidAmount)?;
+    Ok((network, Some(amount_msats)))
+}
+
+fn parse_fields(
+    mut fields: &[u5],
+    invoice: &mut Bolt11Invoice,
+    payee_public_key: &mut Option<PublicKey>,
+) -> Result<(), InvoiceError> {
+    while !fields.is_empty() {
+        if fields.len() < 3 {
+            return Err(InvoiceError::InvalidLength);
+        }
+        let tag = fields[0].to_u8();
+        let length = words_to_u64(&fields[1..3]) as usize;
+        if fields.len() < 3 + length {
+            return Err(InvoiceError::InvalidLength);
+        }
+        let value = &fields[3..3 + length];
+        fields = &fields[3 + length..];
+
+        // Fields with an unexpected length must be skipped, as newer versions may extend them.
+        match tag {
+            // p
+            1 if length == 52 => invoice.payment_hash = hex::encode(words_to_bytes(value)),
+            // s
+            16 if length == 52 => invoice.payment_secret = Some(hex::encode(words_to_bytes(value))),
+            // d
+            13 => {
+                invoice.memo = Some(
+                    String::from_utf8(words_to_bytes(value))
+                        .map_err(|_| InvoiceError::InvalidField("description"))?,
+                )
+            }
+            // h
+            23 if length == 52 => {
+                invoice.description_hash = Some(hex::encode(words_to_bytes(value)))
+            }
+            // n
+            19 if length == 53 => {
+                *payee_public_key = Some(
+                    PublicKey::from_slice(&words_to_bytes(value))
+                        .map_err(|_| InvoiceError::InvalidField("payee"))?,
+                )
+            }
+            // x
+            6 => {
+                let expiry = i64::try_from(words_to_u64(value))
+                    .map_err(|_| InvoiceError::InvalidField("expiry"))?;
+                invoice.expiry =
+                    Duration::try_seconds(expiry).ok_or(InvoiceError::InvalidField("expiry"))?;
+            }
+            // c
+            24 => invoice.min_final_cltv_expiry_delta = words_to_u64(value),
+            // r
+            3 => invoice.route_hints.push(parse_route_hint(value)?),
+            // 9
+            5 => {
+                invoice.features = value
+                    .iter()
+                    .rev()
+                    .enumerate()
+                    .flat_map(|(index, word)| {
+                        (0..5)
+                            .filter(move |bit| word.to_u8() & (1 << bit) != 0)
+                            .map(move |bit| index * 5 + bit)
+                    })
+                    .collect()
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_route_hint(value: &[u5]) -> Result<Vec<RouteHintHop>, InvoiceError> {
+    const HOP_LENGTH: usize = 33 + 8 + 4 + 4 + 2;
+    let bytes = words_to_bytes(value);
+    if bytes.is_empty() || !bytes.len().is_multiple_of(HOP_LENGTH) {
+        return Err(InvoiceError::InvalidField("route hint"));
+    }
+    bytes
+        .chunks(HOP_LENGTH)
+        .map(|hop| {
+            PublicKey::from_slice(&hop[..33])
+                .map_err(|_| InvoiceError::InvalidField("route hint"))?;
+            Ok(RouteHintHop {
+                public_key: hex::encode(&hop[..33]),
+                short_channel_id: u64::from_be_bytes(hop[33..41].try_into().unwrap()),
+                fee_base_msat: u32::from_be_bytes(hop[41..45].try_into().unwrap()),
+                fee_proportional_millionths: u32::from_be_bytes(hop[45..49].try_into().unwrap()),
+                cltv_expiry_delta: u16::from_be_bytes(hop[49..51].try_into().unwrap()),
+            })
+        })
+        .collect()
+}
+
+/// Reads big endian 5 bit words as a number. Numbers longer than 64 bits saturate.
+fn words_to_u64(words: &[u5]) -> u64 {
+    if words.len() > 12 {
+        return u64::MAX;
+    }
+    words
+        .iter()
+        .fold(0u64, |acc, word| (acc << 5) | word.to_u8() as u64)
+}
+
+/// Converts 5 bit words to bytes. With `pad`, the trailing bits are padded with zeros to a whole
+/// byte, otherwise they are dropped.
+fn words_to_bytes_with_padding(words: &[u5], pad: bool) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(words.len() * 5 / 8 + 1);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for word in words {
+        acc = (acc << 5) | word.to_u8() as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if pad && bits > 0 {
+        bytes.push((acc << (8 - bits)) as u8);
+    }
+    bytes
+}
+
+fn words_to_bytes(words: &[u5]) -> Vec<u8> {
+    words_to_bytes_with_padding(words, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bech32::ToBase32;
+    use bitcoin::secp256k1::SecretKey;
+
+    // Test vectors from BOLT #11.
+    const DONATION: &str = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+    const COFFEE: &str = "lnbc2500u1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpu9qrsgquk0rl77nj30yxdy8j9vdx85fkpmdla2087ne0xh8nhedh8w27kyke0lp53ut353s06fv3qfegext0eh0ymjpf39tuven09sam30g4vgpfna3rh";
+    const PAYEE: &str = "03e7156ae33b0a208d0744199163177e909e80176e55d97a2f221ede0f934dd9ad";
+    const TIMESTAMP: u64 = 1_700_000_000;
+
+    fn tagged_field(tag: u8, value: &[u8]) -> Vec<u5> {
+        tagged_words(tag, value.to_base32())
+    }
+
+    /// Integer fields are encoded as big endian 5 bit words, without padding.
+    fn int_field(tag: u8, value: u64) -> Vec<u5> {
+        let words = (0..=(63 - value.leading_zeros()) / 5)
+            .rev()
+            .map(|i| u5::try_from_u8(((value >> (i * 5)) & 31) as u8).unwrap())
+            .collect();
+        tagged_words(tag, words)
+    }
+
+    fn tagged_words(tag: u8, words: Vec<u5>) -> Vec<u5> {
+        let length = words.len() as u8;
+        vec![
+            u5::try_from_u8(tag).unwrap(),
+            u5::try_from_u8(length >> 5).unwrap(),
+            u5::try_from_u8(length & 31).unwrap(),
+        ]
+        .into_iter()
+        .chain(words)
+        .collect()
+    }
+
+    /// Encodes and signs an invoice with the given tagged fields. With `tamper`, a memo is added
+    /// after signing.
+    fn encode(hrp: &str, fields: &[Vec<u5>], key: &SecretKey, tamper: bool) -> String {
+        let mut data = (0..TIMESTAMP_WORDS)
+            .rev()
+            .map(|i| u5::try_from_u8(((TIMESTAMP >> (i * 5)) & 31) as u8).unwrap())
+            .collect::<Vec<_>>();
+        for field in fields {
+            data.extend(field);
+        }
+
+        let mut preimage = hrp.as_bytes().to_vec();
+        preimage.extend(words_to_bytes_with_padding(&data, true));
+        let hash = sha256::Hash::hash(&preimage);
+        let message = Message::from_slice(hash.as_byte_array()).unwrap();
+        let (recovery_id, signature) = Secp256k1::new()
+            .sign_ecdsa_recoverable(&message, key)
+            .serialize_compact();
+        let mut signature = signature.to_vec();
+        signature.push(recovery_id.to_i32() as u8);
+
+        if tamper {
+            data.extend(tagged_field(13, b"tampered"));
+        }
+        data.extend(signature.to_base32());
+        bech32::encode(hrp, data, bech32::Variant::Bech32).unwrap()
+    }
+
+    fn key() -> (SecretKey, PublicKey) {
+        let key = SecretKey::from_slice(&[7; 32]).unwrap();
+        (key, key.public_key(&Secp256k1::new()))
+    }
+
+    fn regtest_fields(public_key: &PublicKey) -> Vec<Vec<u5>> {
+        let mut hop = public_key.serialize().to_vec();
+        hop.extend(123_456u64.to_be_bytes());
+        hop.extend(1_000u32.to_be_bytes());
+        hop.extend(100u32.to_be_bytes());
+        hop.extend(40u16.to_be_bytes());
+        vec![
+            tagged_field(1, &[2; 32]),
+            tagged_field(13, b"regtest"),
+            int_field(6, 300),
+            int_field(24, 144),
+            tagged_field(19, &public_key.serialize()),
+            tagged_field(3, &[hop.clone(), hop].concat()),
+        ]
+    }
+
+    #[test]
+    fn test_decode_spec_vectors() {
+        let donation = decode(DONATION).unwrap();
+        assert_eq!(donation.amount_msats, None);
+        assert_eq!(donation.created_at.timestamp(), 1_496_314_658);
+        assert_eq!(
+            donation.payment_hash,
+            "0001020304050607080900010203040506070809000102030405060708090102"
+        );
+        assert_eq!(donation.payment_secret, Some("11".repeat(32)));
+        assert_eq!(
+            donation.memo.as_deref(),
+            Some("Please consider supporting this project")
+        );
+        assert_eq!(donation.payee_public_key, PAYEE);
+        assert_eq!(donation.expiry, Duration::seconds(3600));
+        assert_eq!(donation.features, [8, 14]);
+
+        let coffee = decode(&format!("lightning:{}", COFFEE)).unwrap();
+        assert_eq!(coffee.encoded_payment_request, COFFEE);
+        assert_eq!(
+            decode(&format!(" Lightning:{} ", COFFEE))
+                .unwrap()
+                .encoded_payment_request,
+            COFFEE
+        );
+        assert_eq!(coffee.amount_msats, Some(250_000_000));
+        assert_eq!(coffee.memo.as_deref(), Some("1 cup coffee"));
+        assert_eq!(coffee.expiry, Duration::seconds(60));
+        assert_eq!(coffee.payee_public_key, PAYEE);
+
+        let invoice_data = coffee.to_invoice_data();
+        assert_eq!(invoice_data.amount.original_value, 250_000_000);
+        assert_eq!(
+            invoice_data.expires_at,
+            coffee.created_at + Duration::seconds(60)
+        );
+        match invoice_data.destination {
+            NodeEnum::GraphNode(node) => assert_eq!(node.public_key.as_deref(), Some(PAYEE)),
+            _ => panic!("destination should be a graph node"),
+        }
+    }
+
+    #[test]
+    fn test_decode_route_hints() {
+        let (key, public_key) = key();
+        let invoice = decode(&encode(
+            "lnbcrt10n",
+            &regtest_fields(&public_key),
+            &key,
+            false,
+        ))
+        .unwrap();
+        assert!(matches!(invoice.bitcoin_network, BitcoinNetwork::Regtest));
+        assert_eq!(invoice.amount_msats, Some(1_000));
+        assert_eq!(invoice.expiry, Duration::seconds(300));
+        assert_eq!(invoice.min_final_cltv_expiry_delta, 144);
+        assert_eq!(invoice.payee_public_key, public_key.to_string());
+        assert_eq!(invoice.route_hints.len(), 1);
+        assert_eq!(
+            invoice.route_hints[0],
+            vec![
+                RouteHintHop {
+                    public_key: public_key.to_string(),
+                    short_channel_id: 123_456,
+                    fee_base_msat: 1_000,
+                    fee_proportional_millionths: 100,
+                    cltv_expiry_delta: 40,
+                };
+                2
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let (key, public_key) = key();
+        let invoice = decode(&encode(
+            "lnbcrt10n",
+            &regtest_fields(&public_key),
+            &key,
+            false,
+        ))
+        .unwrap();
+        assert!(invoice
+            .validate(&BitcoinNetwork::Regtest, invoice.created_at)
+            .is_ok());
+        assert!(matches!(
+            invoice.validate(&BitcoinNetwork::Mainnet, invoice.created_at),
+            Err(Error::InvoiceError(InvoiceError::NetworkMismatch { .. }))
+        ));
+        assert!(matches!(
+            invoice.validate(&BitcoinNetwork::Regtest, invoice.expires_at()),
+            Err(Error::InvoiceError(InvoiceError::Expired(_)))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_invoices() {
+        let (key, public_key) = key();
+        let tampered = encode("lnbcrt10n", &regtest_fields(&public_key), &key, true);
+        assert!(matches!(
+            decode(&tampered),
+            Err(Error::InvoiceError(InvoiceError::InvalidSignature))
+        ));
+
+        let mut bad_checksum = COFFEE.to_owned();
+        bad_checksum.replace_range(20..21, "q");
+        assert!(matches!(
+            decode(&bad_checksum),
+            Err(Error::InvoiceError(InvoiceError::Bech32Error(_)))
+        ));
+
+        let oversized_expiry = encode(
+            "lnbc",
+            &[tagged_field(1, &[2; 32]), int_field(6, 1 << 50)],
+            &key,
+            false,
+        );
+        assert!(matches!(
+            decode(&oversized_expiry),
+            Err(Error::InvoiceError(InvoiceError::InvalidField("expiry")))
+        ));
+
+        let without_hash = encode("lnbc", &[tagged_field(13, b"no hash")], &key, false);
+        assert!(matches!(
+            decode(&without_hash),
+            Err(Error::InvoiceError(InvoiceError::MissingPaymentHash))
+        ));
+    }
+
+    #[test]
+    fn test_parse_hrp() {
+        assert!(matches!(
+            parse_hrp("lnbc2500u"),
+            Ok((BitcoinNetwork::Mainnet, Some(250_000_000)))
+        ));
+        assert!(matches!(
+            parse_hrp("lnbc20m"),
+            Ok((BitcoinNetwork::Mainnet, Some(2_000_000_000)))
+        ));
+        assert!(matches!(
+            parse_hrp("lntb10p"),
+            Ok((BitcoinNetwork::Testnet, Some(1)))
+        ));
+        assert!(matches!(
+            parse_hrp("lntbs"),
+            Ok((BitcoinNetwork::Signet, None))
+        ));
+        assert!(matches!(
+            parse_hrp("lnbcrt1"),
+            Ok((BitcoinNetwork::Regtest, Some(100_000_000_000)))
+        ));
+        assert_eq!(
+            parse_hrp("lnbc1p").unwrap_err(),
+            InvoiceError::InvalidAmount
+        );
+        assert_eq!(
+            parse_hrp("lnbc025m").unwrap_err(),
+            InvoiceError::InvalidAmount
+        );
+        assert_eq!(
+            parse_hrp("lnbc1x").unwrap_err(),
+            InvoiceError::InvalidAmount
+        );
+        assert_eq!(
+            parse_hrp("lnxy1m").unwrap_err(),
+            InvoiceError::InvalidPrefix
+        );
+    }
+}
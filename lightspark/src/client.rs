@@ -2,6 +2,7 @@
 
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 use bitcoin::bip32::{DerivationPath, ExtendedPrivKey};
 use bitcoin::secp256k1::Secp256k1;
@@ -27,33 +28,136 @@ use crate::objects::outgoing_payment::OutgoingPayment;
 use crate::objects::permission::Permission;
 use crate::objects::region_code::RegionCode;
 use crate::objects::risk_rating::RiskRating;
+use crate::objects::transaction_status::TransactionStatus;
 use crate::objects::uma_invitation::UmaInvitation;
 use crate::objects::withdrawal_mode::WithdrawalMode;
 use crate::objects::withdrawal_request::WithdrawalRequest;
+use crate::objects::withdrawal_request_status::WithdrawalRequestStatus;
 use crate::objects::{account, invoice_data, uma_invitation};
 use crate::objects::{api_token, incoming_payment, outgoing_payment};
 use crate::objects::{bitcoin_network, withdrawal_request};
 use crate::objects::{fee_estimate, lightning_fee_estimate_output};
 use crate::request::auth_provider::AuthProvider;
-use crate::request::requester::Requester;
+use crate::request::requester::{Requester, RequesterBuilder};
+use crate::request::retry::RetryPolicy;
 use crate::types::get_entity::GetEntity;
-use crate::types::graphql_requester::GraphQLRequester;
+use crate::types::graphql_requester::SigningGraphQLRequester;
 
 const SIGNING_KEY_PATH: &str = "m/5";
 
-pub struct LightsparkClient<T: OperationSigningKey> {
-    pub requester: Requester,
+/// The delay before the second poll of the `wait_for_*` methods. Each following poll doubles it.
+const WAIT_POLL_INITIAL_DELAY: Duration = Duration::from_millis(250);
+
+/// The upper bound for the delay between two polls of the `wait_for_*` methods.
+const WAIT_POLL_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// The delay to wait after the given poll, starting at 1.
+fn wait_poll_delay(poll: u32) -> Duration {
+    WAIT_POLL_INITIAL_DELAY
+        .saturating_mul(1 << poll.saturating_sub(1).min(31))
+        .min(WAIT_POLL_MAX_DELAY)
+}
+
+pub struct LightsparkClient<T: OperationSigningKey, R: SigningGraphQLRequester = Requester> {
+    pub requester: R,
     signing_keys: HashMap<String, T>,
 }
 
-impl<K: OperationSigningKey> LightsparkClient<K> {
-    pub fn new<T: AuthProvider>(auth_provider: T) -> Result<Self, Error> {
-        let requester = Requester::new(auth_provider)?;
+/// A builder for `LightsparkClient`, exposing the options of the underlying `RequesterBuilder`.
+pub struct LightsparkClientBuilder {
+    requester_builder: RequesterBuilder,
+}
+
+impl LightsparkClientBuilder {
+    pub fn new<T: AuthProvider>(auth_provider: T) -> Self {
+        Self {
+            requester_builder: RequesterBuilder::new(auth_provider),
+        }
+    }
+
+    /// Sets the graphql endpoint. Defaults to the Lightspark API.
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.requester_builder = self.requester_builder.base_url(base_url);
+        self
+    }
+
+    /// Sets the timeout for establishing a connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.requester_builder = self.requester_builder.connect_timeout(timeout);
+        self
+    }
+
+    /// Sets the timeout for each read of the response.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.requester_builder = self.requester_builder.read_timeout(timeout);
+        self
+    }
+
+    /// Sets the timeout for a whole request, from connecting until the response is read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.requester_builder = self.requester_builder.timeout(timeout);
+        self
+    }
+
+    /// Sends all requests through the given http or https proxy url.
+    pub fn proxy(mut self, proxy_url: &str) -> Self {
+        self.requester_builder = self.requester_builder.proxy(proxy_url);
+        self
+    }
+
+    /// Trusts the given PEM encoded root certificates in addition to the built-in ones.
+    pub fn add_root_certificate_pem(mut self, certificate_pem: &[u8]) -> Self {
+        self.requester_builder = self
+            .requester_builder
+            .add_root_certificate_pem(certificate_pem);
+        self
+    }
+
+    /// Appends the given suffix to the `User-Agent` header.
+    pub fn user_agent_suffix(mut self, suffix: &str) -> Self {
+        self.requester_builder = self.requester_builder.user_agent_suffix(suffix);
+        self
+    }
+
+    /// Sets the policy used to retry failed requests.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.requester_builder = self.requester_builder.retry_policy(retry_policy);
+        self
+    }
+
+    pub fn build<K: OperationSigningKey>(self) -> Result<LightsparkClient<K>, Error> {
+        let requester = self.requester_builder.build()?;
         Ok(LightsparkClient {
             requester,
             signing_keys: HashMap::new(),
         })
     }
+}
+
+impl<K: OperationSigningKey> LightsparkClient<K> {
+    pub fn new<T: AuthProvider>(auth_provider: T) -> Result<Self, Error> {
+        LightsparkClientBuilder::new(auth_provider).build()
+    }
+
+    pub fn builder<T: AuthProvider>(auth_provider: T) -> LightsparkClientBuilder {
+        LightsparkClientBuilder::new(auth_provider)
+    }
+
+    /// Sets the policy used to retry failed requests.
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.requester.set_retry_policy(retry_policy);
+    }
+}
+
+impl<K: OperationSigningKey, R: SigningGraphQLRequester> LightsparkClient<K, R> {
+    /// Creates a client that sends its operations through the given requester instead of the
+    /// default http `Requester`.
+    pub fn with_requester(requester: R) -> Self {
+        Self {
+            requester,
+            signing_keys: HashMap::new(),
+        }
+    }
 
     pub fn provide_master_seed(
         &mut self,
@@ -470,11 +574,10 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
     }
 
     fn get_node_signing_key(&self, node_id: &str) -> Result<K, Error> {
-        return self
-            .signing_keys
+        self.signing_keys
             .get(node_id)
             .cloned()
-            .ok_or(Error::SigningKeyNotFound);
+            .ok_or(Error::SigningKeyNotFound)
     }
 
     pub async fn get_decoded_payment_request(
@@ -562,6 +665,29 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         timeout_secs: i32,
         amount_msats: Option<i64>,
         maximum_fees_msats: i64,
+    ) -> Result<OutgoingPayment, Error> {
+        self.pay_invoice_with_idempotency_key(
+            node_id,
+            encoded_invoice,
+            timeout_secs,
+            amount_msats,
+            maximum_fees_msats,
+            None,
+        )
+        .await
+    }
+
+    /// Pays an invoice. Requests sharing an `idempotency_key` return the same payment, and if the
+    /// request fails without a definitive answer, the payment already created for the key (if
+    /// any) is returned instead of the error.
+    pub async fn pay_invoice_with_idempotency_key(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        timeout_secs: i32,
+        amount_msats: Option<i64>,
+        maximum_fees_msats: i64,
+        idempotency_key: Option<&str>,
     ) -> Result<OutgoingPayment, Error> {
         let operation = format!(
             "
@@ -571,6 +697,7 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
             $timeout_secs: Int!
             $maximum_fees_msats: Long!
             $amount_msats: Long
+            $idempotency_key: String
         ) {{
             pay_invoice(input: {{
                 node_id: $node_id
@@ -578,6 +705,7 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
                 timeout_secs: $timeout_secs
                 maximum_fees_msats: $maximum_fees_msats
                 amount_msats: $amount_msats
+                idempotency_key: $idempotency_key
             }}) {{
                 payment {{
                     ...OutgoingPaymentFragment
@@ -598,14 +726,21 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         }
         variables.insert("timeout_secs", timeout_secs.into());
         variables.insert("maximum_fees_msats", maximum_fees_msats.into());
+        if let Some(idempotency_key) = idempotency_key {
+            variables.insert("idempotency_key", idempotency_key.into());
+        }
 
         let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
 
         let signing_key = self.get_node_signing_key(node_id)?;
-        let json = self
+        let json = match self
             .requester
             .execute_graphql_signing(&operation, Some(value), Some(signing_key))
-            .await?;
+            .await
+        {
+            Ok(json) => json,
+            Err(err) => return self.recover_outgoing_payment(err, idempotency_key).await,
+        };
 
         let result = serde_json::from_value(json["pay_invoice"]["payment"].clone())
             .map_err(Error::JsonError)?;
@@ -619,6 +754,29 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         timeout_secs: i32,
         amount_msats: i64,
         maximum_fees_msats: i64,
+    ) -> Result<OutgoingPayment, Error> {
+        self.send_payment_with_idempotency_key(
+            node_id,
+            destination_public_key,
+            timeout_secs,
+            amount_msats,
+            maximum_fees_msats,
+            None,
+        )
+        .await
+    }
+
+    /// Sends a keysend payment. Requests sharing an `idempotency_key` return the same payment, and
+    /// if the request fails without a definitive answer, the payment already created for the key
+    /// (if any) is returned instead of the error.
+    pub async fn send_payment_with_idempotency_key(
+        &self,
+        node_id: &str,
+        destination_public_key: &str,
+        timeout_secs: i32,
+        amount_msats: i64,
+        maximum_fees_msats: i64,
+        idempotency_key: Option<&str>,
     ) -> Result<OutgoingPayment, Error> {
         let operation = format!(
             "
@@ -628,6 +786,7 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
             $amount_msats: Long!
             $timeout_secs: Int!
             $maximum_fees_msats: Long!
+            $idempotency_key: String
         ) {{
             send_payment(input: {{
                 node_id: $node_id
@@ -635,6 +794,7 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
                 amount_msats: $amount_msats
                 timeout_secs: $timeout_secs
                 maximum_fees_msats: $maximum_fees_msats
+                idempotency_key: $idempotency_key
             }}) {{
                 payment {{
                     ...OutgoingPaymentFragment
@@ -652,15 +812,22 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         variables.insert("amount_msats", amount_msats.into());
         variables.insert("timeout_secs", timeout_secs.into());
         variables.insert("maximum_fees_msats", maximum_fees_msats.into());
+        if let Some(idempotency_key) = idempotency_key {
+            variables.insert("idempotency_key", idempotency_key.into());
+        }
 
         let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
 
         let signing_key = self.get_node_signing_key(node_id)?;
 
-        let json = self
+        let json = match self
             .requester
             .execute_graphql_signing(&operation, Some(value), Some(signing_key))
-            .await?;
+            .await
+        {
+            Ok(json) => json,
+            Err(err) => return self.recover_outgoing_payment(err, idempotency_key).await,
+        };
 
         let result = serde_json::from_value(json["send_payment"]["payment"].clone())
             .map_err(Error::JsonError)?;
@@ -726,6 +893,27 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         bitcoin_address: &str,
         amount_sats: i64,
         withdrawal_mode: WithdrawalMode,
+    ) -> Result<WithdrawalRequest, Error> {
+        self.request_withdrawal_with_idempotency_key(
+            node_id,
+            bitcoin_address,
+            amount_sats,
+            withdrawal_mode,
+            None,
+        )
+        .await
+    }
+
+    /// Requests a withdrawal. Requests sharing an `idempotency_key` return the same withdrawal
+    /// request, and if the request fails without a definitive answer, the withdrawal request
+    /// already created for the key (if any) is returned instead of the error.
+    pub async fn request_withdrawal_with_idempotency_key(
+        &self,
+        node_id: &str,
+        bitcoin_address: &str,
+        amount_sats: i64,
+        withdrawal_mode: WithdrawalMode,
+        idempotency_key: Option<&str>,
     ) -> Result<WithdrawalRequest, Error> {
         let operation = format!(
             "
@@ -734,12 +922,14 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
             $amount_sats: Long!
             $bitcoin_address: String!
             $withdrawal_mode: WithdrawalMode!
+            $idempotency_key: String
         ) {{
             request_withdrawal(input: {{
                 node_id: $node_id
                 amount_sats: $amount_sats
                 bitcoin_address: $bitcoin_address
                 withdrawal_mode: $withdrawal_mode
+                idempotency_key: $idempotency_key
             }}) {{
                 request {{
                     ...WithdrawalRequestFragment
@@ -756,13 +946,20 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         variables.insert("amount_sats", amount_sats.into());
         variables.insert("bitcoin_address", bitcoin_address.into());
         variables.insert("withdrawal_mode", withdrawal_mode.into());
+        if let Some(idempotency_key) = idempotency_key {
+            variables.insert("idempotency_key", idempotency_key.into());
+        }
 
         let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
         let signing_key = self.get_node_signing_key(node_id)?;
-        let json = self
+        let json = match self
             .requester
             .execute_graphql_signing(&operation, Some(value), Some(signing_key))
-            .await?;
+            .await
+        {
+            Ok(json) => json,
+            Err(err) => return self.recover_withdrawal_request(err, idempotency_key).await,
+        };
 
         let result = serde_json::from_value(json["request_withdrawal"]["request"].clone())
             .map_err(Error::JsonError)?;
@@ -976,6 +1173,7 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         .await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn pay_uma_invoice_with_sender_identifier(
         &self,
         node_id: &str,
@@ -985,6 +1183,34 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         amount_msats: Option<i64>,
         signing_private_key: Option<&[u8]>,
         sender_identifier: Option<&str>,
+    ) -> Result<OutgoingPayment, Error> {
+        self.pay_uma_invoice_with_idempotency_key(
+            node_id,
+            encoded_invoice,
+            timeout_secs,
+            maximum_fees_msats,
+            amount_msats,
+            signing_private_key,
+            sender_identifier,
+            None,
+        )
+        .await
+    }
+
+    /// Pays an UMA invoice. Requests sharing an `idempotency_key` return the same payment, and if
+    /// the request fails without a definitive answer, the payment already created for the key
+    /// (if any) is returned instead of the error.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn pay_uma_invoice_with_idempotency_key(
+        &self,
+        node_id: &str,
+        encoded_invoice: &str,
+        timeout_secs: i32,
+        maximum_fees_msats: i64,
+        amount_msats: Option<i64>,
+        signing_private_key: Option<&[u8]>,
+        sender_identifier: Option<&str>,
+        idempotency_key: Option<&str>,
     ) -> Result<OutgoingPayment, Error> {
         let operation = format!(
             "mutation PayUmaInvoice(
@@ -994,6 +1220,7 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
             $maximum_fees_msats: Long!
             $amount_msats: Long
             $sender_hash: String
+            $idempotency_key: String
         ) {{
             pay_uma_invoice(input: {{
                 node_id: $node_id
@@ -1002,6 +1229,7 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
                 maximum_fees_msats: $maximum_fees_msats
                 amount_msats: $amount_msats
                 sender_hash: $sender_hash
+                idempotency_key: $idempotency_key
             }}) {{
                 payment {{
                     ...OutgoingPaymentFragment
@@ -1040,14 +1268,21 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         if let Some(sender_hash) = sender_hash {
             variables.insert("sender_hash", sender_hash.into());
         }
+        if let Some(idempotency_key) = idempotency_key {
+            variables.insert("idempotency_key", idempotency_key.into());
+        }
 
         let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
 
         let signing_key = self.get_node_signing_key(node_id)?;
-        let json = self
+        let json = match self
             .requester
             .execute_graphql_signing(&operation, Some(value), Some(signing_key))
-            .await?;
+            .await
+        {
+            Ok(json) => json,
+            Err(err) => return self.recover_outgoing_payment(err, idempotency_key).await,
+        };
 
         let result = serde_json::from_value(json["pay_uma_invoice"]["payment"].clone())
             .map_err(Error::JsonError)?;
@@ -1297,6 +1532,408 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
         Ok(result)
     }
 
+    /// Fetches the outgoing payment created with the given idempotency key, if any.
+    pub async fn outgoing_payment_for_idempotency_key(
+        &self,
+        idempotency_key: &str,
+    ) -> Result<Option<OutgoingPayment>, Error> {
+        let operation = format!(
+            "query OutgoingPaymentForIdempotencyKey(
+            $idempotency_key: String!
+        ) {{
+            outgoing_payment_for_idempotency_key(input: {{
+                idempotency_key: $idempotency_key
+            }}) {{
+                payment {{
+                    ...OutgoingPaymentFragment
+                }}
+            }}
+        }}
+
+        {}
+        ",
+            outgoing_payment::FRAGMENT
+        );
+
+        let mut variables: HashMap<&str, Value> = HashMap::new();
+        variables.insert("idempotency_key", idempotency_key.into());
+
+        let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
+        let json = self
+            .requester
+            .execute_graphql(&operation, Some(value))
+            .await?;
+
+        let result =
+            serde_json::from_value(json["outgoing_payment_for_idempotency_key"]["payment"].clone())
+                .map_err(Error::JsonError)?;
+        Ok(result)
+    }
+
+    /// Fetches the outgoing payments made to the given encoded invoice, optionally filtered by
+    /// status.
+    pub async fn outgoing_payments_for_invoice(
+        &self,
+        encoded_invoice: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<OutgoingPayment>, Error> {
+        let operation = format!(
+            "query OutgoingPaymentsForInvoice(
+            $encoded_invoice: String!
+            $statuses: [TransactionStatus!]
+        ) {{
+            outgoing_payments_for_invoice(input: {{
+                encoded_invoice: $encoded_invoice
+                statuses: $statuses
+            }}) {{
+                payments {{
+                    ...OutgoingPaymentFragment
+                }}
+            }}
+        }}
+
+        {}
+        ",
+            outgoing_payment::FRAGMENT
+        );
+
+        let mut variables: HashMap<&str, Value> = HashMap::new();
+        variables.insert("encoded_invoice", encoded_invoice.into());
+        variables.insert("statuses", statuses.into());
+
+        let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
+        let json = self
+            .requester
+            .execute_graphql(&operation, Some(value))
+            .await?;
+
+        let result =
+            serde_json::from_value(json["outgoing_payments_for_invoice"]["payments"].clone())
+                .map_err(Error::JsonError)?;
+        Ok(result)
+    }
+
+    /// Fetches the outgoing payments for the given payment hash, optionally filtered by status.
+    pub async fn outgoing_payments_for_payment_hash(
+        &self,
+        payment_hash: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<OutgoingPayment>, Error> {
+        let operation = format!(
+            "query OutgoingPaymentsForPaymentHash(
+            $payment_hash: Hash32!
+            $statuses: [TransactionStatus!]
+        ) {{
+            outgoing_payments_for_payment_hash(input: {{
+                payment_hash: $payment_hash
+                statuses: $statuses
+            }}) {{
+                payments {{
+                    ...OutgoingPaymentFragment
+                }}
+            }}
+        }}
+
+        {}
+        ",
+            outgoing_payment::FRAGMENT
+        );
+
+        let mut variables: HashMap<&str, Value> = HashMap::new();
+        variables.insert("payment_hash", payment_hash.into());
+        variables.insert("statuses", statuses.into());
+
+        let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
+        let json = self
+            .requester
+            .execute_graphql(&operation, Some(value))
+            .await?;
+
+        let result =
+            serde_json::from_value(json["outgoing_payments_for_payment_hash"]["payments"].clone())
+                .map_err(Error::JsonError)?;
+        Ok(result)
+    }
+
+    /// Fetches the incoming payments received for the given invoice, optionally filtered by
+    /// status.
+    pub async fn incoming_payments_for_invoice(
+        &self,
+        invoice_id: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<IncomingPayment>, Error> {
+        let operation = format!(
+            "query IncomingPaymentsForInvoice(
+            $invoice_id: ID!
+            $statuses: [TransactionStatus!]
+        ) {{
+            incoming_payments_for_invoice(input: {{
+                invoice_id: $invoice_id
+                statuses: $statuses
+            }}) {{
+                payments {{
+                    ...IncomingPaymentFragment
+                }}
+            }}
+        }}
+
+        {}
+        ",
+            incoming_payment::FRAGMENT
+        );
+
+        let mut variables: HashMap<&str, Value> = HashMap::new();
+        variables.insert("invoice_id", invoice_id.into());
+        variables.insert("statuses", statuses.into());
+
+        let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
+        let json = self
+            .requester
+            .execute_graphql(&operation, Some(value))
+            .await?;
+
+        let result =
+            serde_json::from_value(json["incoming_payments_for_invoice"]["payments"].clone())
+                .map_err(Error::JsonError)?;
+        Ok(result)
+    }
+
+    /// Fetches the incoming payments for the given payment hash, optionally filtered by status.
+    pub async fn incoming_payments_for_payment_hash(
+        &self,
+        payment_hash: &str,
+        statuses: Option<Vec<TransactionStatus>>,
+    ) -> Result<Vec<IncomingPayment>, Error> {
+        let operation = format!(
+            "query IncomingPaymentsForPaymentHash(
+            $payment_hash: Hash32!
+            $statuses: [TransactionStatus!]
+        ) {{
+            incoming_payments_for_payment_hash(input: {{
+                payment_hash: $payment_hash
+                statuses: $statuses
+            }}) {{
+                payments {{
+                    ...IncomingPaymentFragment
+                }}
+            }}
+        }}
+
+        {}
+        ",
+            incoming_payment::FRAGMENT
+        );
+
+        let mut variables: HashMap<&str, Value> = HashMap::new();
+        variables.insert("payment_hash", payment_hash.into());
+        variables.insert("statuses", statuses.into());
+
+        let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
+        let json = self
+            .requester
+            .execute_graphql(&operation, Some(value))
+            .await?;
+
+        let result =
+            serde_json::from_value(json["incoming_payments_for_payment_hash"]["payments"].clone())
+                .map_err(Error::JsonError)?;
+        Ok(result)
+    }
+
+    /// Fetches the invoice for the given payment hash, if any.
+    pub async fn invoice_for_payment_hash(
+        &self,
+        payment_hash: &str,
+    ) -> Result<Option<Invoice>, Error> {
+        let operation = format!(
+            "query InvoiceForPaymentHash(
+            $payment_hash: Hash32!
+        ) {{
+            invoice_for_payment_hash(input: {{
+                payment_hash: $payment_hash
+            }}) {{
+                invoice {{
+                    ...InvoiceFragment
+                }}
+            }}
+        }}
+
+        {}
+        ",
+            invoice::FRAGMENT
+        );
+
+        let mut variables: HashMap<&str, Value> = HashMap::new();
+        variables.insert("payment_hash", payment_hash.into());
+
+        let value = serde_json::to_value(variables).map_err(Error::ConversionError)?;
+        let json = self
+            .requester
+            .execute_graphql(&operation, Some(value))
+            .await?;
+
+        let result = serde_json::from_value(json["invoice_for_payment_hash"]["invoice"].clone())
+            .map_err(Error::JsonError)?;
+        Ok(result)
+    }
+
+    /// Fetches the withdrawal request created with the given idempotency key, if any.
+    pub async fn withdrawal_request_for_idempotency_key(
+        &self,
+        idempotency_key: &str,
+    ) -> Result<Option<WithdrawalRequest>, Error> {
+        let account = self.get_current_account().await?;
+        let connection = account
+            .get_withdrawal_requests(
+                &self.requester,
+                Some(1),
+                None,
+                None,
+                None,
+                None,
+                Some(vec![idempotency_key.to_owned()]),
+                None,
+                None,
+            )
+            .await?;
+        Ok(connection.entities.into_iter().next())
+    }
+
+    /// Polls the outgoing payment with the given id until it succeeds, fails or is cancelled, and
+    /// returns it. Fails with `Error::Timeout` if the payment is still pending after `timeout`.
+    pub async fn wait_for_payment(
+        &self,
+        payment_id: &str,
+        timeout: Duration,
+    ) -> Result<OutgoingPayment, Error> {
+        self.wait_for_entity(payment_id, timeout, |payment: &OutgoingPayment| {
+            is_terminal_transaction_status(&payment.status)
+        })
+        .await
+    }
+
+    /// Same as `wait_for_payment`, for incoming payments.
+    pub async fn wait_for_incoming_payment(
+        &self,
+        payment_id: &str,
+        timeout: Duration,
+    ) -> Result<IncomingPayment, Error> {
+        self.wait_for_entity(payment_id, timeout, |payment: &IncomingPayment| {
+            is_terminal_transaction_status(&payment.status)
+        })
+        .await
+    }
+
+    /// Polls the withdrawal request with the given id until it succeeds, partially succeeds or
+    /// fails, and returns it. Fails with `Error::Timeout` if it is still in progress after
+    /// `timeout`.
+    pub async fn wait_for_withdrawal_request(
+        &self,
+        withdrawal_request_id: &str,
+        timeout: Duration,
+    ) -> Result<WithdrawalRequest, Error> {
+        self.wait_for_entity(
+            withdrawal_request_id,
+            timeout,
+            |request: &WithdrawalRequest| {
+                matches!(
+                    request.status,
+                    WithdrawalRequestStatus::Successful
+                        | WithdrawalRequestStatus::PartiallySuccessful
+                        | WithdrawalRequestStatus::Failed
+                )
+            },
+        )
+        .await
+    }
+
+    async fn wait_for_entity<T: GetEntity>(
+        &self,
+        id: &str,
+        timeout: Duration,
+        is_terminal: impl Fn(&T) -> bool,
+    ) -> Result<T, Error> {
+        let deadline = Instant::now() + timeout;
+        let mut poll = 1;
+        let mut last_error = None;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let result = match tokio::time::timeout(remaining, self.get_entity::<T>(id)).await {
+                Ok(result) => result,
+                Err(_) => break,
+            };
+            match result {
+                Ok(entity) if is_terminal(&entity) => return Ok(entity),
+                Ok(_) => last_error = None,
+                // Transient failures don't end the wait, the next poll may succeed.
+                Err(err) if err.is_retryable() || matches!(err, Error::ReqwestError(_)) => {
+                    last_error = Some(err)
+                }
+                Err(err) => return Err(err),
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            tokio::time::sleep(wait_poll_delay(poll).min(remaining)).await;
+            poll += 1;
+        }
+
+        Err(Error::Timeout(match last_error {
+            Some(err) => format!(
+                "waiting for {} after {:?}, last error: {}",
+                id, timeout, err
+            ),
+            None => format!("waiting for {} after {:?}", id, timeout),
+        }))
+    }
+
+    /// When a payment request failed without a definitive answer from the API (a network error or
+    /// an unavailable server), the payment may still have been created. If an idempotency key was
+    /// used, look it up so the caller gets the existing payment rather than an error that invites
+    /// a second attempt.
+    async fn recover_outgoing_payment(
+        &self,
+        error: Error,
+        idempotency_key: Option<&str>,
+    ) -> Result<OutgoingPayment, Error> {
+        let outcome_unknown = matches!(error, Error::ReqwestError(_)) || error.is_retryable();
+        match (outcome_unknown, idempotency_key) {
+            (true, Some(idempotency_key)) => {
+                match self
+                    .outgoing_payment_for_idempotency_key(idempotency_key)
+                    .await
+                {
+                    Ok(Some(payment)) => Ok(payment),
+                    _ => Err(error),
+                }
+            }
+            _ => Err(error),
+        }
+    }
+
+    /// Same as `recover_outgoing_payment`, for withdrawal requests.
+    async fn recover_withdrawal_request(
+        &self,
+        error: Error,
+        idempotency_key: Option<&str>,
+    ) -> Result<WithdrawalRequest, Error> {
+        let outcome_unknown = matches!(error, Error::ReqwestError(_)) || error.is_retryable();
+        match (outcome_unknown, idempotency_key) {
+            (true, Some(idempotency_key)) => {
+                match self
+                    .withdrawal_request_for_idempotency_key(idempotency_key)
+                    .await
+                {
+                    Ok(Some(request)) => Ok(request),
+                    _ => Err(error),
+                }
+            }
+            _ => Err(error),
+        }
+    }
+
     pub fn hash_uma_identifier(
         identifier: &str,
         signing_private_key: &[u8],
@@ -1325,11 +1962,84 @@ impl<K: OperationSigningKey> LightsparkClient<K> {
     }
 }
 
+fn is_terminal_transaction_status(status: &TransactionStatus) -> bool {
+    matches!(
+        status,
+        TransactionStatus::Success
+            | TransactionStatus::Failed
+            | TransactionStatus::Cancelled
+            | TransactionStatus::Expired
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::key::Secp256k1SigningKey;
+    use crate::error::ApiError;
+    use crate::key::{RSASigningKey, Secp256k1SigningKey};
+    use crate::testing::{fixtures, mock_requester::MockRequester};
+    use crate::types::graphql_requester::GraphQLRequester;
+    use async_trait::async_trait;
     use chrono::prelude::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Records the operations it receives and fails every signed one like a dropped connection.
+    #[derive(Default)]
+    struct StubRequester {
+        operations: Mutex<Vec<(String, bool)>>,
+    }
+
+    #[async_trait]
+    impl GraphQLRequester for StubRequester {
+        async fn execute_graphql(
+            &self,
+            operation: &str,
+            _variables: Option<Value>,
+        ) -> Result<Value, Error> {
+            self.operations
+                .lock()
+                .unwrap()
+                .push((operation.to_owned(), false));
+            Ok(json!({"outgoing_payment_for_idempotency_key": {"payment": null}}))
+        }
+    }
+
+    #[async_trait]
+    impl SigningGraphQLRequester for StubRequester {
+        async fn execute_graphql_signing<T: OperationSigningKey>(
+            &self,
+            operation: &str,
+            _variables: Option<Value>,
+            signing_key: Option<T>,
+        ) -> Result<Value, Error> {
+            self.operations
+                .lock()
+                .unwrap()
+                .push((operation.to_owned(), signing_key.is_some()));
+            Err(Error::ReqwestError("connection reset".to_owned()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_requester() {
+        let mut client =
+            LightsparkClient::<Secp256k1SigningKey, _>::with_requester(StubRequester::default());
+        client.load_node_signing_key("node", Secp256k1SigningKey::new(vec![1; 32]));
+
+        let result = client
+            .pay_invoice_with_idempotency_key("node", "lnbc1", 60, None, 1000, Some("key"))
+            .await;
+        assert!(matches!(result, Err(Error::ReqwestError(_))));
+
+        let operations = client.requester.operations.lock().unwrap();
+        assert_eq!(operations.len(), 2);
+        assert!(operations[0].0.contains("mutation PayInvoice"));
+        assert!(operations[0].1);
+        assert!(operations[1]
+            .0
+            .contains("query OutgoingPaymentForIdempotencyKey"));
+    }
 
     #[test]
     fn test_hash_uma_identifier() {
@@ -1356,4 +2066,151 @@ mod tests {
         );
         assert_ne!(hashed_uma, hashed_uma_diff_month);
     }
+
+    fn lookup_requester() -> MockRequester {
+        let outgoing_payment = fixtures::object("OutgoingPayment").unwrap();
+        let incoming_payment = fixtures::object("IncomingPayment").unwrap();
+        MockRequester::new()
+            .respond(
+                "OutgoingPaymentForIdempotencyKey",
+                json!({"outgoing_payment_for_idempotency_key": {"payment": outgoing_payment}}),
+            )
+            .respond(
+                "OutgoingPaymentsForInvoice",
+                json!({"outgoing_payments_for_invoice": {"payments": [outgoing_payment]}}),
+            )
+            .respond(
+                "OutgoingPaymentsForPaymentHash",
+                json!({"outgoing_payments_for_payment_hash": {"payments": []}}),
+            )
+            .respond(
+                "IncomingPaymentsForInvoice",
+                json!({"incoming_payments_for_invoice": {"payments": [incoming_payment]}}),
+            )
+            .respond(
+                "IncomingPaymentsForPaymentHash",
+                json!({"incoming_payments_for_payment_hash": {"payments": [incoming_payment]}}),
+            )
+            .respond(
+                "InvoiceForPaymentHash",
+                json!({"invoice_for_payment_hash": {"invoice": null}}),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_payment_lookups() {
+        let client = LightsparkClient::<RSASigningKey, _>::with_requester(lookup_requester());
+
+        let payment = client
+            .outgoing_payment_for_idempotency_key("key")
+            .await
+            .unwrap();
+        assert!(payment.is_some());
+        let payments = client
+            .outgoing_payments_for_invoice("lnbc1", Some(vec![TransactionStatus::Success]))
+            .await
+            .unwrap();
+        assert_eq!(payments.len(), 1);
+        let payments = client
+            .outgoing_payments_for_payment_hash("hash", None)
+            .await
+            .unwrap();
+        assert!(payments.is_empty());
+        let payments = client
+            .incoming_payments_for_invoice("invoice", None)
+            .await
+            .unwrap();
+        assert_eq!(payments.len(), 1);
+        let payments = client
+            .incoming_payments_for_payment_hash("hash", None)
+            .await
+            .unwrap();
+        assert_eq!(payments.len(), 1);
+        let invoice = client.invoice_for_payment_hash("hash").await.unwrap();
+        assert!(invoice.is_none());
+
+        let requests = client.requester.requests();
+        assert_eq!(requests.len(), 6);
+        assert_eq!(
+            requests[0].variables,
+            Some(json!({"idempotency_key": "key"}))
+        );
+        assert_eq!(
+            requests[1].variables,
+            Some(json!({"encoded_invoice": "lnbc1", "statuses": ["SUCCESS"]}))
+        );
+        assert_eq!(
+            requests[2].variables,
+            Some(json!({"payment_hash": "hash", "statuses": null}))
+        );
+        assert_eq!(
+            requests[3].variables,
+            Some(json!({"invoice_id": "invoice", "statuses": null}))
+        );
+        assert_eq!(requests[5].variables, Some(json!({"payment_hash": "hash"})));
+        assert!(requests.iter().all(|request| !request.signed));
+    }
+
+    fn payment_requester(status: &str) -> MockRequester {
+        let mut payment = fixtures::object("OutgoingPayment").unwrap();
+        payment["outgoing_payment_status"] = json!(status);
+        MockRequester::new().respond("GetEntity", json!({ "entity": payment }))
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_payment() {
+        let client =
+            LightsparkClient::<RSASigningKey, _>::with_requester(payment_requester("SUCCESS"));
+        let payment = client
+            .wait_for_payment("payment", Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(matches!(payment.status, TransactionStatus::Success));
+        assert_eq!(client.requester.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_payment_transient_errors() {
+        let requester = MockRequester::new().fail(
+            "GetEntity",
+            ApiError {
+                status: 503,
+                errors: vec![],
+            },
+        );
+        let client = LightsparkClient::<RSASigningKey, _>::with_requester(requester);
+        let error = client
+            .wait_for_payment("payment", Duration::from_millis(300))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout(_)));
+        assert!(client.requester.requests().len() >= 2);
+
+        let requester = MockRequester::new().fail(
+            "GetEntity",
+            ApiError {
+                status: 400,
+                errors: vec![],
+            },
+        );
+        let client = LightsparkClient::<RSASigningKey, _>::with_requester(requester);
+        let error = client
+            .wait_for_payment("payment", Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::ApiError(_)));
+        assert_eq!(client.requester.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_payment_timeout() {
+        let client =
+            LightsparkClient::<RSASigningKey, _>::with_requester(payment_requester("PENDING"));
+        let error = client
+            .wait_for_payment("payment", Duration::from_millis(300))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::Timeout(_)));
+        assert!(client.requester.requests().len() >= 2);
+    }
 }
@@ -13,6 +13,7 @@ use rsa::sha2::Sha256;
 use rsa::signature::{RandomizedSigner, SignatureEncoding};
 use rsa::{pkcs8::DecodePrivateKey, RsaPrivateKey};
 use serde_json::{json, Error, Value};
+use zeroize::Zeroizing;
 
 const KEY_LEN: usize = 32;
 const IV_LEN: usize = 12;
@@ -46,22 +47,24 @@ impl fmt::Display for CryptoError {
 
 impl std::error::Error for CryptoError {}
 
-fn derive_key(password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
-    let mut derived = vec![0u8; KEY_LEN];
+fn derive_key(password: &[u8], salt: &[u8], iterations: u32) -> Zeroizing<Vec<u8>> {
+    let mut derived = Zeroizing::new(vec![0u8; KEY_LEN]);
     pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut derived);
     derived
 }
 
+/// Derives the key and the iv from the password. Both are zeroized when dropped, as is the
+/// intermediate buffer they are split from.
 fn derive_key_iv(
     password: &[u8],
     salt: &[u8],
     iterations: u32,
     length: usize,
-) -> (Vec<u8>, Vec<u8>) {
-    let mut derived = vec![0u8; length];
+) -> (Zeroizing<Vec<u8>>, Zeroizing<Vec<u8>>) {
+    let mut derived = Zeroizing::new(vec![0u8; length]);
     pbkdf2_hmac::<Sha256>(password, salt, iterations, &mut derived);
     let (key, iv) = derived.split_at(KEY_LEN);
-    (key.to_vec(), iv.to_vec())
+    (Zeroizing::new(key.to_vec()), Zeroizing::new(iv.to_vec()))
 }
 
 pub fn encrypt(plaintext: &[u8], password: &str) -> Result<(String, String), CryptoError> {
@@ -84,9 +87,9 @@ pub fn encrypt(plaintext: &[u8], password: &str) -> Result<(String, String), Cry
 type Aes256CbcDec = cbc::Decryptor<Aes256>;
 
 fn decrypt_cbc(ciphertext: &[u8], key: &[u8], iv: &[u8]) -> Result<Vec<u8>, CryptoError> {
-    let mut buf = [0u8; 48];
+    let mut buf = Zeroizing::new([0u8; 48]);
     let pt = Aes256CbcDec::new(key.into(), iv.into())
-        .decrypt_padded_b2b_mut::<Pkcs7>(ciphertext, &mut buf)
+        .decrypt_padded_b2b_mut::<Pkcs7>(ciphertext, buf.as_mut())
         .map_err(|_| CryptoError::DecryptionError)?;
     Ok(pt.to_vec())
 }
@@ -0,0 +1,469 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+//! Arithmetic, conversion and formatting for `CurrencyAmount`.
+//!
+//! Amounts in bitcoin denominated units (`Bitcoin`, `Millibitcoin`, `Microbitcoin`, `Satoshi`,
+//! `Nanobitcoin` and `Millisatoshi`) can be added, subtracted, compared and converted exactly.
+//! Amounts in fiat units (`Usd` and `Mxn`) are expressed in cents and can only be converted to
+//! and from bitcoin with an `ExchangeRate`.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::mem::discriminant;
+
+use crate::error::Error;
+use crate::objects::currency_amount::CurrencyAmount;
+use crate::objects::currency_unit::CurrencyUnit;
+
+const MILLISATOSHIS_PER_BITCOIN: i64 = 100_000_000_000;
+
+impl CurrencyUnit {
+    /// The number of millisatoshis in one unit, or `None` for fiat units.
+    pub fn millisatoshis_per_unit(&self) -> Option<i64> {
+        match self {
+            Self::Bitcoin => Some(MILLISATOSHIS_PER_BITCOIN),
+            Self::Millibitcoin => Some(100_000_000),
+            Self::Microbitcoin => Some(100_000),
+            Self::Satoshi => Some(1000),
+            Self::Nanobitcoin => Some(100),
+            Self::Millisatoshi => Some(1),
+            Self::Usd | Self::Mxn => None,
+        }
+    }
+
+    /// Whether the unit is a denomination of bitcoin rather than a fiat currency.
+    pub fn is_bitcoin_denominated(&self) -> bool {
+        self.millisatoshis_per_unit().is_some()
+    }
+
+    /// The symbol used when formatting amounts in this unit.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Bitcoin => "BTC",
+            Self::Millibitcoin => "mBTC",
+            Self::Microbitcoin => "μBTC",
+            Self::Satoshi => "sat",
+            Self::Nanobitcoin => "nBTC",
+            Self::Millisatoshi => "msat",
+            Self::Usd => "$",
+            Self::Mxn => "MX$",
+        }
+    }
+
+    fn is_same(&self, other: &CurrencyUnit) -> bool {
+        discriminant(self) == discriminant(other)
+    }
+}
+
+/// The price of bitcoin in a fiat currency, used to convert amounts between `Usd` or `Mxn` and
+/// bitcoin denominated units.
+#[derive(Debug, Clone)]
+pub struct ExchangeRate {
+    /// The fiat unit the rate applies to.
+    pub unit: CurrencyUnit,
+
+    /// The number of millisatoshis worth one cent of `unit`.
+    pub millisatoshis_per_cent: f64,
+}
+
+impl ExchangeRate {
+    /// Creates a rate from the number of millisatoshis worth one cent of the given fiat unit.
+    pub fn new(unit: CurrencyUnit, millisatoshis_per_cent: f64) -> Result<Self, Error> {
+        if unit.is_bitcoin_denominated()
+            || !millisatoshis_per_cent.is_finite()
+            || millisatoshis_per_cent <= 0.0
+        {
+            return Err(Error::InvalidCurrencyConversion);
+        }
+        Ok(Self {
+            unit,
+            millisatoshis_per_cent,
+        })
+    }
+
+    /// Creates a rate from the price of one bitcoin in cents of the given fiat unit.
+    pub fn from_bitcoin_price(unit: CurrencyUnit, cents_per_bitcoin: f64) -> Result<Self, Error> {
+        Self::new(unit, MILLISATOSHIS_PER_BITCOIN as f64 / cents_per_bitcoin)
+    }
+}
+
+impl CurrencyAmount {
+    /// Creates an amount of the given value and unit, which is also its preferred unit.
+    pub fn new(value: i64, unit: CurrencyUnit) -> Self {
+        Self {
+            original_value: value,
+            original_unit: unit.clone(),
+            preferred_currency_unit: unit,
+            preferred_currency_value_rounded: value,
+            preferred_currency_value_approx: value as f64,
+        }
+    }
+
+    /// Creates an amount of the given number of millisatoshis.
+    pub fn from_millisatoshis(value: i64) -> Self {
+        Self::new(value, CurrencyUnit::Millisatoshi)
+    }
+
+    /// Creates an amount of the given number of satoshis.
+    pub fn from_satoshis(value: i64) -> Self {
+        Self::new(value, CurrencyUnit::Satoshi)
+    }
+
+    /// The amount in millisatoshis. Fails with `InvalidCurrencyConversion` for fiat amounts or
+    /// if the result does not fit in an `i64`.
+    pub fn to_millisatoshis(&self) -> Result<i64, Error> {
+        self.original_unit
+            .millisatoshis_per_unit()
+            .and_then(|factor| self.original_value.checked_mul(factor))
+            .ok_or(Error::InvalidCurrencyConversion)
+    }
+
+    /// The amount in satoshis, rounded down. Fails like `to_millisatoshis`.
+    pub fn to_satoshis(&self) -> Result<i64, Error> {
+        Ok(self.to_millisatoshis()?.div_euclid(1000))
+    }
+
+    /// Converts the amount to another bitcoin denominated unit. Fails with
+    /// `InvalidCurrencyConversion` if either unit is a fiat unit or if the amount cannot be
+    /// represented exactly in the target unit, e.g. 1500 msat in satoshis.
+    pub fn convert_to(&self, unit: CurrencyUnit) -> Result<CurrencyAmount, Error> {
+        let millisatoshis = self.to_millisatoshis()?;
+        let factor = unit
+            .millisatoshis_per_unit()
+            .ok_or(Error::InvalidCurrencyConversion)?;
+        if millisatoshis % factor != 0 {
+            return Err(Error::InvalidCurrencyConversion);
+        }
+        Ok(CurrencyAmount {
+            original_value: millisatoshis / factor,
+            original_unit: unit,
+            ..self.clone()
+        })
+    }
+
+    /// Converts the amount between a fiat unit and a bitcoin denominated unit with the given
+    /// rate, rounding to the nearest unit of the target. Amounts already in the target unit are
+    /// returned as is and bitcoin denominated amounts are converted exactly as by `convert_to`.
+    pub fn convert_with_rate(
+        &self,
+        unit: CurrencyUnit,
+        rate: &ExchangeRate,
+    ) -> Result<CurrencyAmount, Error> {
+        if self.original_unit.is_same(&unit) {
+            return Ok(self.clone());
+        }
+        let value = match (
+            self.original_unit.millisatoshis_per_unit(),
+            unit.millisatoshis_per_unit(),
+        ) {
+            (Some(_), Some(_)) => return self.convert_to(unit),
+            (Some(_), None) if unit.is_same(&rate.unit) => {
+                self.to_millisatoshis()? as f64 / rate.millisatoshis_per_cent
+            }
+            (None, Some(factor)) if self.original_unit.is_same(&rate.unit) => {
+                self.original_value as f64 * rate.millisatoshis_per_cent / factor as f64
+            }
+            _ => return Err(Error::InvalidCurrencyConversion),
+        };
+        let value = value.round();
+        if !(i64::MIN as f64..i64::MAX as f64).contains(&value) {
+            return Err(Error::InvalidCurrencyConversion);
+        }
+        Ok(CurrencyAmount {
+            original_value: value as i64,
+            original_unit: unit,
+            ..self.clone()
+        })
+    }
+
+    /// Adds two bitcoin denominated amounts. The result is expressed in the finer of the two
+    /// units so that no precision is lost. Fails with `InvalidCurrencyConversion` for fiat
+    /// amounts or on overflow.
+    pub fn checked_add(&self, other: &CurrencyAmount) -> Result<CurrencyAmount, Error> {
+        self.combine(other, i64::checked_add, |a, b| a + b)
+    }
+
+    /// Subtracts `other` from this amount, like `checked_add`.
+    pub fn checked_sub(&self, other: &CurrencyAmount) -> Result<CurrencyAmount, Error> {
+        self.combine(other, i64::checked_sub, |a, b| a - b)
+    }
+
+    /// Compares two bitcoin denominated amounts regardless of their units. Fails with
+    /// `InvalidCurrencyConversion` for fiat amounts.
+    pub fn checked_cmp(&self, other: &CurrencyAmount) -> Result<Ordering, Error> {
+        Ok(self.to_millisatoshis()?.cmp(&other.to_millisatoshis()?))
+    }
+
+    fn combine(
+        &self,
+        other: &CurrencyAmount,
+        op: fn(i64, i64) -> Option<i64>,
+        approx_op: fn(f64, f64) -> f64,
+    ) -> Result<CurrencyAmount, Error> {
+        let millisatoshis = op(self.to_millisatoshis()?, other.to_millisatoshis()?)
+            .ok_or(Error::InvalidCurrencyConversion)?;
+        let unit = if self.original_unit.millisatoshis_per_unit()
+            <= other.original_unit.millisatoshis_per_unit()
+        {
+            self.original_unit.clone()
+        } else {
+            other.original_unit.clone()
+        };
+        let factor = unit
+            .millisatoshis_per_unit()
+            .ok_or(Error::InvalidCurrencyConversion)?;
+
+        let mut result = CurrencyAmount::new(millisatoshis / factor, unit);
+        if self
+            .preferred_currency_unit
+            .is_same(&other.preferred_currency_unit)
+        {
+            result.preferred_currency_unit = self.preferred_currency_unit.clone();
+            result.preferred_currency_value_rounded = op(
+                self.preferred_currency_value_rounded,
+                other.preferred_currency_value_rounded,
+            )
+            .ok_or(Error::InvalidCurrencyConversion)?;
+            result.preferred_currency_value_approx = approx_op(
+                self.preferred_currency_value_approx,
+                other.preferred_currency_value_approx,
+            );
+        }
+        Ok(result)
+    }
+}
+
+/// Formats the original value with its unit symbol, e.g. `1500 sat`, `2 BTC` or `$12.34` for an
+/// amount of 1234 cents.
+impl fmt::Display for CurrencyAmount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let value = self.original_value;
+        if self.original_unit.is_bitcoin_denominated() {
+            return write!(f, "{} {}", value, self.original_unit.symbol());
+        }
+        let sign = if value < 0 { "-" } else { "" };
+        let cents = value.unsigned_abs();
+        write!(
+            f,
+            "{}{}{}.{:02}",
+            sign,
+            self.original_unit.symbol(),
+            cents / 100,
+            cents % 100
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNITS: [CurrencyUnit; 6] = [
+        CurrencyUnit::Bitcoin,
+        CurrencyUnit::Millibitcoin,
+        CurrencyUnit::Microbitcoin,
+        CurrencyUnit::Satoshi,
+        CurrencyUnit::Nanobitcoin,
+        CurrencyUnit::Millisatoshi,
+    ];
+
+    #[test]
+    fn test_to_millisatoshis() {
+        let expected = [100_000_000_000, 100_000_000, 100_000, 1000, 100, 1];
+        for (unit, expected) in UNITS.iter().zip(expected) {
+            let amount = CurrencyAmount::new(3, unit.clone());
+            assert_eq!(amount.to_millisatoshis().unwrap(), 3 * expected, "{}", unit);
+            assert_eq!(unit.millisatoshis_per_unit(), Some(expected));
+        }
+        assert!(CurrencyAmount::new(1, CurrencyUnit::Usd)
+            .to_millisatoshis()
+            .is_err());
+        assert!(CurrencyAmount::new(1, CurrencyUnit::Mxn)
+            .to_millisatoshis()
+            .is_err());
+        assert!(CurrencyAmount::new(i64::MAX, CurrencyUnit::Bitcoin)
+            .to_millisatoshis()
+            .is_err());
+        assert_eq!(
+            CurrencyAmount::from_millisatoshis(-1500)
+                .to_satoshis()
+                .unwrap(),
+            -2
+        );
+    }
+
+    #[test]
+    fn test_convert_to() {
+        for from in UNITS.iter() {
+            for to in UNITS.iter() {
+                let amount = CurrencyAmount::new(7, from.clone());
+                let converted = amount.convert_to(to.clone());
+                let from_factor = from.millisatoshis_per_unit().unwrap();
+                let to_factor = to.millisatoshis_per_unit().unwrap();
+                if (7 * from_factor) % to_factor == 0 {
+                    let converted = converted.unwrap();
+                    assert_eq!(converted.original_value, 7 * from_factor / to_factor);
+                    assert!(converted.original_unit.is_same(to));
+                    assert_eq!(
+                        converted.checked_cmp(&amount).unwrap(),
+                        Ordering::Equal,
+                        "{} -> {}",
+                        from,
+                        to
+                    );
+                } else {
+                    assert!(converted.is_err(), "{} -> {}", from, to);
+                }
+            }
+        }
+        assert!(CurrencyAmount::from_satoshis(1)
+            .convert_to(CurrencyUnit::Usd)
+            .is_err());
+        assert!(CurrencyAmount::new(1, CurrencyUnit::Usd)
+            .convert_to(CurrencyUnit::Satoshi)
+            .is_err());
+    }
+
+    #[test]
+    fn test_checked_add_sub() {
+        let sum = CurrencyAmount::from_satoshis(2)
+            .checked_add(&CurrencyAmount::from_millisatoshis(500))
+            .unwrap();
+        assert_eq!(sum.original_value, 2500);
+        assert!(matches!(sum.original_unit, CurrencyUnit::Millisatoshi));
+
+        let sum = CurrencyAmount::new(1, CurrencyUnit::Bitcoin)
+            .checked_add(&CurrencyAmount::new(1, CurrencyUnit::Millibitcoin))
+            .unwrap();
+        assert_eq!(sum.original_value, 1001);
+        assert!(matches!(sum.original_unit, CurrencyUnit::Millibitcoin));
+
+        let difference = CurrencyAmount::from_satoshis(1)
+            .checked_sub(&CurrencyAmount::new(3, CurrencyUnit::Nanobitcoin))
+            .unwrap();
+        assert_eq!(difference.original_value, 7);
+        assert!(matches!(
+            difference.original_unit,
+            CurrencyUnit::Nanobitcoin
+        ));
+        assert_eq!(difference.preferred_currency_value_rounded, 7);
+
+        assert!(CurrencyAmount::from_millisatoshis(i64::MAX)
+            .checked_add(&CurrencyAmount::from_millisatoshis(1))
+            .is_err());
+        assert!(CurrencyAmount::from_millisatoshis(i64::MIN)
+            .checked_sub(&CurrencyAmount::from_millisatoshis(1))
+            .is_err());
+        assert!(CurrencyAmount::from_satoshis(1)
+            .checked_add(&CurrencyAmount::new(1, CurrencyUnit::Usd))
+            .is_err());
+    }
+
+    #[test]
+    fn test_preferred_values() {
+        let mut a = CurrencyAmount::from_satoshis(1000);
+        a.preferred_currency_unit = CurrencyUnit::Usd;
+        a.preferred_currency_value_rounded = 60;
+        a.preferred_currency_value_approx = 60.2;
+        let mut b = a.clone();
+        b.preferred_currency_value_rounded = 30;
+        b.preferred_currency_value_approx = 30.1;
+
+        let sum = a.checked_add(&b).unwrap();
+        assert!(matches!(sum.preferred_currency_unit, CurrencyUnit::Usd));
+        assert_eq!(sum.preferred_currency_value_rounded, 90);
+        assert!((sum.preferred_currency_value_approx - 90.3).abs() < 1e-9);
+
+        let sum = a.checked_add(&CurrencyAmount::from_satoshis(1)).unwrap();
+        assert!(matches!(sum.preferred_currency_unit, CurrencyUnit::Satoshi));
+        assert_eq!(sum.preferred_currency_value_rounded, 1001);
+
+        let converted = a.convert_to(CurrencyUnit::Millisatoshi).unwrap();
+        assert_eq!(converted.original_value, 1_000_000);
+        assert_eq!(converted.preferred_currency_value_rounded, 60);
+    }
+
+    #[test]
+    fn test_checked_cmp() {
+        let one_sat = CurrencyAmount::from_satoshis(1);
+        assert_eq!(
+            one_sat
+                .checked_cmp(&CurrencyAmount::from_millisatoshis(999))
+                .unwrap(),
+            Ordering::Greater
+        );
+        assert_eq!(
+            one_sat
+                .checked_cmp(&CurrencyAmount::new(10, CurrencyUnit::Nanobitcoin))
+                .unwrap(),
+            Ordering::Equal
+        );
+        assert_eq!(
+            one_sat
+                .checked_cmp(&CurrencyAmount::new(1, CurrencyUnit::Microbitcoin))
+                .unwrap(),
+            Ordering::Less
+        );
+        assert!(one_sat
+            .checked_cmp(&CurrencyAmount::new(1, CurrencyUnit::Mxn))
+            .is_err());
+    }
+
+    #[test]
+    fn test_convert_with_rate() {
+        // 1 BTC = $50,000.00, i.e. 5,000,000 cents.
+        let rate = ExchangeRate::from_bitcoin_price(CurrencyUnit::Usd, 5_000_000.0).unwrap();
+        assert_eq!(rate.millisatoshis_per_cent, 20_000.0);
+
+        let usd = CurrencyAmount::new(1234, CurrencyUnit::Usd);
+        let sats = usd.convert_with_rate(CurrencyUnit::Satoshi, &rate).unwrap();
+        assert_eq!(sats.original_value, 24_680);
+        assert!(matches!(sats.original_unit, CurrencyUnit::Satoshi));
+
+        let back = sats.convert_with_rate(CurrencyUnit::Usd, &rate).unwrap();
+        assert_eq!(back.original_value, 1234);
+
+        let rounded = CurrencyAmount::from_millisatoshis(29_999)
+            .convert_with_rate(CurrencyUnit::Usd, &rate)
+            .unwrap();
+        assert_eq!(rounded.original_value, 1);
+
+        let msats = CurrencyAmount::new(3, CurrencyUnit::Millibitcoin)
+            .convert_with_rate(CurrencyUnit::Millisatoshi, &rate)
+            .unwrap();
+        assert_eq!(msats.original_value, 300_000_000);
+
+        assert!(usd.convert_with_rate(CurrencyUnit::Mxn, &rate).is_err());
+        assert!(CurrencyAmount::new(1, CurrencyUnit::Mxn)
+            .convert_with_rate(CurrencyUnit::Satoshi, &rate)
+            .is_err());
+        assert!(CurrencyAmount::new(i64::MAX, CurrencyUnit::Usd)
+            .convert_with_rate(CurrencyUnit::Millisatoshi, &rate)
+            .is_err());
+
+        assert!(ExchangeRate::new(CurrencyUnit::Satoshi, 1.0).is_err());
+        assert!(ExchangeRate::new(CurrencyUnit::Mxn, 0.0).is_err());
+        assert!(ExchangeRate::new(CurrencyUnit::Mxn, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(CurrencyAmount::from_satoshis(1500).to_string(), "1500 sat");
+        assert_eq!(
+            CurrencyAmount::new(2, CurrencyUnit::Bitcoin).to_string(),
+            "2 BTC"
+        );
+        assert_eq!(
+            CurrencyAmount::new(-7, CurrencyUnit::Microbitcoin).to_string(),
+            "-7 μBTC"
+        );
+        assert_eq!(
+            CurrencyAmount::new(1234, CurrencyUnit::Usd).to_string(),
+            "$12.34"
+        );
+        assert_eq!(
+            CurrencyAmount::new(-5, CurrencyUnit::Mxn).to_string(),
+            "-MX$0.05"
+        );
+    }
+}
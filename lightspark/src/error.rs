@@ -2,12 +2,16 @@
 
 use std::fmt;
 
+use serde::Deserialize;
+use serde_json::Value;
+
 use crate::crypto::CryptoError;
 
 #[derive(Debug)]
 pub enum Error {
     ReqwestError(String),
     GraphqlError(String),
+    ApiError(ApiError),
     InvalidHeaderValue,
     ClientCreationError(String),
     JsonError(serde_json::Error),
@@ -18,6 +22,9 @@ pub enum Error {
     InvalidCurrencyConversion,
     InvalidPhoneNumber,
     InvalidArgumentError(String),
+    Timeout(String),
+    #[cfg(feature = "objects")]
+    InvoiceError(crate::bolt11::InvoiceError),
 }
 
 impl fmt::Display for Error {
@@ -25,6 +32,7 @@ impl fmt::Display for Error {
         match self {
             Self::ReqwestError(err) => write!(f, "Reqwest error {}", err),
             Self::GraphqlError(err) => write!(f, "Graphql error {}", err),
+            Self::ApiError(err) => write!(f, "API error {}", err),
             Self::InvalidHeaderValue => write!(f, "Invalid header value"),
             Self::ClientCreationError(err) => write!(f, "Client creation error {}", err),
             Self::JsonError(err) => write!(f, "JSON Parser error {}", err),
@@ -37,8 +45,183 @@ impl fmt::Display for Error {
             Self::InvalidCurrencyConversion => write!(f, "Invalid currency conversion"),
             Self::InvalidPhoneNumber => write!(f, "Invalid phone number. Must be E.164 format."),
             Self::InvalidArgumentError(err) => write!(f, "Invalid argument error {}", err),
+            Self::Timeout(err) => write!(f, "Timed out {}", err),
+            #[cfg(feature = "objects")]
+            Self::InvoiceError(err) => write!(f, "Invoice error {}", err),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+impl Error {
+    /// Whether the request may succeed if it is sent again later, e.g. the API was unavailable
+    /// or rate limited the request.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::ApiError(err) => {
+                err.status >= 500 || err.status == 429 || err.has_code(&["RateLimit"])
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the request was rejected because the credentials are invalid or lack permission.
+    pub fn is_auth_error(&self) -> bool {
+        match self {
+            Self::ApiError(err) => {
+                err.status == 401
+                    || err.status == 403
+                    || err.has_code(&[
+                        "Unauthenticated",
+                        "Unauthorized",
+                        "PermissionDenied",
+                        "Forbidden",
+                    ])
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the request was rejected because the node does not have enough funds.
+    pub fn is_insufficient_balance(&self) -> bool {
+        match self {
+            Self::ApiError(err) => {
+                err.has_code(&["InsufficientBalance", "InsufficientFunds"])
+                    || err.errors.iter().any(|e| {
+                        let message = e.message.to_lowercase();
+                        message.contains("insufficient balance")
+                            || message.contains("insufficient funds")
+                    })
+            }
+            _ => false,
+        }
+    }
+}
+
+/// An error response from the Lightspark API.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    /// The HTTP status of the response.
+    pub status: u16,
+
+    /// The graphql errors of the response. This is empty if the response was not a graphql
+    /// response, e.g. when a proxy rejected the request.
+    pub errors: Vec<GraphqlErrorDetail>,
+}
+
+impl ApiError {
+    /// Whether any of the errors has one of the given codes. Codes are compared ignoring case,
+    /// underscores and an `Error` suffix, so `RateLimit` matches both `RATE_LIMIT` and
+    /// `RateLimitError`.
+    pub fn has_code(&self, codes: &[&str]) -> bool {
+        fn normalize(code: &str) -> String {
+            let code = code.replace('_', "").to_lowercase();
+            code.strip_suffix("error").unwrap_or(&code).to_owned()
+        }
+        self.errors
+            .iter()
+            .filter_map(|err| err.code())
+            .map(normalize)
+            .any(|code| codes.iter().any(|c| normalize(c) == code))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(status {})", self.status)?;
+        for err in &self.errors {
+            write!(f, " {}", err.message)?;
+            if let Some(code) = err.code() {
+                write!(f, " [{}]", code)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A single entry of the `errors` field of a graphql response.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphqlErrorDetail {
+    pub message: String,
+
+    /// The path of the response field that failed, if any.
+    pub path: Option<Vec<Value>>,
+
+    pub extensions: Option<Value>,
+}
+
+impl GraphqlErrorDetail {
+    /// The machine readable error code found in the extensions, if any.
+    pub fn code(&self) -> Option<&str> {
+        let extensions = self.extensions.as_ref()?;
+        extensions
+            .get("error_name")
+            .or_else(|| extensions.get("code"))
+            .and_then(|code| code.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn api_error(status: u16, errors: Value) -> Error {
+        Error::ApiError(ApiError {
+            status,
+            errors: serde_json::from_value(errors).unwrap(),
+        })
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(api_error(503, json!([])).is_retryable());
+        assert!(api_error(429, json!([])).is_retryable());
+        assert!(api_error(
+            200,
+            json!([{"message": "Slow down", "extensions": {"error_name": "RateLimitError"}}])
+        )
+        .is_retryable());
+        assert!(!api_error(400, json!([{"message": "Invalid input"}])).is_retryable());
+        assert!(!Error::SigningKeyNotFound.is_retryable());
+    }
+
+    #[test]
+    fn test_is_auth_error() {
+        assert!(api_error(401, json!([])).is_auth_error());
+        assert!(api_error(
+            200,
+            json!([{"message": "Nope", "extensions": {"code": "UNAUTHENTICATED"}}])
+        )
+        .is_auth_error());
+        assert!(!api_error(500, json!([])).is_auth_error());
+    }
+
+    #[test]
+    fn test_is_insufficient_balance() {
+        assert!(api_error(
+            200,
+            json!([{"message": "Payment failed", "path": ["pay_invoice"], "extensions": {"error_name": "InsufficientBalanceError"}}])
+        )
+        .is_insufficient_balance());
+        assert!(api_error(
+            200,
+            json!([{"message": "Node has insufficient balance to pay"}])
+        )
+        .is_insufficient_balance());
+        assert!(!api_error(200, json!([{"message": "Invalid invoice"}])).is_insufficient_balance());
+    }
+
+    #[test]
+    fn test_display() {
+        let error = api_error(
+            200,
+            json!([{"message": "Invalid invoice", "extensions": {"error_name": "InvalidInput"}}]),
+        );
+        assert_eq!(
+            error.to_string(),
+            "API error (status 200) Invalid invoice [InvalidInput]"
+        );
+    }
+}
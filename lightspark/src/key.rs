@@ -4,7 +4,7 @@ use serde_json::json;
 
 use crate::crypto::{self, CryptoError};
 
-pub trait OperationSigningKey: Clone {
+pub trait OperationSigningKey: Clone + Send + Sync {
     fn new(key_bytes: Vec<u8>) -> Self;
     fn sign_payload(&self, data: &[u8]) -> Result<String, CryptoError>;
 }
@@ -7,6 +7,8 @@
 //!
 //! After obtain an API token, a client can be initiate.
 //! ```
+//! # #[cfg(feature = "client")]
+//! # {
 //! use lightspark::request::auth_provider::AccountAuthProvider;
 //! use lightspark::client::LightsparkClient;
 //! use lightspark::key::RSASigningKey;
@@ -21,6 +23,7 @@
 //!       return;
 //!    }
 //! };
+//! # }
 //! ```
 //!
 //! You are now ready to use the Lightspark SDK!
@@ -30,18 +33,28 @@
 /// The version of this library.
 pub const VERSION: &str = "0.10.2";
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "objects")]
+pub mod bolt11;
 #[cfg(feature = "client")]
 pub mod client;
 #[cfg(feature = "base")]
 pub mod crypto;
+#[cfg(feature = "objects")]
+pub mod currency;
 #[cfg(feature = "base")]
 pub mod error;
 #[cfg(feature = "base")]
 pub mod key;
 #[cfg(feature = "objects")]
 pub mod objects;
+#[cfg(feature = "objects")]
+pub mod pagination;
 #[cfg(feature = "client")]
 pub mod request;
+#[cfg(all(feature = "client", any(test, feature = "testing")))]
+pub mod testing;
 #[cfg(feature = "base")]
 pub mod types;
 #[cfg(feature = "objects")]
@@ -0,0 +1,515 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+//! Streams over paginated connections.
+//!
+//! The `get_*` methods returning a connection only fetch one page, leaving it to the caller to
+//! pass the `end_cursor` of each page to the next call. The `stream_*` methods defined here do
+//! that instead and yield the entities of all pages one by one:
+//!
+//! ```no_run
+//! # #[cfg(feature = "client")]
+//! # async fn example(
+//! #     client: &lightspark::client::LightsparkClient<lightspark::key::RSASigningKey>,
+//! # ) -> Result<(), lightspark::error::Error> {
+//! use futures::TryStreamExt;
+//! use lightspark::pagination::PaginationOptions;
+//!
+//! let account = client.get_current_account().await?;
+//! let options = PaginationOptions::default().page_size(50).max_items(500);
+//! let transactions = account
+//!     .stream_transactions(&client.requester, options, None, None, None, None, None, None, None)
+//!     .try_collect::<Vec<_>>()
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::future::Future;
+
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
+
+use crate::error::Error;
+use crate::objects::account::Account;
+use crate::objects::account_to_api_tokens_connection::AccountToApiTokensConnection;
+use crate::objects::account_to_channels_connection::AccountToChannelsConnection;
+use crate::objects::account_to_nodes_connection::AccountToNodesConnection;
+use crate::objects::account_to_payment_requests_connection::AccountToPaymentRequestsConnection;
+use crate::objects::account_to_transactions_connection::AccountToTransactionsConnection;
+use crate::objects::account_to_wallets_connection::AccountToWalletsConnection;
+use crate::objects::account_to_withdrawal_requests_connection::AccountToWithdrawalRequestsConnection;
+use crate::objects::bitcoin_network::BitcoinNetwork;
+use crate::objects::channel::Channel;
+use crate::objects::connection::Connection;
+use crate::objects::incoming_payment_to_attempts_connection::IncomingPaymentToAttemptsConnection;
+use crate::objects::lightspark_node_to_channels_connection::LightsparkNodeToChannelsConnection;
+use crate::objects::outgoing_payment_attempt_to_hops_connection::OutgoingPaymentAttemptToHopsConnection;
+use crate::objects::outgoing_payment_to_attempts_connection::OutgoingPaymentToAttemptsConnection;
+use crate::objects::payment_request::PaymentRequestEnum;
+use crate::objects::transaction::TransactionEnum;
+use crate::objects::transaction_failures::TransactionFailures;
+use crate::objects::transaction_status::TransactionStatus;
+use crate::objects::transaction_type::TransactionType;
+use crate::objects::wallet::Wallet;
+use crate::objects::wallet_to_payment_requests_connection::WalletToPaymentRequestsConnection;
+use crate::objects::wallet_to_transactions_connection::WalletToTransactionsConnection;
+use crate::objects::wallet_to_withdrawal_requests_connection::WalletToWithdrawalRequestsConnection;
+use crate::objects::withdrawal_request::WithdrawalRequest;
+use crate::objects::withdrawal_request_status::WithdrawalRequestStatus;
+use crate::objects::withdrawal_request_to_channel_closing_transactions_connection::WithdrawalRequestToChannelClosingTransactionsConnection;
+use crate::objects::withdrawal_request_to_channel_opening_transactions_connection::WithdrawalRequestToChannelOpeningTransactionsConnection;
+use crate::types::graphql_requester::GraphQLRequester;
+
+/// A connection whose page of entities can be taken out of it.
+pub trait PaginatedConnection: Connection {
+    type Entity;
+
+    fn into_entities(self) -> Vec<Self::Entity>;
+}
+
+macro_rules! impl_paginated_connection {
+    ($($connection:ty => $entity:ty),* $(,)?) => {
+        $(
+            impl PaginatedConnection for $connection {
+                type Entity = $entity;
+
+                fn into_entities(self) -> Vec<Self::Entity> {
+                    self.entities
+                }
+            }
+        )*
+    };
+}
+
+impl_paginated_connection!(
+    AccountToApiTokensConnection => crate::objects::api_token::ApiToken,
+    AccountToChannelsConnection => Channel,
+    AccountToNodesConnection => crate::objects::lightspark_node::LightsparkNodeEnum,
+    AccountToPaymentRequestsConnection => PaymentRequestEnum,
+    AccountToTransactionsConnection => TransactionEnum,
+    AccountToWalletsConnection => Wallet,
+    AccountToWithdrawalRequestsConnection => WithdrawalRequest,
+    IncomingPaymentToAttemptsConnection => crate::objects::incoming_payment_attempt::IncomingPaymentAttempt,
+    LightsparkNodeToChannelsConnection => Channel,
+    OutgoingPaymentAttemptToHopsConnection => crate::objects::hop::Hop,
+    OutgoingPaymentToAttemptsConnection => crate::objects::outgoing_payment_attempt::OutgoingPaymentAttempt,
+    WalletToPaymentRequestsConnection => PaymentRequestEnum,
+    WalletToTransactionsConnection => TransactionEnum,
+    WalletToWithdrawalRequestsConnection => WithdrawalRequest,
+    WithdrawalRequestToChannelClosingTransactionsConnection => crate::objects::channel_closing_transaction::ChannelClosingTransaction,
+    WithdrawalRequestToChannelOpeningTransactionsConnection => crate::objects::channel_opening_transaction::ChannelOpeningTransaction,
+);
+
+/// Controls how a paginated stream fetches its pages.
+#[derive(Debug, Clone, Default)]
+pub struct PaginationOptions {
+    /// The number of entities requested per page. Defaults to the API's page size.
+    pub page_size: Option<i64>,
+
+    /// The maximum number of entities yielded by the stream. Defaults to all of them.
+    pub max_items: Option<usize>,
+}
+
+impl PaginationOptions {
+    pub fn page_size(mut self, page_size: i64) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+}
+
+struct PaginationState<F, E> {
+    fetch_page: F,
+    options: PaginationOptions,
+    entities: VecDeque<E>,
+    after: Option<String>,
+    done: bool,
+    yielded: usize,
+}
+
+/// Turns a function fetching one page of a connection into a stream of the entities of all pages.
+///
+/// `fetch_page` is called with the `first` and `after` arguments of the page to fetch. The stream
+/// ends after the last page, once `max_items` entities were yielded, or after yielding the first
+/// error. A page announcing a next page without making progress, i.e. an empty page or one whose
+/// end cursor is the cursor it was fetched with, fails the stream rather than fetching forever.
+pub fn paginate<C, F, Fut>(
+    options: PaginationOptions,
+    fetch_page: F,
+) -> impl Stream<Item = Result<C::Entity, Error>>
+where
+    C: PaginatedConnection,
+    F: FnMut(Option<i64>, Option<String>) -> Fut,
+    Fut: Future<Output = Result<C, Error>>,
+{
+    let state = PaginationState {
+        fetch_page,
+        options,
+        entities: VecDeque::new(),
+        after: None,
+        done: false,
+        yielded: 0,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            let remaining = state
+                .options
+                .max_items
+                .map(|max_items| max_items.saturating_sub(state.yielded));
+            if remaining == Some(0) {
+                return None;
+            }
+            if let Some(entity) = state.entities.pop_front() {
+                state.yielded += 1;
+                return Some((Ok(entity), state));
+            }
+            if state.done {
+                return None;
+            }
+
+            let first = match (state.options.page_size, remaining) {
+                (Some(page_size), Some(remaining)) => {
+                    Some(page_size.min(remaining.try_into().unwrap_or(i64::MAX)))
+                }
+                (page_size, _) => page_size,
+            };
+            match (state.fetch_page)(first, state.after.clone()).await {
+                Ok(connection) => {
+                    let page_info = connection.get_page_info();
+                    let stalled = page_info.end_cursor == state.after;
+                    state.after = page_info.end_cursor;
+                    state.done = !page_info.has_next_page.unwrap_or(false) || state.after.is_none();
+                    state.entities.extend(connection.into_entities());
+                    if !state.done && (stalled || state.entities.is_empty()) {
+                        state.done = true;
+                        state.entities.clear();
+                        return Some((
+                            Err(Error::GraphqlError(format!(
+                                "pagination did not advance past cursor {}",
+                                state.after.as_deref().unwrap_or_default()
+                            ))),
+                            state,
+                        ));
+                    }
+                }
+                Err(err) => {
+                    state.done = true;
+                    return Some((Err(err), state));
+                }
+            }
+        }
+    })
+}
+
+impl Account {
+    /// Streams the transactions of this account across all pages. See `get_transactions`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_transactions<'a>(
+        &'a self,
+        requester: &'a impl GraphQLRequester,
+        options: PaginationOptions,
+        types: Option<Vec<TransactionType>>,
+        after_date: Option<DateTime<Utc>>,
+        before_date: Option<DateTime<Utc>>,
+        bitcoin_network: Option<BitcoinNetwork>,
+        lightning_node_id: Option<String>,
+        statuses: Option<Vec<TransactionStatus>>,
+        exclude_failures: Option<TransactionFailures>,
+    ) -> impl Stream<Item = Result<TransactionEnum, Error>> + 'a {
+        paginate(options, move |first, after| {
+            self.get_transactions(
+                requester,
+                first,
+                after,
+                types.clone(),
+                after_date,
+                before_date,
+                bitcoin_network.clone(),
+                lightning_node_id.clone(),
+                statuses.clone(),
+                exclude_failures.clone(),
+            )
+        })
+    }
+
+    /// Streams the payment requests of this account across all pages. See
+    /// `get_payment_requests`.
+    pub fn stream_payment_requests<'a>(
+        &'a self,
+        requester: &'a impl GraphQLRequester,
+        options: PaginationOptions,
+        after_date: Option<DateTime<Utc>>,
+        before_date: Option<DateTime<Utc>>,
+        bitcoin_network: Option<BitcoinNetwork>,
+        lightning_node_id: Option<String>,
+    ) -> impl Stream<Item = Result<PaymentRequestEnum, Error>> + 'a {
+        paginate(options, move |first, after| {
+            self.get_payment_requests(
+                requester,
+                first,
+                after,
+                after_date,
+                before_date,
+                bitcoin_network.clone(),
+                lightning_node_id.clone(),
+            )
+        })
+    }
+
+    /// Streams the withdrawal requests of this account across all pages. See
+    /// `get_withdrawal_requests`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_withdrawal_requests<'a>(
+        &'a self,
+        requester: &'a impl GraphQLRequester,
+        options: PaginationOptions,
+        bitcoin_networks: Option<Vec<BitcoinNetwork>>,
+        statuses: Option<Vec<WithdrawalRequestStatus>>,
+        node_ids: Option<Vec<String>>,
+        idempotency_keys: Option<Vec<String>>,
+        after_date: Option<DateTime<Utc>>,
+        before_date: Option<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<WithdrawalRequest, Error>> + 'a {
+        paginate(options, move |first, after| {
+            self.get_withdrawal_requests(
+                requester,
+                first,
+                after,
+                bitcoin_networks.clone(),
+                statuses.clone(),
+                node_ids.clone(),
+                idempotency_keys.clone(),
+                after_date,
+                before_date,
+            )
+        })
+    }
+
+    /// Streams the channels of this account across all pages. See `get_channels`.
+    pub fn stream_channels<'a>(
+        &'a self,
+        requester: &'a impl GraphQLRequester,
+        options: PaginationOptions,
+        bitcoin_network: BitcoinNetwork,
+        lightning_node_id: Option<String>,
+        after_date: Option<DateTime<Utc>>,
+        before_date: Option<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<Channel, Error>> + 'a {
+        paginate(options, move |first, after| {
+            self.get_channels(
+                requester,
+                bitcoin_network.clone(),
+                lightning_node_id.clone(),
+                after_date,
+                before_date,
+                first,
+                after,
+            )
+        })
+    }
+}
+
+impl Wallet {
+    /// Streams the transactions of this wallet across all pages. See `get_transactions`.
+    pub fn stream_transactions<'a>(
+        &'a self,
+        requester: &'a impl GraphQLRequester,
+        options: PaginationOptions,
+        created_after_date: Option<DateTime<Utc>>,
+        created_before_date: Option<DateTime<Utc>>,
+        statuses: Option<Vec<TransactionStatus>>,
+        types: Option<Vec<TransactionType>>,
+    ) -> impl Stream<Item = Result<TransactionEnum, Error>> + 'a {
+        paginate(options, move |first, after| {
+            self.get_transactions(
+                requester,
+                first,
+                after,
+                created_after_date,
+                created_before_date,
+                statuses.clone(),
+                types.clone(),
+            )
+        })
+    }
+
+    /// Streams the payment requests of this wallet across all pages. See
+    /// `get_payment_requests`.
+    pub fn stream_payment_requests<'a>(
+        &'a self,
+        requester: &'a impl GraphQLRequester,
+        options: PaginationOptions,
+        created_after_date: Option<DateTime<Utc>>,
+        created_before_date: Option<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<PaymentRequestEnum, Error>> + 'a {
+        paginate(options, move |first, after| {
+            self.get_payment_requests(
+                requester,
+                first,
+                after,
+                created_after_date,
+                created_before_date,
+            )
+        })
+    }
+
+    /// Streams the withdrawal requests of this wallet across all pages. See
+    /// `get_withdrawal_requests`.
+    pub fn stream_withdrawal_requests<'a>(
+        &'a self,
+        requester: &'a impl GraphQLRequester,
+        options: PaginationOptions,
+        statuses: Option<Vec<WithdrawalRequestStatus>>,
+        created_after_date: Option<DateTime<Utc>>,
+        created_before_date: Option<DateTime<Utc>>,
+    ) -> impl Stream<Item = Result<WithdrawalRequest, Error>> + 'a {
+        paginate(options, move |first, after| {
+            self.get_withdrawal_requests(
+                requester,
+                first,
+                after,
+                statuses.clone(),
+                created_after_date,
+                created_before_date,
+            )
+        })
+    }
+}
+
+#[cfg(all(test, feature = "client"))]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use crate::testing::{fixtures, mock_requester::MockRequester};
+    use futures::{StreamExt, TryStreamExt};
+    use serde_json::{json, Value};
+
+    const OPERATION: &str = "FetchAccountToWithdrawalRequestsConnection";
+
+    fn account() -> Account {
+        serde_json::from_value(fixtures::object("Account").unwrap()).unwrap()
+    }
+
+    /// A page of withdrawal requests with the given ids, followed by the page at `next`.
+    fn page(ids: &[&str], next: Option<&str>) -> Value {
+        let mut connection = fixtures::object("AccountToWithdrawalRequestsConnection").unwrap();
+        let request = fixtures::object("WithdrawalRequest").unwrap();
+        connection["account_to_withdrawal_requests_connection_entities"] = ids
+            .iter()
+            .map(|id| {
+                let mut request = request.clone();
+                request["withdrawal_request_id"] = json!(id);
+                request
+            })
+            .collect();
+        connection["account_to_withdrawal_requests_connection_page_info"]
+            ["page_info_has_next_page"] = json!(next.is_some());
+        connection["account_to_withdrawal_requests_connection_page_info"]["page_info_end_cursor"] =
+            json!(next);
+        json!({"entity": {"withdrawal_requests": connection}})
+    }
+
+    fn requester() -> MockRequester {
+        MockRequester::new()
+            .respond(OPERATION, page(&["a", "b"], Some("page-2")))
+            .respond_with_variables(OPERATION, json!({"after": "page-2"}), page(&["c"], None))
+    }
+
+    async fn ids(
+        requester: &MockRequester,
+        options: PaginationOptions,
+    ) -> Result<Vec<String>, Error> {
+        let account = account();
+        let requests = account
+            .stream_withdrawal_requests(requester, options, None, None, None, None, None, None)
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(requests.into_iter().map(|request| request.id).collect())
+    }
+
+    #[tokio::test]
+    async fn test_streams_all_pages() {
+        let requester = requester();
+        let options = PaginationOptions::default().page_size(2);
+        assert_eq!(ids(&requester, options).await.unwrap(), ["a", "b", "c"]);
+
+        let requests = requester.requests();
+        assert_eq!(requests.len(), 2);
+        let variables = requests[1].variables.as_ref().unwrap();
+        assert_eq!(variables["first"], 2);
+        assert_eq!(variables["after"], "page-2");
+    }
+
+    #[tokio::test]
+    async fn test_max_items() {
+        let requester = requester();
+        let options = PaginationOptions::default().page_size(10).max_items(2);
+        assert_eq!(ids(&requester, options).await.unwrap(), ["a", "b"]);
+
+        let requests = requester.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].variables.as_ref().unwrap()["first"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_stops_at_first_error() {
+        let requester = requester().fail_with_variables(
+            OPERATION,
+            json!({"after": "page-2"}),
+            ApiError {
+                status: 503,
+                errors: vec![],
+            },
+        );
+        let account = account();
+        let results = account
+            .stream_withdrawal_requests(
+                &requester,
+                PaginationOptions::default(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(results.len(), 3);
+        assert!(results[2].as_ref().unwrap_err().is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_stops_when_the_cursor_does_not_advance() {
+        let requester = MockRequester::new()
+            .respond(OPERATION, page(&["a"], Some("page-2")))
+            .respond_with_variables(
+                OPERATION,
+                json!({"after": "page-2"}),
+                page(&["b"], Some("page-2")),
+            );
+        assert!(matches!(
+            ids(&requester, PaginationOptions::default()).await,
+            Err(Error::GraphqlError(_))
+        ));
+        assert_eq!(requester.requests().len(), 2);
+
+        let requester = MockRequester::new()
+            .respond(OPERATION, page(&["a"], Some("page-2")))
+            .respond_with_variables(
+                OPERATION,
+                json!({"after": "page-2"}),
+                page(&[], Some("page-3")),
+            );
+        assert!(matches!(
+            ids(&requester, PaginationOptions::default()).await,
+            Err(Error::GraphqlError(_))
+        ));
+        assert_eq!(requester.requests().len(), 2);
+    }
+}
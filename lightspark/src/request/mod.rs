@@ -2,3 +2,4 @@
 
 pub mod auth_provider;
 pub mod requester;
+pub mod retry;
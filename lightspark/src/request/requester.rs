@@ -1,12 +1,13 @@
 // Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
 
 use std::env;
+use std::time;
 
 use crate::{
-    error::Error,
+    error::{ApiError, Error},
     key::{OperationSigningKey, RSASigningKey},
-    request::auth_provider::AuthProvider,
-    types::graphql_requester::GraphQLRequester,
+    request::{auth_provider::AuthProvider, retry::RetryPolicy},
+    types::graphql_requester::{GraphQLRequester, SigningGraphQLRequester},
     VERSION,
 };
 use async_trait::async_trait;
@@ -25,6 +26,7 @@ const DEFAULT_BASE_URL: &str = "https://api.lightspark.com/graphql/server/2023-0
 pub struct Requester {
     client: reqwest::Client,
     base_url: Option<String>,
+    retry_policy: RetryPolicy,
 }
 
 fn user_agent() -> String {
@@ -33,7 +35,7 @@ fn user_agent() -> String {
         None => String::new(),
     };
     let os_version = match detect() {
-        Ok(version) => format!("/{}", version.to_string()),
+        Ok(version) => format!("/{}", version),
         Err(_) => String::new(),
     };
     format!(
@@ -65,19 +67,37 @@ impl GraphQLRequester for Requester {
     }
 }
 
-pub fn build_graphql_request_body(
-    operation: &str,
-    variables: Option<Value>,
-    has_signing_key: bool,
-) -> Result<Value, Error> {
+#[async_trait]
+impl SigningGraphQLRequester for Requester {
+    async fn execute_graphql_signing<K: OperationSigningKey>(
+        &self,
+        operation: &str,
+        variables: Option<Value>,
+        signing_key: Option<K>,
+    ) -> Result<Value, Error> {
+        Requester::execute_graphql_signing(self, operation, variables, signing_key).await
+    }
+}
+
+/// Extracts the name of a graphql query or mutation, e.g. `PayInvoice` for
+/// `mutation PayInvoice(...) { ... }`.
+pub(crate) fn operation_name(operation: &str) -> Result<Option<String>, Error> {
     let re = regex::Regex::new(r"\s*(?:query|mutation)\s+(\w+)").map_err(|_| {
         Error::GraphqlError("The operation is not a query or a mutation".to_owned())
     })?;
-    let operation_name = re
+    Ok(re
         .captures(operation)
         .and_then(|caps| caps.get(1))
         .map(|m| m.as_str())
-        .map(|s| s.to_owned());
+        .map(|s| s.to_owned()))
+}
+
+pub fn build_graphql_request_body(
+    operation: &str,
+    variables: Option<Value>,
+    has_signing_key: bool,
+) -> Result<Value, Error> {
+    let operation_name = operation_name(operation)?;
 
     let mut body = json!({
         "operationName": operation_name,
@@ -99,12 +119,88 @@ pub fn build_graphql_request_body(
     Ok(body)
 }
 
-impl Requester {
-    pub fn new<T: AuthProvider>(auth_provider: T) -> Result<Self, Error> {
+/// A builder for `Requester`, for when the default http client settings are not enough, e.g. to
+/// go through an egress proxy or to trust a private certificate authority.
+pub struct RequesterBuilder {
+    auth_token: String,
+    base_url: Option<String>,
+    connect_timeout: Option<time::Duration>,
+    read_timeout: Option<time::Duration>,
+    timeout: Option<time::Duration>,
+    proxy_url: Option<String>,
+    root_certificates_pem: Vec<Vec<u8>>,
+    user_agent_suffix: Option<String>,
+    retry_policy: RetryPolicy,
+}
+
+impl RequesterBuilder {
+    pub fn new<T: AuthProvider>(auth_provider: T) -> Self {
+        Self {
+            auth_token: auth_provider.auth_token(),
+            base_url: None,
+            connect_timeout: None,
+            read_timeout: None,
+            timeout: None,
+            proxy_url: None,
+            root_certificates_pem: vec![],
+            user_agent_suffix: None,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Sets the graphql endpoint. Defaults to the Lightspark API.
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.to_owned());
+        self
+    }
+
+    /// Sets the timeout for establishing a connection.
+    pub fn connect_timeout(mut self, timeout: time::Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for each read of the response.
+    pub fn read_timeout(mut self, timeout: time::Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for a whole request, from connecting until the response is read.
+    pub fn timeout(mut self, timeout: time::Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sends all requests through the given http or https proxy url.
+    pub fn proxy(mut self, proxy_url: &str) -> Self {
+        self.proxy_url = Some(proxy_url.to_owned());
+        self
+    }
+
+    /// Trusts the given PEM encoded root certificates in addition to the built-in ones.
+    pub fn add_root_certificate_pem(mut self, certificate_pem: &[u8]) -> Self {
+        self.root_certificates_pem.push(certificate_pem.to_vec());
+        self
+    }
+
+    /// Appends the given suffix to the `User-Agent` header.
+    pub fn user_agent_suffix(mut self, suffix: &str) -> Self {
+        self.user_agent_suffix = Some(suffix.to_owned());
+        self
+    }
+
+    /// Sets the policy used to retry failed requests.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn build(self) -> Result<Requester, Error> {
         let mut headers = reqwest::header::HeaderMap::new();
 
-        let auth_header_value = reqwest::header::HeaderValue::from_str(&auth_provider.auth_token())
-            .map_err(|err| {
+        let auth_header_value =
+            reqwest::header::HeaderValue::from_str(&self.auth_token).map_err(|err| {
                 Error::ClientCreationError(format!(
                     "Auth token cannot convert to HeaderValue: {}",
                     err
@@ -112,22 +208,61 @@ impl Requester {
             })?;
 
         headers.insert(reqwest::header::AUTHORIZATION, auth_header_value);
-        let user_agent = user_agent();
+        let sdk_user_agent = user_agent();
+        let sdk_user_agent_header_value = reqwest::header::HeaderValue::from_str(&sdk_user_agent)
+            .map_err(|err| {
+            Error::ClientCreationError(format!("User agent cannot convert to HeaderValue: {}", err))
+        })?;
+        let user_agent = match &self.user_agent_suffix {
+            Some(suffix) => format!("{} {}", sdk_user_agent, suffix),
+            None => sdk_user_agent,
+        };
         let user_agent_header_value =
             reqwest::header::HeaderValue::from_str(&user_agent).map_err(|err| {
                 Error::ClientCreationError(format!(
-                    "Auth token cannot convert to HeaderValue: {}",
+                    "User agent cannot convert to HeaderValue: {}",
                     err
                 ))
             })?;
-        headers.insert("User-Agent", user_agent_header_value.clone());
+        headers.insert("User-Agent", user_agent_header_value);
 
-        headers.insert("X-Lightspark-SDK", user_agent_header_value);
+        headers.insert("X-Lightspark-SDK", sdk_user_agent_header_value);
+
+        let mut builder = reqwest::Client::builder().default_headers(headers);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy_url) = &self.proxy_url {
+            let proxy = reqwest::Proxy::all(proxy_url)
+                .map_err(|err| Error::ClientCreationError(format!("Invalid proxy url: {}", err)))?;
+            builder = builder.proxy(proxy);
+        }
+        for certificate_pem in &self.root_certificates_pem {
+            let certificates =
+                reqwest::Certificate::from_pem_bundle(certificate_pem).map_err(|err| {
+                    Error::ClientCreationError(format!("Invalid root certificate: {}", err))
+                })?;
+            if certificates.is_empty() {
+                return Err(Error::ClientCreationError(
+                    "Invalid root certificate: no PEM certificate found".to_owned(),
+                ));
+            }
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
 
-        match reqwest::Client::builder().default_headers(headers).build() {
+        match builder.build() {
             Ok(client) => Ok(Requester {
                 client,
-                base_url: None,
+                base_url: self.base_url,
+                retry_policy: self.retry_policy,
             }),
             Err(err) => Err(Error::ClientCreationError(format!(
                 "reqwest client creation error: {}",
@@ -135,13 +270,27 @@ impl Requester {
             ))),
         }
     }
+}
+
+impl Requester {
+    pub fn new<T: AuthProvider>(auth_provider: T) -> Result<Self, Error> {
+        RequesterBuilder::new(auth_provider).build()
+    }
+
+    pub fn builder<T: AuthProvider>(auth_provider: T) -> RequesterBuilder {
+        RequesterBuilder::new(auth_provider)
+    }
 
     pub fn set_base_url(&mut self, base_url: Option<String>) {
         self.base_url = base_url;
     }
 
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
     /// This executes a graphql operaion. If the signing_key is provided, the operation will be
-    /// signed.
+    /// signed. Failed requests are retried according to the requester's `RetryPolicy`.
     ///
     /// Returns the json result for the operation.
     ///
@@ -156,6 +305,46 @@ impl Requester {
         variables: Option<Value>,
         signing_key: Option<T>,
     ) -> Result<Value, Error> {
+        let url = match &self.base_url {
+            Some(base_url) => base_url.clone(),
+            None => DEFAULT_BASE_URL.to_owned(),
+        };
+        let retryable = RetryPolicy::is_retryable_operation(operation, variables.as_ref());
+
+        let mut attempt = 1;
+        loop {
+            // The payload is rebuilt for every attempt so that signed requests get a fresh nonce.
+            let (headers, body) =
+                Self::build_request(operation, variables.clone(), signing_key.as_ref())?;
+            let result = self
+                .client
+                .post(url.as_str())
+                .headers(headers)
+                .body(body)
+                .send()
+                .await;
+
+            let should_retry = retryable
+                && attempt < self.retry_policy.max_attempts
+                && match &result {
+                    Ok(response) => RetryPolicy::should_retry_status(response.status()),
+                    Err(err) => RetryPolicy::should_retry_error(err),
+                };
+            if !should_retry {
+                let response = result.map_err(|e| Error::ReqwestError(e.to_string()))?;
+                return Self::parse_response(response).await;
+            }
+
+            tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    fn build_request<T: OperationSigningKey>(
+        operation: &str,
+        variables: Option<Value>,
+        signing_key: Option<&T>,
+    ) -> Result<(HeaderMap, Vec<u8>), Error> {
         let payload = build_graphql_request_body(operation, variables, signing_key.is_some())?;
 
         let mut headers = HeaderMap::new();
@@ -188,28 +377,42 @@ impl Requester {
             json_string.into_bytes()
         };
 
-        let url = match &self.base_url {
-            Some(base_url) => base_url.clone(),
-            None => DEFAULT_BASE_URL.to_owned(),
-        };
+        Ok((headers, body))
+    }
 
-        let response = self
-            .client
-            .post(url)
-            .headers(headers)
-            .body(body)
-            .send()
+    async fn parse_response(response: reqwest::Response) -> Result<Value, Error> {
+        let status = response.status();
+        let body = response
+            .bytes()
             .await
             .map_err(|e| Error::ReqwestError(e.to_string()))?;
 
-        let response_json: Value = response
-            .json()
-            .await
-            .map_err(|e| Error::ReqwestError(e.to_string()))?;
+        let response_json: Value = match serde_json::from_slice(&body) {
+            Ok(json) => json,
+            Err(err) if status.is_success() => return Err(Error::JsonError(err)),
+            // Not a graphql response, e.g. a proxy or load balancer error page.
+            Err(_) => {
+                return Err(Error::ApiError(ApiError {
+                    status: status.as_u16(),
+                    errors: vec![],
+                }))
+            }
+        };
 
-        if let Some(_errors) = response_json.get("errors") {
-            // Check if there are any errors in the response
-            Err(Error::GraphqlError(_errors.to_string()))
+        if let Some(errors) = response_json.get("errors") {
+            // Check if there are any errors in the response. Errors not following the graphql
+            // spec are passed on as is, so that the message of the server is not lost.
+            let errors = serde_json::from_value(errors.clone())
+                .map_err(|_| Error::GraphqlError(errors.to_string()))?;
+            Err(Error::ApiError(ApiError {
+                status: status.as_u16(),
+                errors,
+            }))
+        } else if !status.is_success() {
+            Err(Error::ApiError(ApiError {
+                status: status.as_u16(),
+                errors: vec![],
+            }))
         } else if let Some(data) = response_json.get("data") {
             // Return the data field of the response as json
             Ok(data.clone())
@@ -218,3 +421,294 @@ impl Requester {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::auth_provider::AccountAuthProvider;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    const SUCCESS_BODY: &str = r#"{"data": {"current_account": {"id": "account"}}}"#;
+    const QUERY: &str = "query GetCurrentAccount { current_account { id } }";
+
+    /// Starts a stand-in graphql server answering each connection with the next canned
+    /// `(status, body)` response, over TLS if `tls` is set. Returns its address and the heads of
+    /// the requests it received.
+    fn serve(
+        responses: Vec<(u16, &'static str)>,
+        tls: Option<Arc<rustls::ServerConfig>>,
+        delay: Duration,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("localhost:{}", listener.local_addr().unwrap().port());
+        let requests = Arc::new(Mutex::new(vec![]));
+        let received = requests.clone();
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let request = match &tls {
+                    Some(config) => {
+                        let connection = rustls::ServerConnection::new(config.clone()).unwrap();
+                        let stream = rustls::StreamOwned::new(connection, stream);
+                        respond(stream, status, body, delay)
+                    }
+                    None => respond(stream, status, body, delay),
+                };
+                received.lock().unwrap().push(request);
+            }
+        });
+        (address, requests)
+    }
+
+    fn respond<S: Read + Write>(stream: S, status: u16, body: &str, delay: Duration) -> String {
+        let mut reader = BufReader::new(stream);
+        let mut head = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            head.push_str(&line);
+        }
+        let mut request_body = vec![0u8; content_length];
+        reader.read_exact(&mut request_body).unwrap();
+
+        std::thread::sleep(delay);
+        let mut stream = reader.into_inner();
+        // The client may have given up already, in which case there is nobody to answer.
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} Stand-in\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+        .and_then(|_| stream.flush());
+        head
+    }
+
+    fn tls_config() -> Arc<rustls::ServerConfig> {
+        let certificate = include_bytes!("../../tests/fixtures/tls/localhost.der").to_vec();
+        let key = include_bytes!("../../tests/fixtures/tls/localhost.key.der").to_vec();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![certificate.into()],
+            rustls::pki_types::PrivatePkcs8KeyDer::from(key).into(),
+        )
+        .unwrap();
+        Arc::new(config)
+    }
+
+    fn builder(base_url: String, max_attempts: u32) -> RequesterBuilder {
+        let auth_provider = AccountAuthProvider::new("id".to_owned(), "token".to_owned());
+        Requester::builder(auth_provider)
+            .base_url(&base_url)
+            .retry_policy(RetryPolicy {
+                max_attempts,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(5),
+                jitter: true,
+            })
+    }
+
+    fn stand_in_requester(
+        responses: Vec<(u16, &'static str)>,
+        max_attempts: u32,
+    ) -> (Requester, Arc<Mutex<Vec<String>>>) {
+        let (address, requests) = serve(responses, None, Duration::ZERO);
+        let requester = builder(format!("http://{}", address), max_attempts)
+            .build()
+            .unwrap();
+        (requester, requests)
+    }
+
+    #[tokio::test]
+    async fn test_retries_server_errors() {
+        let (requester, requests) =
+            stand_in_requester(vec![(503, "{}"), (429, "{}"), (200, SUCCESS_BODY)], 3);
+
+        let result = requester.execute_graphql(QUERY, None).await.unwrap();
+        assert_eq!(result["current_account"]["id"], "account");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let (requester, requests) = stand_in_requester(vec![(500, "{}"), (500, "{}")], 2);
+
+        let result = requester.execute_graphql(QUERY, None).await;
+        assert!(result.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_client_errors() {
+        let (requester, requests) = stand_in_requester(vec![(400, "{}")], 3);
+
+        let result = requester.execute_graphql(QUERY, None).await;
+        assert!(result.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_mutation_without_idempotency_key() {
+        let (requester, requests) = stand_in_requester(vec![(503, "{}")], 3);
+
+        let result = requester
+            .execute_graphql(
+                "mutation CreateInvoice($node_id: ID!) { create_invoice { id } }",
+                Some(json!({"node_id": "node"})),
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retries_mutation_with_idempotency_key() {
+        let (requester, requests) = stand_in_requester(vec![(503, "{}"), (200, SUCCESS_BODY)], 3);
+
+        let result = requester
+            .execute_graphql(
+                "mutation PayInvoice($idempotency_key: String) { pay_invoice { id } }",
+                Some(json!({"idempotency_key": "key"})),
+            )
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_structured_errors() {
+        let (requester, _) = stand_in_requester(
+            vec![(
+                200,
+                r#"{"errors": [{"message": "Not enough funds", "path": ["pay_invoice"], "extensions": {"error_name": "InsufficientBalanceError"}}], "data": null}"#,
+            )],
+            1,
+        );
+
+        let error = requester.execute_graphql(QUERY, None).await.unwrap_err();
+        assert!(error.is_insufficient_balance());
+        assert!(!error.is_retryable());
+        match error {
+            Error::ApiError(err) => {
+                assert_eq!(err.status, 200);
+                assert_eq!(err.errors[0].message, "Not enough funds");
+                assert_eq!(err.errors[0].path, Some(vec![json!("pay_invoice")]));
+                assert_eq!(err.errors[0].code(), Some("InsufficientBalanceError"));
+            }
+            _ => panic!("unexpected error {}", error),
+        }
+
+        let (requester, _) = stand_in_requester(vec![(401, "Unauthorized")], 1);
+        let error = requester.execute_graphql(QUERY, None).await.unwrap_err();
+        assert!(error.is_auth_error());
+
+        let (requester, _) = stand_in_requester(
+            vec![(200, r#"{"errors": ["Something went wrong"], "data": null}"#)],
+            1,
+        );
+        match requester.execute_graphql(QUERY, None).await.unwrap_err() {
+            Error::GraphqlError(message) => assert!(message.contains("Something went wrong")),
+            error => panic!("unexpected error {}", error),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_root_certificate() {
+        let (address, requests) = serve(
+            vec![(200, SUCCESS_BODY)],
+            Some(tls_config()),
+            Duration::ZERO,
+        );
+        let requester = builder(format!("https://{}", address), 1)
+            .add_root_certificate_pem(include_bytes!("../../tests/fixtures/tls/ca.pem"))
+            .build()
+            .unwrap();
+
+        let result = requester.execute_graphql(QUERY, None).await.unwrap();
+        assert_eq!(result["current_account"]["id"], "account");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_certificate() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = tls_config();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let connection = rustls::ServerConnection::new(config).unwrap();
+            let mut stream: rustls::StreamOwned<_, TcpStream> =
+                rustls::StreamOwned::new(connection, stream);
+            // The handshake fails on the client side.
+            let _ = stream.read(&mut [0u8; 1]);
+        });
+        let requester = builder(format!("https://localhost:{}", port), 1)
+            .build()
+            .unwrap();
+
+        let result = requester.execute_graphql(QUERY, None).await;
+        assert!(matches!(result, Err(Error::ReqwestError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_proxy_and_user_agent_suffix() {
+        let (address, requests) = serve(vec![(200, SUCCESS_BODY)], None, Duration::ZERO);
+        let requester = builder("http://api.lightspark.invalid/graphql".to_owned(), 1)
+            .proxy(&format!("http://{}", address))
+            .user_agent_suffix("reconciliation/1.0")
+            .build()
+            .unwrap();
+
+        let result = requester.execute_graphql(QUERY, None).await.unwrap();
+        assert_eq!(result["current_account"]["id"], "account");
+
+        let requests = requests.lock().unwrap();
+        let head = requests[0].to_lowercase();
+        assert!(head.starts_with("post http://api.lightspark.invalid/graphql "));
+        assert!(head.contains(" reconciliation/1.0\r\n"));
+        assert!(head.contains("x-lightspark-sdk: lightspark-rs/"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let (address, _) = serve(vec![(200, SUCCESS_BODY)], None, Duration::from_millis(500));
+        let requester = builder(format!("http://{}", address), 1)
+            .timeout(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let result = requester.execute_graphql(QUERY, None).await;
+        assert!(matches!(result, Err(Error::ReqwestError(_))));
+    }
+
+    #[test]
+    fn test_invalid_builder_options() {
+        let auth_provider = AccountAuthProvider::new("id".to_owned(), "token".to_owned());
+        let result = Requester::builder(auth_provider)
+            .add_root_certificate_pem(b"not a certificate")
+            .build();
+        assert!(matches!(result, Err(Error::ClientCreationError(_))));
+
+        let auth_provider = AccountAuthProvider::new("id".to_owned(), "token".to_owned());
+        let result = Requester::builder(auth_provider).proxy("not a url").build();
+        assert!(matches!(result, Err(Error::ClientCreationError(_))));
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::StatusCode;
+use serde_json::Value;
+
+/// A policy describing how failed graphql requests are retried.
+///
+/// Only failures where the request most likely never reached the server, or where the server
+/// asked us to come back later, are retried: connection errors, 5xx and 429 responses. Mutations
+/// are only retried when they carry an `idempotency_key` variable, so a retry can never move
+/// funds twice.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+
+    /// The delay before the first retry. Each following retry doubles it.
+    pub initial_backoff: Duration,
+
+    /// The upper bound for the delay between two attempts.
+    pub max_backoff: Duration,
+
+    /// Whether each delay is randomized between zero and its computed value.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that sends every request exactly once.
+    pub fn no_retry() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// The delay to wait after the given failed attempt, starting at 1.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        if self.jitter && !delay.is_zero() {
+            rand::thread_rng().gen_range(Duration::ZERO..=delay)
+        } else {
+            delay
+        }
+    }
+
+    pub(crate) fn should_retry_status(status: StatusCode) -> bool {
+        status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    pub(crate) fn should_retry_error(error: &reqwest::Error) -> bool {
+        error.is_connect()
+    }
+
+    /// Queries can always be retried. Mutations only when the server can deduplicate them with
+    /// an idempotency key.
+    pub(crate) fn is_retryable_operation(operation: &str, variables: Option<&Value>) -> bool {
+        let is_mutation = operation.trim_start().starts_with("mutation");
+        if !is_mutation {
+            return true;
+        }
+        variables
+            .and_then(|vars| vars.get("idempotency_key"))
+            .is_some_and(|key| !key.is_null())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_backoff_without_jitter() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            jitter: false,
+        };
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), Duration::from_millis(500));
+        assert_eq!(policy.backoff(100), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_backoff_with_jitter() {
+        let policy = RetryPolicy::default();
+        for attempt in 1..10 {
+            assert!(policy.backoff(attempt) <= policy.max_backoff);
+        }
+    }
+
+    #[test]
+    fn test_is_retryable_operation() {
+        assert!(RetryPolicy::is_retryable_operation(
+            "query GetCurrentAccount { current_account { id } }",
+            None
+        ));
+        assert!(!RetryPolicy::is_retryable_operation(
+            "\n  mutation PayInvoice($node_id: ID!) { pay_invoice { id } }",
+            Some(&json!({"node_id": "node"}))
+        ));
+        assert!(!RetryPolicy::is_retryable_operation(
+            "mutation PayInvoice($idempotency_key: String) { pay_invoice { id } }",
+            Some(&json!({"idempotency_key": null}))
+        ));
+        assert!(RetryPolicy::is_retryable_operation(
+            "mutation PayInvoice($idempotency_key: String) { pay_invoice { id } }",
+            Some(&json!({"idempotency_key": "key"}))
+        ));
+    }
+}
@@ -0,0 +1,521 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use serde_json::Value;
+
+/// Sample responses for every object fragment, keyed by graphql typename. Each one holds every
+/// field of the object's `FRAGMENT`, aliased the same way, so it can be returned from a
+/// `MockRequester` wherever the real API would return that object.
+const OBJECTS: &[(&str, &str)] = &[
+    (
+        "Account",
+        include_str!("../../tests/fixtures/objects/Account.json"),
+    ),
+    (
+        "AccountToApiTokensConnection",
+        include_str!("../../tests/fixtures/objects/AccountToApiTokensConnection.json"),
+    ),
+    (
+        "AccountToChannelsConnection",
+        include_str!("../../tests/fixtures/objects/AccountToChannelsConnection.json"),
+    ),
+    (
+        "AccountToNodesConnection",
+        include_str!("../../tests/fixtures/objects/AccountToNodesConnection.json"),
+    ),
+    (
+        "AccountToPaymentRequestsConnection",
+        include_str!("../../tests/fixtures/objects/AccountToPaymentRequestsConnection.json"),
+    ),
+    (
+        "AccountToTransactionsConnection",
+        include_str!("../../tests/fixtures/objects/AccountToTransactionsConnection.json"),
+    ),
+    (
+        "AccountToWalletsConnection",
+        include_str!("../../tests/fixtures/objects/AccountToWalletsConnection.json"),
+    ),
+    (
+        "AccountToWithdrawalRequestsConnection",
+        include_str!("../../tests/fixtures/objects/AccountToWithdrawalRequestsConnection.json"),
+    ),
+    (
+        "ApiToken",
+        include_str!("../../tests/fixtures/objects/ApiToken.json"),
+    ),
+    (
+        "Balances",
+        include_str!("../../tests/fixtures/objects/Balances.json"),
+    ),
+    (
+        "BlockchainBalance",
+        include_str!("../../tests/fixtures/objects/BlockchainBalance.json"),
+    ),
+    (
+        "CancelInvoiceOutput",
+        include_str!("../../tests/fixtures/objects/CancelInvoiceOutput.json"),
+    ),
+    (
+        "Channel",
+        include_str!("../../tests/fixtures/objects/Channel.json"),
+    ),
+    (
+        "ChannelClosingTransaction",
+        include_str!("../../tests/fixtures/objects/ChannelClosingTransaction.json"),
+    ),
+    (
+        "ChannelFees",
+        include_str!("../../tests/fixtures/objects/ChannelFees.json"),
+    ),
+    (
+        "ChannelOpeningTransaction",
+        include_str!("../../tests/fixtures/objects/ChannelOpeningTransaction.json"),
+    ),
+    (
+        "ChannelSnapshot",
+        include_str!("../../tests/fixtures/objects/ChannelSnapshot.json"),
+    ),
+    (
+        "ChannelToTransactionsConnection",
+        include_str!("../../tests/fixtures/objects/ChannelToTransactionsConnection.json"),
+    ),
+    (
+        "ClaimUmaInvitationOutput",
+        include_str!("../../tests/fixtures/objects/ClaimUmaInvitationOutput.json"),
+    ),
+    (
+        "ClaimUmaInvitationWithIncentivesOutput",
+        include_str!("../../tests/fixtures/objects/ClaimUmaInvitationWithIncentivesOutput.json"),
+    ),
+    (
+        "CreateApiTokenOutput",
+        include_str!("../../tests/fixtures/objects/CreateApiTokenOutput.json"),
+    ),
+    (
+        "CreateInvitationWithIncentivesOutput",
+        include_str!("../../tests/fixtures/objects/CreateInvitationWithIncentivesOutput.json"),
+    ),
+    (
+        "CreateInvoiceOutput",
+        include_str!("../../tests/fixtures/objects/CreateInvoiceOutput.json"),
+    ),
+    (
+        "CreateNodeWalletAddressOutput",
+        include_str!("../../tests/fixtures/objects/CreateNodeWalletAddressOutput.json"),
+    ),
+    (
+        "CreateTestModeInvoiceOutput",
+        include_str!("../../tests/fixtures/objects/CreateTestModeInvoiceOutput.json"),
+    ),
+    (
+        "CreateTestModePaymentoutput",
+        include_str!("../../tests/fixtures/objects/CreateTestModePaymentoutput.json"),
+    ),
+    (
+        "CreateUmaInvitationOutput",
+        include_str!("../../tests/fixtures/objects/CreateUmaInvitationOutput.json"),
+    ),
+    (
+        "CurrencyAmount",
+        include_str!("../../tests/fixtures/objects/CurrencyAmount.json"),
+    ),
+    (
+        "DailyLiquidityForecast",
+        include_str!("../../tests/fixtures/objects/DailyLiquidityForecast.json"),
+    ),
+    (
+        "DeclineToSignMessagesOutput",
+        include_str!("../../tests/fixtures/objects/DeclineToSignMessagesOutput.json"),
+    ),
+    (
+        "DeleteApiTokenOutput",
+        include_str!("../../tests/fixtures/objects/DeleteApiTokenOutput.json"),
+    ),
+    (
+        "Deposit",
+        include_str!("../../tests/fixtures/objects/Deposit.json"),
+    ),
+    (
+        "FailHtlcsOutput",
+        include_str!("../../tests/fixtures/objects/FailHtlcsOutput.json"),
+    ),
+    (
+        "FeeEstimate",
+        include_str!("../../tests/fixtures/objects/FeeEstimate.json"),
+    ),
+    (
+        "FundNodeOutput",
+        include_str!("../../tests/fixtures/objects/FundNodeOutput.json"),
+    ),
+    (
+        "GraphNode",
+        include_str!("../../tests/fixtures/objects/GraphNode.json"),
+    ),
+    (
+        "Hop",
+        include_str!("../../tests/fixtures/objects/Hop.json"),
+    ),
+    (
+        "IncomingPayment",
+        include_str!("../../tests/fixtures/objects/IncomingPayment.json"),
+    ),
+    (
+        "IncomingPaymentAttempt",
+        include_str!("../../tests/fixtures/objects/IncomingPaymentAttempt.json"),
+    ),
+    (
+        "IncomingPaymentToAttemptsConnection",
+        include_str!("../../tests/fixtures/objects/IncomingPaymentToAttemptsConnection.json"),
+    ),
+    (
+        "IncomingPaymentsForInvoiceQueryOutput",
+        include_str!("../../tests/fixtures/objects/IncomingPaymentsForInvoiceQueryOutput.json"),
+    ),
+    (
+        "IncomingPaymentsForPaymentHashQueryOutput",
+        include_str!("../../tests/fixtures/objects/IncomingPaymentsForPaymentHashQueryOutput.json"),
+    ),
+    (
+        "Invoice",
+        include_str!("../../tests/fixtures/objects/Invoice.json"),
+    ),
+    (
+        "InvoiceData",
+        include_str!("../../tests/fixtures/objects/InvoiceData.json"),
+    ),
+    (
+        "InvoiceForPaymentHashOutput",
+        include_str!("../../tests/fixtures/objects/InvoiceForPaymentHashOutput.json"),
+    ),
+    (
+        "LightningFeeEstimateOutput",
+        include_str!("../../tests/fixtures/objects/LightningFeeEstimateOutput.json"),
+    ),
+    (
+        "LightsparkNodeToChannelsConnection",
+        include_str!("../../tests/fixtures/objects/LightsparkNodeToChannelsConnection.json"),
+    ),
+    (
+        "LightsparkNodeToDailyLiquidityForecastsConnection",
+        include_str!("../../tests/fixtures/objects/LightsparkNodeToDailyLiquidityForecastsConnection.json"),
+    ),
+    (
+        "LightsparkNodeWithOSK",
+        include_str!("../../tests/fixtures/objects/LightsparkNodeWithOSK.json"),
+    ),
+    (
+        "LightsparkNodeWithRemoteSigning",
+        include_str!("../../tests/fixtures/objects/LightsparkNodeWithRemoteSigning.json"),
+    ),
+    (
+        "MultiSigAddressValidationParameters",
+        include_str!("../../tests/fixtures/objects/MultiSigAddressValidationParameters.json"),
+    ),
+    (
+        "NodeAddress",
+        include_str!("../../tests/fixtures/objects/NodeAddress.json"),
+    ),
+    (
+        "NodeToAddressesConnection",
+        include_str!("../../tests/fixtures/objects/NodeToAddressesConnection.json"),
+    ),
+    (
+        "OutgoingPayment",
+        include_str!("../../tests/fixtures/objects/OutgoingPayment.json"),
+    ),
+    (
+        "OutgoingPaymentAttempt",
+        include_str!("../../tests/fixtures/objects/OutgoingPaymentAttempt.json"),
+    ),
+    (
+        "OutgoingPaymentAttemptToHopsConnection",
+        include_str!("../../tests/fixtures/objects/OutgoingPaymentAttemptToHopsConnection.json"),
+    ),
+    (
+        "OutgoingPaymentForIdempotencyKeyOutput",
+        include_str!("../../tests/fixtures/objects/OutgoingPaymentForIdempotencyKeyOutput.json"),
+    ),
+    (
+        "OutgoingPaymentToAttemptsConnection",
+        include_str!("../../tests/fixtures/objects/OutgoingPaymentToAttemptsConnection.json"),
+    ),
+    (
+        "OutgoingPaymentsForInvoiceQueryOutput",
+        include_str!("../../tests/fixtures/objects/OutgoingPaymentsForInvoiceQueryOutput.json"),
+    ),
+    (
+        "OutgoingPaymentsForPaymentHashQueryOutput",
+        include_str!("../../tests/fixtures/objects/OutgoingPaymentsForPaymentHashQueryOutput.json"),
+    ),
+    (
+        "PageInfo",
+        include_str!("../../tests/fixtures/objects/PageInfo.json"),
+    ),
+    (
+        "PayInvoiceOutput",
+        include_str!("../../tests/fixtures/objects/PayInvoiceOutput.json"),
+    ),
+    (
+        "PostTransactionData",
+        include_str!("../../tests/fixtures/objects/PostTransactionData.json"),
+    ),
+    (
+        "RegisterPaymentOutput",
+        include_str!("../../tests/fixtures/objects/RegisterPaymentOutput.json"),
+    ),
+    (
+        "ReleaseChannelPerCommitmentSecretOutput",
+        include_str!("../../tests/fixtures/objects/ReleaseChannelPerCommitmentSecretOutput.json"),
+    ),
+    (
+        "ReleasePaymentPreimageOutput",
+        include_str!("../../tests/fixtures/objects/ReleasePaymentPreimageOutput.json"),
+    ),
+    (
+        "RequestWithdrawalOutput",
+        include_str!("../../tests/fixtures/objects/RequestWithdrawalOutput.json"),
+    ),
+    (
+        "RichText",
+        include_str!("../../tests/fixtures/objects/RichText.json"),
+    ),
+    (
+        "RoutingTransaction",
+        include_str!("../../tests/fixtures/objects/RoutingTransaction.json"),
+    ),
+    (
+        "ScreenNodeOutput",
+        include_str!("../../tests/fixtures/objects/ScreenNodeOutput.json"),
+    ),
+    (
+        "Secret",
+        include_str!("../../tests/fixtures/objects/Secret.json"),
+    ),
+    (
+        "SendPaymentOutput",
+        include_str!("../../tests/fixtures/objects/SendPaymentOutput.json"),
+    ),
+    (
+        "SetInvoicePaymentHashOutput",
+        include_str!("../../tests/fixtures/objects/SetInvoicePaymentHashOutput.json"),
+    ),
+    (
+        "SignInvoiceOutput",
+        include_str!("../../tests/fixtures/objects/SignInvoiceOutput.json"),
+    ),
+    (
+        "SignMessagesOutput",
+        include_str!("../../tests/fixtures/objects/SignMessagesOutput.json"),
+    ),
+    (
+        "Signable",
+        include_str!("../../tests/fixtures/objects/Signable.json"),
+    ),
+    (
+        "SignablePayload",
+        include_str!("../../tests/fixtures/objects/SignablePayload.json"),
+    ),
+    (
+        "UmaInvitation",
+        include_str!("../../tests/fixtures/objects/UmaInvitation.json"),
+    ),
+    (
+        "UpdateChannelPerCommitmentPointOutput",
+        include_str!("../../tests/fixtures/objects/UpdateChannelPerCommitmentPointOutput.json"),
+    ),
+    (
+        "UpdateNodeSharedSecretOutput",
+        include_str!("../../tests/fixtures/objects/UpdateNodeSharedSecretOutput.json"),
+    ),
+    (
+        "Wallet",
+        include_str!("../../tests/fixtures/objects/Wallet.json"),
+    ),
+    (
+        "WalletToPaymentRequestsConnection",
+        include_str!("../../tests/fixtures/objects/WalletToPaymentRequestsConnection.json"),
+    ),
+    (
+        "WalletToTransactionsConnection",
+        include_str!("../../tests/fixtures/objects/WalletToTransactionsConnection.json"),
+    ),
+    (
+        "WalletToWithdrawalRequestsConnection",
+        include_str!("../../tests/fixtures/objects/WalletToWithdrawalRequestsConnection.json"),
+    ),
+    (
+        "Withdrawal",
+        include_str!("../../tests/fixtures/objects/Withdrawal.json"),
+    ),
+    (
+        "WithdrawalFeeEstimateOutput",
+        include_str!("../../tests/fixtures/objects/WithdrawalFeeEstimateOutput.json"),
+    ),
+    (
+        "WithdrawalRequest",
+        include_str!("../../tests/fixtures/objects/WithdrawalRequest.json"),
+    ),
+    (
+        "WithdrawalRequestToChannelClosingTransactionsConnection",
+        include_str!("../../tests/fixtures/objects/WithdrawalRequestToChannelClosingTransactionsConnection.json"),
+    ),
+    (
+        "WithdrawalRequestToChannelOpeningTransactionsConnection",
+        include_str!("../../tests/fixtures/objects/WithdrawalRequestToChannelOpeningTransactionsConnection.json"),
+    ),
+    (
+        "WithdrawalRequestToWithdrawalsConnection",
+        include_str!("../../tests/fixtures/objects/WithdrawalRequestToWithdrawalsConnection.json"),
+    ),
+];
+
+/// Returns the sample response for the object with the given graphql typename, e.g.
+/// `OutgoingPayment`.
+pub fn object(typename: &str) -> Option<Value> {
+    OBJECTS
+        .iter()
+        .find(|(name, _)| *name == typename)
+        .map(|(_, json)| serde_json::from_str(json).expect("fixtures are valid json"))
+}
+
+/// The typenames of all objects with a sample response.
+pub fn typenames() -> impl Iterator<Item = &'static str> {
+    OBJECTS.iter().map(|(name, _)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects;
+
+    /// The response keys selected at the top level of a fragment.
+    fn fragment_fields(fragment: &str) -> Vec<&str> {
+        fragment
+            .lines()
+            .filter(|line| line.starts_with("    ") && !line.starts_with("     "))
+            .filter(|line| !line.trim().starts_with('}'))
+            .map(|line| {
+                line.trim()
+                    .split(':')
+                    .next()
+                    .unwrap()
+                    .trim_end_matches(" {")
+            })
+            .collect()
+    }
+
+    macro_rules! check_fixtures {
+        ($($module:ident::$name:ident),* $(,)?) => {
+            $(
+                let fixture = object(stringify!($name)).expect(stringify!($name));
+                for field in fragment_fields(objects::$module::FRAGMENT) {
+                    assert!(
+                        fixture.get(field).is_some(),
+                        "{} is missing {}",
+                        stringify!($name),
+                        field
+                    );
+                }
+                if let Err(err) = serde_json::from_value::<objects::$module::$name>(fixture) {
+                    panic!("{} does not deserialize: {}", stringify!($name), err);
+                }
+            )*
+        };
+    }
+
+    #[test]
+    fn test_fixtures_match_fragments() {
+        assert_eq!(typenames().count(), 90);
+        check_fixtures!(
+            account::Account,
+            account_to_api_tokens_connection::AccountToApiTokensConnection,
+            account_to_channels_connection::AccountToChannelsConnection,
+            account_to_nodes_connection::AccountToNodesConnection,
+            account_to_payment_requests_connection::AccountToPaymentRequestsConnection,
+            account_to_transactions_connection::AccountToTransactionsConnection,
+            account_to_wallets_connection::AccountToWalletsConnection,
+            account_to_withdrawal_requests_connection::AccountToWithdrawalRequestsConnection,
+            api_token::ApiToken,
+            balances::Balances,
+            blockchain_balance::BlockchainBalance,
+            cancel_invoice_output::CancelInvoiceOutput,
+            channel::Channel,
+            channel_closing_transaction::ChannelClosingTransaction,
+            channel_fees::ChannelFees,
+            channel_opening_transaction::ChannelOpeningTransaction,
+            channel_snapshot::ChannelSnapshot,
+            channel_to_transactions_connection::ChannelToTransactionsConnection,
+            claim_uma_invitation_output::ClaimUmaInvitationOutput,
+            claim_uma_invitation_with_incentives_output::ClaimUmaInvitationWithIncentivesOutput,
+            create_api_token_output::CreateApiTokenOutput,
+            create_invitation_with_incentives_output::CreateInvitationWithIncentivesOutput,
+            create_invoice_output::CreateInvoiceOutput,
+            create_node_wallet_address_output::CreateNodeWalletAddressOutput,
+            create_test_mode_invoice_output::CreateTestModeInvoiceOutput,
+            create_test_mode_paymentoutput::CreateTestModePaymentoutput,
+            create_uma_invitation_output::CreateUmaInvitationOutput,
+            currency_amount::CurrencyAmount,
+            daily_liquidity_forecast::DailyLiquidityForecast,
+            decline_to_sign_messages_output::DeclineToSignMessagesOutput,
+            delete_api_token_output::DeleteApiTokenOutput,
+            deposit::Deposit,
+            fail_htlcs_output::FailHtlcsOutput,
+            fee_estimate::FeeEstimate,
+            fund_node_output::FundNodeOutput,
+            graph_node::GraphNode,
+            hop::Hop,
+            incoming_payment::IncomingPayment,
+            incoming_payment_attempt::IncomingPaymentAttempt,
+            incoming_payment_to_attempts_connection::IncomingPaymentToAttemptsConnection,
+            incoming_payments_for_invoice_query_output::IncomingPaymentsForInvoiceQueryOutput,
+            incoming_payments_for_payment_hash_query_output::IncomingPaymentsForPaymentHashQueryOutput,
+            invoice::Invoice,
+            invoice_data::InvoiceData,
+            invoice_for_payment_hash_output::InvoiceForPaymentHashOutput,
+            lightning_fee_estimate_output::LightningFeeEstimateOutput,
+            lightspark_node_to_channels_connection::LightsparkNodeToChannelsConnection,
+            lightspark_node_to_daily_liquidity_forecasts_connection::LightsparkNodeToDailyLiquidityForecastsConnection,
+            lightspark_node_with_o_s_k::LightsparkNodeWithOSK,
+            lightspark_node_with_remote_signing::LightsparkNodeWithRemoteSigning,
+            multi_sig_address_validation_parameters::MultiSigAddressValidationParameters,
+            node_address::NodeAddress,
+            node_to_addresses_connection::NodeToAddressesConnection,
+            outgoing_payment::OutgoingPayment,
+            outgoing_payment_attempt::OutgoingPaymentAttempt,
+            outgoing_payment_attempt_to_hops_connection::OutgoingPaymentAttemptToHopsConnection,
+            outgoing_payment_for_idempotency_key_output::OutgoingPaymentForIdempotencyKeyOutput,
+            outgoing_payment_to_attempts_connection::OutgoingPaymentToAttemptsConnection,
+            outgoing_payments_for_invoice_query_output::OutgoingPaymentsForInvoiceQueryOutput,
+            outgoing_payments_for_payment_hash_query_output::OutgoingPaymentsForPaymentHashQueryOutput,
+            page_info::PageInfo,
+            pay_invoice_output::PayInvoiceOutput,
+            post_transaction_data::PostTransactionData,
+            register_payment_output::RegisterPaymentOutput,
+            release_channel_per_commitment_secret_output::ReleaseChannelPerCommitmentSecretOutput,
+            release_payment_preimage_output::ReleasePaymentPreimageOutput,
+            request_withdrawal_output::RequestWithdrawalOutput,
+            rich_text::RichText,
+            routing_transaction::RoutingTransaction,
+            screen_node_output::ScreenNodeOutput,
+            secret::Secret,
+            send_payment_output::SendPaymentOutput,
+            set_invoice_payment_hash_output::SetInvoicePaymentHashOutput,
+            sign_invoice_output::SignInvoiceOutput,
+            sign_messages_output::SignMessagesOutput,
+            signable::Signable,
+            signable_payload::SignablePayload,
+            uma_invitation::UmaInvitation,
+            update_channel_per_commitment_point_output::UpdateChannelPerCommitmentPointOutput,
+            update_node_shared_secret_output::UpdateNodeSharedSecretOutput,
+            wallet::Wallet,
+            wallet_to_payment_requests_connection::WalletToPaymentRequestsConnection,
+            wallet_to_transactions_connection::WalletToTransactionsConnection,
+            wallet_to_withdrawal_requests_connection::WalletToWithdrawalRequestsConnection,
+            withdrawal::Withdrawal,
+            withdrawal_fee_estimate_output::WithdrawalFeeEstimateOutput,
+            withdrawal_request::WithdrawalRequest,
+            withdrawal_request_to_channel_closing_transactions_connection::WithdrawalRequestToChannelClosingTransactionsConnection,
+            withdrawal_request_to_channel_opening_transactions_connection::WithdrawalRequestToChannelOpeningTransactionsConnection,
+            withdrawal_request_to_withdrawals_connection::WithdrawalRequestToWithdrawalsConnection,
+        );
+    }
+}
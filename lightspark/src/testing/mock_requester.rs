@@ -0,0 +1,336 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::{ApiError, Error, GraphqlErrorDetail};
+use crate::key::OperationSigningKey;
+use crate::request::requester::operation_name;
+use crate::types::graphql_requester::{GraphQLRequester, SigningGraphQLRequester};
+
+/// A `SigningGraphQLRequester` answering operations with canned responses instead of calling the
+/// Lightspark API, for unit tests of code built on `LightsparkClient`.
+///
+/// Responses are matched by operation name and, optionally, by variables. A response registered
+/// with variables only matches requests containing all of those variables with the same values.
+/// When several responses match, the one registered last wins. Every request is recorded and can
+/// be inspected with `requests`.
+///
+/// ```
+/// use lightspark::client::LightsparkClient;
+/// use lightspark::key::RSASigningKey;
+/// use lightspark::testing::{fixtures, mock_requester::MockRequester};
+/// use serde_json::json;
+///
+/// let requester = MockRequester::new().respond(
+///     "OutgoingPaymentForIdempotencyKey",
+///     json!({
+///         "outgoing_payment_for_idempotency_key": {
+///             "payment": fixtures::object("OutgoingPayment"),
+///         }
+///     }),
+/// );
+/// let client = LightsparkClient::<RSASigningKey, _>::with_requester(requester);
+/// ```
+#[derive(Default)]
+pub struct MockRequester {
+    responses: Vec<MockResponse>,
+    requests: Mutex<Vec<RecordedRequest>>,
+}
+
+struct MockResponse {
+    operation_name: String,
+    variables: Option<Value>,
+    result: Result<Value, ApiError>,
+}
+
+/// A request received by a `MockRequester`.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub operation_name: Option<String>,
+    pub operation: String,
+    pub variables: Option<Value>,
+
+    /// Whether the request was sent with a signing key.
+    pub signed: bool,
+}
+
+/// The content of a fixture file. `response` has the shape of a graphql response, i.e. it holds
+/// either `data` or `errors`.
+#[derive(Deserialize)]
+struct FixtureFile {
+    operation_name: String,
+    variables: Option<Value>,
+    response: FixtureResponse,
+}
+
+#[derive(Deserialize)]
+struct FixtureResponse {
+    data: Option<Value>,
+    errors: Option<Vec<GraphqlErrorDetail>>,
+}
+
+impl MockRequester {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every request for the given operation with the given `data`.
+    pub fn respond(self, operation_name: &str, data: Value) -> Self {
+        self.add(operation_name, None, Ok(data))
+    }
+
+    /// Answers requests for the given operation with the given `data`, if the request contains
+    /// all of the given variables.
+    pub fn respond_with_variables(
+        self,
+        operation_name: &str,
+        variables: Value,
+        data: Value,
+    ) -> Self {
+        self.add(operation_name, Some(variables), Ok(data))
+    }
+
+    /// Fails every request for the given operation with the given API error.
+    pub fn fail(self, operation_name: &str, error: ApiError) -> Self {
+        self.add(operation_name, None, Err(error))
+    }
+
+    /// Fails requests for the given operation with the given API error, if the request contains
+    /// all of the given variables.
+    pub fn fail_with_variables(
+        self,
+        operation_name: &str,
+        variables: Value,
+        error: ApiError,
+    ) -> Self {
+        self.add(operation_name, Some(variables), Err(error))
+    }
+
+    /// Loads a response from a json file of the form
+    /// `{"operation_name": "...", "variables": {...}, "response": {"data": {...}}}`, where
+    /// `variables` is optional and `response` may hold `errors` instead of `data`.
+    pub fn load_fixture<P: AsRef<Path>>(self, path: P) -> Result<Self, Error> {
+        let content = fs::read_to_string(path.as_ref()).map_err(|err| {
+            Error::InvalidArgumentError(format!("{}: {}", path.as_ref().display(), err))
+        })?;
+        let fixture: FixtureFile = serde_json::from_str(&content).map_err(Error::JsonError)?;
+        let result = match (fixture.response.errors, fixture.response.data) {
+            (Some(errors), _) => Err(ApiError {
+                status: 200,
+                errors,
+            }),
+            (None, Some(data)) => Ok(data),
+            (None, None) => {
+                return Err(Error::InvalidArgumentError(format!(
+                    "{}: response has neither data nor errors",
+                    path.as_ref().display()
+                )))
+            }
+        };
+        Ok(self.add(&fixture.operation_name, fixture.variables, result))
+    }
+
+    /// Loads every `.json` file of the given directory with `load_fixture`, in file name order.
+    pub fn load_fixture_dir<P: AsRef<Path>>(mut self, path: P) -> Result<Self, Error> {
+        let entries = fs::read_dir(path.as_ref()).map_err(|err| {
+            Error::InvalidArgumentError(format!("{}: {}", path.as_ref().display(), err))
+        })?;
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect::<Vec<_>>();
+        paths.sort();
+        for path in paths {
+            self = self.load_fixture(path)?;
+        }
+        Ok(self)
+    }
+
+    /// The requests received so far, oldest first.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn add(
+        mut self,
+        operation_name: &str,
+        variables: Option<Value>,
+        result: Result<Value, ApiError>,
+    ) -> Self {
+        self.responses.push(MockResponse {
+            operation_name: operation_name.to_owned(),
+            variables,
+            result,
+        });
+        self
+    }
+
+    fn answer(
+        &self,
+        operation: &str,
+        variables: Option<Value>,
+        signed: bool,
+    ) -> Result<Value, Error> {
+        let name = operation_name(operation)?;
+        self.requests.lock().unwrap().push(RecordedRequest {
+            operation_name: name.clone(),
+            operation: operation.to_owned(),
+            variables: variables.clone(),
+            signed,
+        });
+
+        let name = name.unwrap_or_default();
+        let response = self
+            .responses
+            .iter()
+            .rev()
+            .find(|response| {
+                response.operation_name == name
+                    && response
+                        .variables
+                        .as_ref()
+                        .is_none_or(|expected| contains(variables.as_ref(), expected))
+            })
+            .ok_or_else(|| {
+                Error::GraphqlError(format!("no mock response for operation {}", name))
+            })?;
+        response.result.clone().map_err(Error::ApiError)
+    }
+}
+
+/// Whether all the entries of `expected` are present in `variables` with the same value.
+fn contains(variables: Option<&Value>, expected: &Value) -> bool {
+    match expected.as_object() {
+        Some(expected) => expected
+            .iter()
+            .all(|(key, value)| variables.and_then(|vars| vars.get(key)) == Some(value)),
+        None => variables == Some(expected),
+    }
+}
+
+#[async_trait]
+impl GraphQLRequester for MockRequester {
+    async fn execute_graphql(
+        &self,
+        operation: &str,
+        variables: Option<Value>,
+    ) -> Result<Value, Error> {
+        self.answer(operation, variables, false)
+    }
+}
+
+#[async_trait]
+impl SigningGraphQLRequester for MockRequester {
+    async fn execute_graphql_signing<K: OperationSigningKey>(
+        &self,
+        operation: &str,
+        variables: Option<Value>,
+        signing_key: Option<K>,
+    ) -> Result<Value, Error> {
+        self.answer(operation, variables, signing_key.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::LightsparkClient;
+    use crate::key::RSASigningKey;
+    use crate::testing::fixtures;
+    use serde_json::json;
+
+    const QUERY: &str = "query GetThing($id: ID!) { thing(id: $id) { id } }";
+
+    #[tokio::test]
+    async fn test_matches_operation_and_variables() {
+        let requester = MockRequester::new()
+            .respond("GetThing", json!({"thing": {"id": "default"}}))
+            .respond_with_variables(
+                "GetThing",
+                json!({"id": "b"}),
+                json!({"thing": {"id": "b"}}),
+            );
+
+        let a = requester
+            .execute_graphql(QUERY, Some(json!({"id": "a"})))
+            .await
+            .unwrap();
+        assert_eq!(a["thing"]["id"], "default");
+        let b = requester
+            .execute_graphql(QUERY, Some(json!({"id": "b"})))
+            .await
+            .unwrap();
+        assert_eq!(b["thing"]["id"], "b");
+
+        let error = requester
+            .execute_graphql("query Other { other }", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::GraphqlError(_)));
+
+        let requests = requester.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[1].operation_name.as_deref(), Some("GetThing"));
+        assert_eq!(requests[1].variables, Some(json!({"id": "b"})));
+        assert!(!requests[1].signed);
+    }
+
+    #[tokio::test]
+    async fn test_load_fixture() {
+        let dir = std::env::temp_dir().join(format!("lightspark-mock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("get_thing.json"),
+            r#"{"operation_name": "GetThing", "response": {"data": {"thing": {"id": "a"}}}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("pay_invoice.json"),
+            r#"{
+                "operation_name": "PayInvoice",
+                "response": {"errors": [{"message": "Insufficient balance"}]}
+            }"#,
+        )
+        .unwrap();
+        let requester = MockRequester::new().load_fixture_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let thing = requester.execute_graphql(QUERY, None).await.unwrap();
+        assert_eq!(thing["thing"]["id"], "a");
+        let error = requester
+            .execute_graphql("mutation PayInvoice { pay_invoice { id } }", None)
+            .await
+            .unwrap_err();
+        assert!(error.is_insufficient_balance());
+    }
+
+    #[tokio::test]
+    async fn test_client_with_mock_requester() {
+        let requester = MockRequester::new().respond(
+            "OutgoingPaymentForIdempotencyKey",
+            json!({
+                "outgoing_payment_for_idempotency_key": {
+                    "payment": fixtures::object("OutgoingPayment"),
+                }
+            }),
+        );
+        let client = LightsparkClient::<RSASigningKey, _>::with_requester(requester);
+
+        let payment = client
+            .outgoing_payment_for_idempotency_key("key")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(payment.amount.original_value, 1000);
+        assert_eq!(
+            client.requester.requests()[0].variables,
+            Some(json!({"idempotency_key": "key"}))
+        );
+    }
+}
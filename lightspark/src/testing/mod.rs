@@ -0,0 +1,4 @@
+// Copyright ©, 2023-present, Lightspark Group, Inc. - All Rights Reserved
+
+pub mod fixtures;
+pub mod mock_requester;
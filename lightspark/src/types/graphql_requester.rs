@@ -2,6 +2,7 @@ use async_trait::async_trait;
 use serde_json::Value;
 
 use crate::error::Error;
+use crate::key::OperationSigningKey;
 
 #[async_trait]
 pub trait GraphQLRequester: Send + Sync {
@@ -11,3 +12,18 @@ pub trait GraphQLRequester: Send + Sync {
         variables: Option<Value>,
     ) -> Result<Value, Error>;
 }
+
+/// A `GraphQLRequester` that can also sign operations with a node's signing key, which is
+/// required for mutations that move funds.
+///
+/// `LightsparkClient` is generic over this trait, so a stub, caching or instrumented transport
+/// can be used in place of the default `Requester`.
+#[async_trait]
+pub trait SigningGraphQLRequester: GraphQLRequester {
+    async fn execute_graphql_signing<K: OperationSigningKey>(
+        &self,
+        operation: &str,
+        variables: Option<Value>,
+        signing_key: Option<K>,
+    ) -> Result<Value, Error>;
+}